@@ -0,0 +1,310 @@
+/*
+   TU - Time's Up!
+   Copyright (C) 2020  Julio Biason
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Sorting and grouping events for display.
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+use chrono::prelude::*;
+use chrono::Duration;
+
+use crate::clock::Clock;
+use crate::event::Event;
+
+/// What to sort events by.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum SortKey {
+    /// When the event is due.
+    #[default]
+    Due,
+    /// The event description, ignoring case.
+    Description,
+    /// When the event was created; events with unknown creation come first.
+    Created,
+    /// When the event was last changed; events never changed as far as
+    /// known come first.
+    Updated,
+    /// How important the event is, the most important first.
+    Priority,
+}
+
+/// How to group events.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GroupBy {
+    /// The day the event is due.
+    Day,
+    /// The week (Monday to Sunday) the event is due.
+    Week,
+    /// The month the event is due.
+    Month,
+    /// The event tags; events with more than one tag appear in each of them.
+    Tag,
+}
+
+/// A group of events, with a title for it.
+#[derive(Debug)]
+pub struct Group<'a> {
+    /// Title of the group, like "This week" or the tag name.
+    pub title: String,
+    /// Events in the group, in the same order they were given.
+    pub events: Vec<&'a Event>,
+}
+
+/// Sort the events by the key; events with the same key keep their order.
+pub fn sort(events: &mut [&Event], key: SortKey, reverse: bool) {
+    match key {
+        SortKey::Due => events.sort_by_key(|event| event.due.timestamp()),
+        SortKey::Description => events.sort_by_key(|event| event.description.to_lowercase()),
+        SortKey::Created => {
+            events.sort_by_key(|event| event.created_at.map(|created| created.timestamp()))
+        }
+        SortKey::Updated => {
+            events.sort_by_key(|event| event.updated_at.map(|updated| updated.timestamp()))
+        }
+        SortKey::Priority => events.sort_by_key(|event| Reverse(event.priority)),
+    }
+    if reverse {
+        events.reverse();
+    }
+}
+
+/// Split the events in groups. Groups are in chronological order (or
+/// alphabetical, for tags, with untagged events last), reversed if asked.
+pub fn group<'a>(
+    events: &[&'a Event],
+    by: GroupBy,
+    clock: &dyn Clock,
+    reverse: bool,
+) -> Vec<Group<'a>> {
+    let mut groups = match by {
+        GroupBy::Tag => group_by_tag(events),
+        _ => group_by_date(events, by, clock.today()),
+    };
+    if reverse {
+        groups.reverse();
+    }
+    groups
+}
+
+fn group_by_tag<'a>(events: &[&'a Event]) -> Vec<Group<'a>> {
+    let mut tagged: BTreeMap<&str, Vec<&Event>> = BTreeMap::new();
+    let mut untagged = Vec::new();
+    for event in events {
+        if event.tags.is_empty() {
+            untagged.push(*event);
+        }
+        for tag in &event.tags {
+            tagged.entry(tag).or_default().push(*event);
+        }
+    }
+
+    let mut groups: Vec<Group> = tagged
+        .into_iter()
+        .map(|(tag, events)| Group {
+            title: tag.into(),
+            events,
+        })
+        .collect();
+    if !untagged.is_empty() {
+        groups.push(Group {
+            title: "No tag".into(),
+            events: untagged,
+        });
+    }
+    groups
+}
+
+fn group_by_date<'a>(events: &[&'a Event], by: GroupBy, today: NaiveDate) -> Vec<Group<'a>> {
+    let mut grouped: BTreeMap<NaiveDate, Vec<&Event>> = BTreeMap::new();
+    for event in events {
+        let start = period_start(event.due.date().0, by);
+        grouped.entry(start).or_default().push(*event);
+    }
+    grouped
+        .into_iter()
+        .map(|(start, events)| Group {
+            title: period_title(start, by, today),
+            events,
+        })
+        .collect()
+}
+
+/// The first day of the period the date is in.
+fn period_start(date: NaiveDate, by: GroupBy) -> NaiveDate {
+    match by {
+        GroupBy::Week => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+        GroupBy::Month => date.with_day(1).unwrap_or(date),
+        _ => date,
+    }
+}
+
+fn period_title(start: NaiveDate, by: GroupBy, today: NaiveDate) -> String {
+    let current = period_start(today, by);
+    match by {
+        GroupBy::Week => match (start - current).num_days() {
+            0 => "This week".into(),
+            7 => "Next week".into(),
+            -7 => "Last week".into(),
+            _ => format!("Week of {}", start.format("%Y-%m-%d")),
+        },
+        GroupBy::Month => {
+            let months = |date: NaiveDate| date.year() * 12 + date.month() as i32;
+            match months(start) - months(current) {
+                0 => "This month".into(),
+                1 => "Next month".into(),
+                -1 => "Last month".into(),
+                _ => start.format("%B %Y").to_string(),
+            }
+        }
+        _ => match (start - current).num_days() {
+            0 => "Today".into(),
+            1 => "Tomorrow".into(),
+            -1 => "Yesterday".into(),
+            _ => start.format("%a %Y-%m-%d").to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod agenda_test {
+    use super::group;
+    use super::sort;
+    use super::GroupBy;
+    use super::SortKey;
+    use crate::clock::FixedClock;
+    use crate::datetime::DateTime;
+    use crate::event::Event;
+    use crate::priority::Priority;
+
+    fn tagged(id: &str, description: &str, month: u8, day: u8, tags: &[&str]) -> Event {
+        let mut event = Event::on_day(id, description, 2020, month, day);
+        event.tags = tags.iter().map(|tag| tag.to_string()).collect();
+        event
+    }
+
+    fn events() -> Vec<Event> {
+        vec![
+            tagged("1", "beta", 6, 9, &["work"]),
+            tagged("2", "Alpha", 6, 10, &[]),
+            tagged("3", "gamma", 6, 16, &["home", "work"]),
+            tagged("4", "delta", 7, 30, &["home"]),
+        ]
+    }
+
+    fn ids(events: &[&Event]) -> Vec<String> {
+        events.iter().map(|event| event.id.clone()).collect()
+    }
+
+    #[test]
+    pub fn sort_by_description() {
+        let events = events();
+        let mut refs: Vec<&Event> = events.iter().collect();
+        sort(&mut refs, SortKey::Description, false);
+        assert_eq!(ids(&refs), vec!["2", "1", "4", "3"]);
+        sort(&mut refs, SortKey::Due, true);
+        assert_eq!(ids(&refs), vec!["4", "3", "2", "1"]);
+    }
+
+    #[test]
+    pub fn sort_by_created() {
+        let mut events = events();
+        events[0].created_at = Some(DateTime::new(2020, 6, 2, 10, 0).unwrap());
+        events[1].created_at = Some(DateTime::new(2020, 6, 1, 10, 0).unwrap());
+        let mut refs: Vec<&Event> = events.iter().collect();
+        sort(&mut refs, SortKey::Created, false);
+        assert_eq!(ids(&refs), vec!["3", "4", "2", "1"]);
+    }
+
+    #[test]
+    pub fn sort_by_priority() {
+        let mut events = events();
+        events[1].priority = Priority::High;
+        events[2].priority = Priority::Low;
+        events[3].priority = Priority::Critical;
+        let mut refs: Vec<&Event> = events.iter().collect();
+        sort(&mut refs, SortKey::Priority, false);
+        assert_eq!(ids(&refs), vec!["4", "2", "1", "3"]);
+    }
+
+    #[test]
+    pub fn group_by_day() {
+        let events = events();
+        let refs: Vec<&Event> = events.iter().collect();
+        let titles: Vec<String> = group(
+            &refs,
+            GroupBy::Day,
+            &FixedClock::at(2020, 6, 10, 12, 0),
+            false,
+        )
+        .into_iter()
+        .map(|group| group.title)
+        .collect();
+        assert_eq!(
+            titles,
+            vec!["Yesterday", "Today", "Tue 2020-06-16", "Thu 2020-07-30"]
+        );
+    }
+
+    #[test]
+    pub fn group_by_week() {
+        let events = events();
+        let refs: Vec<&Event> = events.iter().collect();
+        let groups = group(
+            &refs,
+            GroupBy::Week,
+            &FixedClock::at(2020, 6, 10, 12, 0),
+            false,
+        );
+        assert_eq!(groups[0].title, "This week");
+        assert_eq!(ids(&groups[0].events), vec!["1", "2"]);
+        assert_eq!(groups[1].title, "Next week");
+        assert_eq!(groups[2].title, "Week of 2020-07-27");
+    }
+
+    #[test]
+    pub fn group_by_month() {
+        let events = events();
+        let refs: Vec<&Event> = events.iter().collect();
+        let groups = group(
+            &refs,
+            GroupBy::Month,
+            &FixedClock::at(2020, 6, 10, 12, 0),
+            true,
+        );
+        assert_eq!(groups[0].title, "Next month");
+        assert_eq!(groups[1].title, "This month");
+        assert_eq!(ids(&groups[1].events), vec!["1", "2", "3"]);
+    }
+
+    #[test]
+    pub fn group_by_tag() {
+        let events = events();
+        let refs: Vec<&Event> = events.iter().collect();
+        let groups = group(
+            &refs,
+            GroupBy::Tag,
+            &FixedClock::at(2020, 6, 10, 12, 0),
+            false,
+        );
+        let titles: Vec<&str> = groups.iter().map(|group| group.title.as_str()).collect();
+        assert_eq!(titles, vec!["home", "work", "No tag"]);
+        assert_eq!(ids(&groups[0].events), vec!["3", "4"]);
+        assert_eq!(ids(&groups[1].events), vec!["1", "3"]);
+    }
+}
@@ -16,79 +16,386 @@
    along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
 use clap::crate_authors;
 use clap::crate_description;
 use clap::crate_name;
 use clap::crate_version;
-use clap::App;
 use clap::Arg;
 use clap::ArgMatches;
-use clap::SubCommand;
+use clap::Command;
+
+use tu::agenda::GroupBy;
+use tu::agenda::SortKey;
+use tu::caldav::Policy;
+use tu::config::Backend;
+use tu::csvfile::Column;
+use tu::csvfile::DateFormats;
+use tu::csvfile::ImportOptions;
+use tu::filter::parse_duration;
+use tu::filter::Filter;
+use tu::filter::FilterError;
+use tu::report;
+use tu::search::SearchMode;
+use tu::template::Template;
+use tu::template::TemplateError;
+use tu::Date;
+use tu::DateError;
+use tu::DateTime;
+use tu::NewEvent;
+use tu::Priority;
 
-use crate::date::Date;
-use crate::date_errors::DateError;
-use crate::datetime::DateTime;
+use crate::render::ColorChoice;
 
-type Description = String;
 type Id = String;
 
+#[derive(Debug)]
 pub enum ParseError {
-    InvalidDate,
+    InvalidDate(DateError),
+    InvalidDuration(String),
     UnknownOption,
     MissingDescription,
     MissingDate,
     MissingEventId,
+    MissingBackend,
+    MissingQuery,
+    InvalidWeeks,
+    MissingFile,
+    InvalidTemplate(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::InvalidDate(error) => write!(f, "{}", error),
+            ParseError::InvalidDuration(reason) => write!(f, "{}", reason),
+            ParseError::UnknownOption => write!(f, "unknown option"),
+            ParseError::MissingDescription => write!(f, "missing event description"),
+            ParseError::MissingDate => write!(f, "missing event date"),
+            ParseError::MissingEventId => write!(f, "missing event ID"),
+            ParseError::MissingBackend => write!(f, "missing storage backend"),
+            ParseError::MissingQuery => write!(f, "missing search query"),
+            ParseError::InvalidWeeks => write!(f, "the number of weeks must be a number"),
+            ParseError::MissingFile => write!(f, "missing file name"),
+            ParseError::InvalidTemplate(reason) => write!(f, "{}", reason),
+        }
+    }
 }
 
 impl From<DateError> for ParseError {
-    fn from(_: DateError) -> ParseError {
-        ParseError::InvalidDate
+    fn from(error: DateError) -> ParseError {
+        ParseError::InvalidDate(error)
+    }
+}
+
+impl From<FilterError> for ParseError {
+    fn from(error: FilterError) -> ParseError {
+        ParseError::InvalidDuration(error.to_string())
+    }
+}
+
+impl From<TemplateError> for ParseError {
+    fn from(error: TemplateError) -> ParseError {
+        ParseError::InvalidTemplate(error.to_string())
     }
 }
 
+/// Names of the priorities, for the options that take one.
+static PRIORITIES: [&str; 4] = ["low", "normal", "high", "critical"];
+
+/// Template used by `tu next` when none is given.
+static NEXT_FORMAT: &str = "{description} ({eta})";
+
+/// How to present the event list.
+#[derive(Debug, Default)]
+pub struct ListOptions {
+    /// Only events passing all the filters are listed.
+    pub filters: Vec<Filter>,
+    /// List the events that are done too.
+    pub all: bool,
+    pub sort: SortKey,
+    pub reverse: bool,
+    pub group: Option<GroupBy>,
+    /// Show each event through this template instead of the table.
+    pub template: Option<Template>,
+}
+
+/// What `tu next` prints.
+#[derive(Debug)]
+pub struct NextOptions {
+    pub template: Template,
+    /// Reuse the last result if it is younger than this.
+    pub max_age: Option<Duration>,
+    pub output: NextOutput,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum NextOutput {
+    /// Just the template.
+    Plain,
+    /// JSON for waybar custom modules.
+    Waybar,
+    /// A JSON block of the i3bar protocol.
+    I3bar,
+}
+
+/// Where and how `tu export` writes the events.
+#[derive(Debug)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    pub formats: DateFormats,
+    /// File to write to; the standard output if not set.
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    /// The same layout as the events of `tu serve`.
+    Json,
+}
+
+/// What `tu report` writes.
+#[derive(Debug)]
+pub struct ReportOptions {
+    pub format: ReportFormat,
+    pub title: String,
+    /// File to write to; the standard output if not set.
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+/// How `tu caldav` synchronizes; anything not set comes from the
+/// configuration.
+#[derive(Debug)]
+pub struct CalDavOptions {
+    pub url: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub policy: Option<Policy>,
+}
+
 #[derive(Debug)]
 pub enum Action {
-    List,
-    Add(Description, Date),
-    AddWithTime(Description, DateTime),
+    List(ListOptions),
+    Add(NewEvent),
     RemoveById(Id),
-    RemoveOutdated,
+    /// Remove the events that are over; only the ones less important than
+    /// the priority, if there is one.
+    RemoveOutdated(Option<Priority>),
+    Done(Id),
+    Undone(Id),
+    Migrate(Backend),
+    Search(String, SearchMode),
+    Calendar(CalendarView),
+    Tui,
+    Next(NextOptions),
+    Export(ExportOptions),
+    /// Import the file in the path, `-` being the standard input.
+    Import(String, ImportOptions),
+    Report(ReportOptions),
+    Stats,
+    /// Answer the HTTP API on the address, to the web pages of the origins.
+    Serve(String, Vec<String>),
+    CalDav(CalDavOptions),
+    Sync,
+    /// Merge the event files: base, ours (where the result goes) and
+    /// theirs.
+    Merge(PathBuf, PathBuf, PathBuf),
+}
+
+#[derive(Debug)]
+pub enum CalendarView {
+    /// A month; the current one if not set.
+    Month(Option<(u16, u8)>),
+    /// This many weeks, starting in the current one.
+    Weeks(u16),
+}
+
+#[derive(Debug)]
+pub struct Arguments {
+    pub action: Action,
+    pub color: ColorChoice,
+    /// Moment to use as "now", instead of the system clock.
+    pub now: Option<DateTime>,
 }
 
-pub fn parse() -> Result<Action, ParseError> {
-    let params = App::new(crate_name!())
+pub fn parse() -> Result<Arguments, ParseError> {
+    let params = Command::new(crate_name!())
         .version(crate_version!())
         .author(crate_authors!())
         .about(crate_description!())
+        .arg(
+            Arg::new("now")
+                .long("now")
+                .env("TU_NOW")
+                .takes_value(true)
+                .global(true)
+                .hide(true)
+                .value_name("YYYY-MM-DD[THH:MM]")
+                .help("Pretend the current time is this one"),
+        )
+        .arg(
+            Arg::new("color")
+                .long("color")
+                .takes_value(true)
+                .global(true)
+                .possible_values(["always", "never", "auto"])
+                .default_value("auto")
+                .value_name("WHEN")
+                .help("Use colors; auto uses them on terminals, unless NO_COLOR is set"),
+        )
+        .arg(
+            Arg::new("within")
+                .long("within")
+                .takes_value(true)
+                .value_name("DURATION")
+                .help("Only events due in this time from now, like 12h, 7d or 2w"),
+        )
+        .arg(
+            Arg::new("after")
+                .long("after")
+                .takes_value(true)
+                .value_name("YYYY-MM-DD")
+                .help("Only events due on this date or later"),
+        )
+        .arg(
+            Arg::new("before")
+                .long("before")
+                .takes_value(true)
+                .value_name("YYYY-MM-DD")
+                .help("Only events due on this date or earlier"),
+        )
+        .arg(
+            Arg::new("overdue")
+                .long("overdue")
+                .takes_value(false)
+                .help("Only events that are already over"),
+        )
+        .arg(
+            Arg::new("today")
+                .long("today")
+                .takes_value(false)
+                .help("Only events due today"),
+        )
+        .arg(
+            Arg::new("this-week")
+                .long("this-week")
+                .takes_value(false)
+                .help("Only events due this week"),
+        )
+        .arg(
+            Arg::new("priority")
+                .long("priority")
+                .takes_value(true)
+                .possible_values(PRIORITIES)
+                .value_name("PRIORITY")
+                .help("Only events at least this important"),
+        )
+        .arg(
+            Arg::new("all")
+                .long("all")
+                .takes_value(false)
+                .help("Also list the events that are done"),
+        )
+        .arg(
+            Arg::new("sort")
+                .long("sort")
+                .takes_value(true)
+                .possible_values(["due", "description", "created", "updated", "priority"])
+                .value_name("KEY")
+                .help(
+                    "Sort the events by due date (default), description, creation, last change \
+                     or priority (the most important first)",
+                ),
+        )
+        .arg(
+            Arg::new("reverse")
+                .long("reverse")
+                .takes_value(false)
+                .help("Reverse the order of the events"),
+        )
+        .arg(
+            Arg::new("group")
+                .long("group")
+                .takes_value(true)
+                .possible_values(["day", "week", "month", "tag"])
+                .value_name("PERIOD")
+                .help("Group the events by due day, week, month or by tag"),
+        )
+        .arg(
+            Arg::new("template")
+                .long("template")
+                .takes_value(true)
+                .value_name("TEMPLATE")
+                .help(
+                    "Show each event through a template, like '{id}\\t{eta_days}\\t{description}'",
+                ),
+        )
+        .arg(
+            Arg::new("template-file")
+                .long("template-file")
+                .takes_value(true)
+                .value_name("FILE")
+                .conflicts_with("template")
+                .help("Show each event through the template in the file"),
+        )
         .subcommand(
-            SubCommand::with_name("add")
+            Command::new("add")
                 .about("Add a new event")
                 .arg(
-                    Arg::with_name("date")
+                    Arg::new("date")
                         .required(true)
                         .takes_value(true)
                         .help("Date for the event, in YYYY-MM-DD format"),
                 )
                 .arg(
-                    Arg::with_name("description")
+                    Arg::new("description")
                         .required(true)
                         .takes_value(true)
                         .help("Event description"),
                 )
                 .arg(
-                    Arg::with_name("time")
-                        .short("t")
+                    Arg::new("time")
+                        .short('t')
                         .long("time")
                         .takes_value(true)
                         .required(false)
                         .help("Time for the event"),
+                )
+                .arg(
+                    Arg::new("tag")
+                        .long("tag")
+                        .takes_value(true)
+                        .multiple_occurrences(true)
+                        .value_name("TAG")
+                        .help("Tag the event; can be used more than once"),
+                )
+                .arg(
+                    Arg::new("priority")
+                        .short('p')
+                        .long("priority")
+                        .takes_value(true)
+                        .possible_values(PRIORITIES)
+                        .default_value("normal")
+                        .value_name("PRIORITY")
+                        .help("How important the event is"),
                 ),
         )
         .subcommand(
-            SubCommand::with_name("rm")
+            Command::new("rm")
                 .about("Remove an event")
                 .arg(
-                    Arg::with_name("id")
+                    Arg::new("id")
                         .takes_value(true)
                         .required(false)
                         .conflicts_with("outdated")
@@ -96,22 +403,458 @@ pub fn parse() -> Result<Action, ParseError> {
                         .help("Remove a specific event by its ID"),
                 )
                 .arg(
-                    Arg::with_name("outdated")
-                        .short("o")
+                    Arg::new("outdated")
+                        .short('o')
                         .long("outdated")
                         .takes_value(false)
                         .required(false)
                         .conflicts_with("id"),
+                )
+                .arg(
+                    Arg::new("below")
+                        .long("below")
+                        .takes_value(true)
+                        .requires("outdated")
+                        .possible_values(PRIORITIES)
+                        .value_name("PRIORITY")
+                        .help("With --outdated, keep the events at least this important"),
+                ),
+        )
+        .subcommand(
+            Command::new("done")
+                .about("Mark an event as done")
+                .arg(
+                    Arg::new("id")
+                        .required(true)
+                        .takes_value(true)
+                        .value_name("ID")
+                        .help("ID of the event"),
+                ),
+        )
+        .subcommand(
+            Command::new("undone")
+                .about("Mark a done event as not done")
+                .arg(
+                    Arg::new("id")
+                        .required(true)
+                        .takes_value(true)
+                        .value_name("ID")
+                        .help("ID of the event"),
+                ),
+        )
+        .subcommand(
+            Command::new("next")
+                .about("Show the nearest upcoming event, for prompts and status bars")
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .takes_value(true)
+                        .value_name("TEMPLATE")
+                        .help(
+                            "How to show the event, like '{description} in {eta}'; \
+                             fields are id, description, eta, date, time and tags",
+                        ),
+                )
+                .arg(
+                    Arg::new("max-age")
+                        .long("max-age")
+                        .takes_value(true)
+                        .value_name("DURATION")
+                        .help("Reuse the last result if it is younger than this, like 30s or 5m"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .takes_value(true)
+                        .possible_values(["plain", "waybar", "i3bar"])
+                        .default_value("plain")
+                        .help("Output plain text or JSON for waybar or i3bar"),
+                ),
+        )
+        .subcommand(
+            Command::new("search")
+                .about("Search events by their description or tags")
+                .arg(
+                    Arg::new("query")
+                        .required(true)
+                        .takes_value(true)
+                        .help("What to search for"),
+                )
+                .arg(
+                    Arg::new("regex")
+                        .short('r')
+                        .long("regex")
+                        .takes_value(false)
+                        .conflicts_with("fuzzy")
+                        .help("The query is a regular expression"),
+                )
+                .arg(
+                    Arg::new("fuzzy")
+                        .short('f')
+                        .long("fuzzy")
+                        .takes_value(false)
+                        .conflicts_with("regex")
+                        .help("Match the letters of the query in order, even if apart"),
+                ),
+        )
+        .subcommand(
+            Command::new("cal")
+                .about("Show the events in a calendar")
+                .arg(
+                    Arg::new("month")
+                        .takes_value(true)
+                        .required(false)
+                        .conflicts_with("weeks")
+                        .value_name("YYYY-MM")
+                        .help("Month to show; the current one if not set"),
+                )
+                .arg(
+                    Arg::new("weeks")
+                        .long("weeks")
+                        .takes_value(true)
+                        .value_name("N")
+                        .help("Show N weeks, starting in the current one"),
+                ),
+        )
+        .subcommand(Command::new("tui").about("Browse and change the events in a full screen view"))
+        .subcommand(
+            Command::new("export")
+                .about("Write all events to a file")
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(["csv", "json"])
+                        .default_value("csv")
+                        .help("Format of the file"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("File to write; the standard output if not set"),
+                )
+                .arg(
+                    Arg::new("date-format")
+                        .long("date-format")
+                        .takes_value(true)
+                        .value_name("FORMAT")
+                        .help("Format of the dates, like %d/%m/%Y; YYYY-MM-DD if not set"),
+                )
+                .arg(
+                    Arg::new("time-format")
+                        .long("time-format")
+                        .takes_value(true)
+                        .value_name("FORMAT")
+                        .help("Format of the times, like %I:%M %p; HH:MM if not set"),
+                ),
+        )
+        .subcommand(
+            Command::new("import")
+                .about("Add the events in a CSV file")
+                .arg(
+                    Arg::new("file")
+                        .required(true)
+                        .takes_value(true)
+                        .help("CSV file to read; - for the standard input"),
+                )
+                .arg(
+                    Arg::new("date-col")
+                        .long("date-col")
+                        .takes_value(true)
+                        .value_name("COLUMN")
+                        .help("Name or number of the column with the dates [default: date]"),
+                )
+                .arg(
+                    Arg::new("time-col")
+                        .long("time-col")
+                        .takes_value(true)
+                        .value_name("COLUMN")
+                        .help("Name or number of the column with the times [default: time, if present]"),
+                )
+                .arg(
+                    Arg::new("desc-col")
+                        .long("desc-col")
+                        .takes_value(true)
+                        .value_name("COLUMN")
+                        .help("Name or number of the column with the descriptions [default: description]"),
+                )
+                .arg(
+                    Arg::new("tags-col")
+                        .long("tags-col")
+                        .takes_value(true)
+                        .value_name("COLUMN")
+                        .help("Name or number of the column with the tags [default: tags, if present]"),
+                )
+                .arg(
+                    Arg::new("no-header")
+                        .long("no-header")
+                        .takes_value(false)
+                        .help("The first line is an event, not the column names"),
+                )
+                .arg(
+                    Arg::new("date-format")
+                        .long("date-format")
+                        .takes_value(true)
+                        .value_name("FORMAT")
+                        .help("Format of the dates, like %d/%m/%Y; YYYY-MM-DD if not set"),
+                )
+                .arg(
+                    Arg::new("time-format")
+                        .long("time-format")
+                        .takes_value(true)
+                        .value_name("FORMAT")
+                        .help("Format of the times, like %I:%M %p; HH:MM if not set"),
+                ),
+        )
+        .subcommand(
+            Command::new("report")
+                .about("Write a report of the events, grouped by week")
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(["md", "html"])
+                        .default_value("md")
+                        .help("Markdown, or a standalone HTML page"),
+                )
+                .arg(
+                    Arg::new("title")
+                        .long("title")
+                        .takes_value(true)
+                        .default_value(report::DEFAULT_TITLE)
+                        .help("Title of the report"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("File to write; the standard output if not set"),
+                ),
+        )
+        .subcommand(
+            Command::new("stats")
+                .about("Summarize the events, done or not, for looking back at them"),
+        )
+        .subcommand(
+            Command::new("serve")
+                .about("Answer an HTTP API to read and change the events")
+                .arg(
+                    Arg::new("bind")
+                        .long("bind")
+                        .takes_value(true)
+                        .default_value("127.0.0.1:8080")
+                        .value_name("ADDRESS:PORT")
+                        .help("Address and port to listen on"),
+                )
+                .arg(
+                    Arg::new("allow-origin")
+                        .long("allow-origin")
+                        .takes_value(true)
+                        .multiple_occurrences(true)
+                        .value_name("ORIGIN")
+                        .help("Let web pages from the origin, like http://localhost:3000, use the API"),
+                ),
+        )
+        .subcommand(
+            Command::new("caldav")
+                .about("Synchronize the events with a CalDAV calendar, both ways")
+                .arg(
+                    Arg::new("url")
+                        .long("url")
+                        .takes_value(true)
+                        .help("URL of the calendar collection; the configured one if not set"),
+                )
+                .arg(
+                    Arg::new("username")
+                        .long("user")
+                        .takes_value(true)
+                        .help("User for the server"),
+                )
+                .arg(
+                    Arg::new("password")
+                        .long("password")
+                        .env("TU_CALDAV_PASSWORD")
+                        .hide_env_values(true)
+                        .takes_value(true)
+                        .help("Password for the user"),
+                )
+                .arg(
+                    Arg::new("policy")
+                        .long("policy")
+                        .takes_value(true)
+                        .possible_values(["local", "remote", "newest"])
+                        .help("Which side stays when an event changed in both"),
+                ),
+        )
+        .subcommand(
+            Command::new("sync")
+                .about("Share the events through the git remote in the configuration"),
+        )
+        .subcommand(
+            Command::new("merge")
+                .about("Merge two changed copies of the event file, event by event")
+                .after_help(
+                    "Writes the result to OURS; fails if both sides changed the same field \
+                     of an event, keeping OURS for it. Use it as a git merge driver with \
+                     `tu merge %O %A %B`.",
+                )
+                .arg(
+                    Arg::new("base")
+                        .required(true)
+                        .value_name("BASE")
+                        .help("The file both copies came from; an empty or missing one if unknown"),
+                )
+                .arg(
+                    Arg::new("ours")
+                        .required(true)
+                        .value_name("OURS")
+                        .help("One of the copies, overwritten with the result"),
+                )
+                .arg(
+                    Arg::new("theirs")
+                        .required(true)
+                        .value_name("THEIRS")
+                        .help("The other copy"),
+                ),
+        )
+        .subcommand(
+            Command::new("migrate")
+                .about("Move all events to another storage backend")
+                .arg(
+                    Arg::new("to")
+                        .long("to")
+                        .takes_value(true)
+                        .required(true)
+                        .possible_values(["toml", "sqlite"])
+                        .value_name("BACKEND")
+                        .help("Backend to move the events to"),
                 ),
         );
 
     let matches = params.get_matches();
-    match matches.subcommand() {
-        ("", _) => Ok(Action::List),
-        ("add", Some(arguments)) => parse_add(arguments),
-        ("rm", Some(arguments)) => parse_rm(arguments),
-        (_, _) => Err(ParseError::UnknownOption),
+    let now = match matches.value_of("now") {
+        Some(now) => Some(parse_now(now)?),
+        None => None,
+    };
+    let action = match matches.subcommand() {
+        None => parse_list(&matches),
+        Some(("add", arguments)) => parse_add(arguments),
+        Some(("rm", arguments)) => parse_rm(arguments),
+        Some(("done", arguments)) => Ok(Action::Done(event_id(arguments)?)),
+        Some(("undone", arguments)) => Ok(Action::Undone(event_id(arguments)?)),
+        Some(("migrate", arguments)) => parse_migrate(arguments),
+        Some(("search", arguments)) => parse_search(arguments),
+        Some(("cal", arguments)) => parse_cal(arguments),
+        Some(("tui", _)) => Ok(Action::Tui),
+        Some(("next", arguments)) => parse_next(arguments),
+        Some(("export", arguments)) => parse_export(arguments),
+        Some(("import", arguments)) => parse_import(arguments),
+        Some(("report", arguments)) => parse_report(arguments),
+        Some(("stats", _)) => Ok(Action::Stats),
+        Some(("caldav", arguments)) => parse_caldav(arguments),
+        Some(("sync", _)) => Ok(Action::Sync),
+        Some(("merge", arguments)) => parse_merge(arguments),
+        Some(("serve", arguments)) => Ok(Action::Serve(
+            arguments.value_of("bind").unwrap_or_default().into(),
+            arguments
+                .values_of("allow-origin")
+                .map(|origins| origins.map(String::from).collect())
+                .unwrap_or_default(),
+        )),
+        Some((_, _)) => Err(ParseError::UnknownOption),
+    }?;
+    let color = match matches.value_of("color") {
+        Some("always") => ColorChoice::Always,
+        Some("never") => ColorChoice::Never,
+        _ => ColorChoice::Auto,
+    };
+    Ok(Arguments { action, color, now })
+}
+
+/// Parse the fake current time, either a date (meaning the start of the day)
+/// or a date and time separated by `T` or a space.
+fn parse_now(now: &str) -> Result<DateTime, ParseError> {
+    let mut frags = now.splitn(2, ['T', ' ']);
+    let date = frags.next().unwrap_or_default();
+    match frags.next() {
+        Some(time) => Ok(DateTime::try_from(date, time)?),
+        None => Ok(DateTime::start_of_day(&Date::try_from(date)?)?),
+    }
+}
+
+fn parse_list(arguments: &ArgMatches) -> Result<Action, ParseError> {
+    let mut filters = Vec::new();
+    if let Some(duration) = arguments.value_of("within") {
+        filters.push(Filter::Within(parse_duration(duration)?));
     }
+    if let Some(date) = arguments.value_of("after") {
+        filters.push(Filter::After(Date::try_from(date)?));
+    }
+    if let Some(date) = arguments.value_of("before") {
+        filters.push(Filter::Before(Date::try_from(date)?));
+    }
+    if arguments.is_present("overdue") {
+        filters.push(Filter::Overdue);
+    }
+    if arguments.is_present("today") {
+        filters.push(Filter::Today);
+    }
+    if arguments.is_present("this-week") {
+        filters.push(Filter::ThisWeek);
+    }
+    if let Some(priority) = priority(arguments, "priority") {
+        filters.push(Filter::Priority(priority));
+    }
+    let sort = match arguments.value_of("sort") {
+        Some("description") => SortKey::Description,
+        Some("created") => SortKey::Created,
+        Some("updated") => SortKey::Updated,
+        Some("priority") => SortKey::Priority,
+        _ => SortKey::Due,
+    };
+    let group = match arguments.value_of("group") {
+        Some("day") => Some(GroupBy::Day),
+        Some("week") => Some(GroupBy::Week),
+        Some("month") => Some(GroupBy::Month),
+        Some("tag") => Some(GroupBy::Tag),
+        _ => None,
+    };
+    let template = match (
+        arguments.value_of("template"),
+        arguments.value_of("template-file"),
+    ) {
+        (Some(template), _) => Some(Template::parse(template)?),
+        (None, Some(path)) => Some(read_template(path)?),
+        (None, None) => None,
+    };
+    Ok(Action::List(ListOptions {
+        filters,
+        all: arguments.is_present("all"),
+        sort,
+        reverse: arguments.is_present("reverse"),
+        group,
+        template,
+    }))
+}
+
+/// Read a template from a file; the line break at the end of the file is not
+/// part of the template, as each event already goes in its own line.
+fn read_template(path: &str) -> Result<Template, ParseError> {
+    let content = fs::read_to_string(path)
+        .map_err(|error| ParseError::InvalidTemplate(format!("can't read {}: {}", path, error)))?;
+    let content = content.strip_suffix('\n').unwrap_or(&content);
+    let content = content.strip_suffix('\r').unwrap_or(content);
+    Ok(Template::parse(content)?)
+}
+
+/// The priority in the argument, if set.
+fn priority(arguments: &ArgMatches, name: &str) -> Option<Priority> {
+    arguments.value_of(name).and_then(Priority::from_name)
 }
 
 fn parse_add(arguments: &ArgMatches) -> Result<Action, ParseError> {
@@ -119,22 +862,166 @@ fn parse_add(arguments: &ArgMatches) -> Result<Action, ParseError> {
         .value_of("description")
         .ok_or(ParseError::MissingDescription)?;
     let date = arguments.value_of("date").ok_or(ParseError::MissingDate)?;
+    let tags = arguments
+        .values_of("tag")
+        .map(|tags| tags.map(String::from).collect())
+        .unwrap_or_default();
 
-    if let Some(time) = arguments.value_of("time") {
-        Ok(Action::AddWithTime(
-            description.into(),
-            DateTime::try_from(date, time)?,
-        ))
+    let new = if let Some(time) = arguments.value_of("time") {
+        NewEvent::at_time(description, &DateTime::try_from(date, time)?)
     } else {
-        Ok(Action::Add(description.into(), Date::try_from(date)?))
-    }
+        NewEvent::on_date(description, &Date::try_from(date)?)
+    };
+    let priority = priority(arguments, "priority").unwrap_or_default();
+    Ok(Action::Add(new.with_tags(tags).with_priority(priority)))
+}
+
+fn event_id(arguments: &ArgMatches) -> Result<Id, ParseError> {
+    let id = arguments.value_of("id").ok_or(ParseError::MissingEventId)?;
+    Ok(id.into())
 }
 
 fn parse_rm(arguments: &ArgMatches) -> Result<Action, ParseError> {
     if arguments.is_present("outdated") {
-        Ok(Action::RemoveOutdated)
+        Ok(Action::RemoveOutdated(priority(arguments, "below")))
     } else {
         let id = arguments.value_of("id").ok_or(ParseError::MissingEventId)?;
         Ok(Action::RemoveById(id.into()))
     }
 }
+
+fn parse_caldav(arguments: &ArgMatches) -> Result<Action, ParseError> {
+    let policy = match arguments.value_of("policy") {
+        Some("local") => Some(Policy::Local),
+        Some("remote") => Some(Policy::Remote),
+        Some("newest") => Some(Policy::Newest),
+        _ => None,
+    };
+    Ok(Action::CalDav(CalDavOptions {
+        url: arguments.value_of("url").map(String::from),
+        username: arguments.value_of("username").map(String::from),
+        password: arguments.value_of("password").map(String::from),
+        policy,
+    }))
+}
+
+fn parse_merge(arguments: &ArgMatches) -> Result<Action, ParseError> {
+    let path = |name| {
+        arguments
+            .value_of(name)
+            .map(PathBuf::from)
+            .ok_or(ParseError::MissingFile)
+    };
+    Ok(Action::Merge(path("base")?, path("ours")?, path("theirs")?))
+}
+
+fn parse_migrate(arguments: &ArgMatches) -> Result<Action, ParseError> {
+    match arguments.value_of("to") {
+        Some("toml") => Ok(Action::Migrate(Backend::Toml)),
+        Some("sqlite") => Ok(Action::Migrate(Backend::Sqlite)),
+        _ => Err(ParseError::MissingBackend),
+    }
+}
+
+fn parse_next(arguments: &ArgMatches) -> Result<Action, ParseError> {
+    let template = Template::parse(arguments.value_of("format").unwrap_or(NEXT_FORMAT))?;
+    let max_age = match arguments.value_of("max-age") {
+        Some(age) => Some(
+            parse_duration(age)?
+                .to_std()
+                .map_err(|_| ParseError::InvalidDuration(format!("invalid duration '{}'", age)))?,
+        ),
+        None => None,
+    };
+    let output = match arguments.value_of("output") {
+        Some("waybar") => NextOutput::Waybar,
+        Some("i3bar") => NextOutput::I3bar,
+        _ => NextOutput::Plain,
+    };
+    Ok(Action::Next(NextOptions {
+        template,
+        max_age,
+        output,
+    }))
+}
+
+fn parse_date_formats(arguments: &ArgMatches) -> DateFormats {
+    DateFormats {
+        date: arguments.value_of("date-format").map(String::from),
+        time: arguments.value_of("time-format").map(String::from),
+    }
+}
+
+fn parse_export(arguments: &ArgMatches) -> Result<Action, ParseError> {
+    let format = match arguments.value_of("format") {
+        Some("json") => ExportFormat::Json,
+        _ => ExportFormat::Csv,
+    };
+    Ok(Action::Export(ExportOptions {
+        format,
+        formats: parse_date_formats(arguments),
+        output: arguments.value_of("output").map(PathBuf::from),
+    }))
+}
+
+fn parse_import(arguments: &ArgMatches) -> Result<Action, ParseError> {
+    let path = arguments.value_of("file").ok_or(ParseError::MissingFile)?;
+    let defaults = ImportOptions::default();
+    let options = ImportOptions {
+        date: arguments
+            .value_of("date-col")
+            .map_or(defaults.date, Column::parse),
+        time: arguments.value_of("time-col").map(Column::parse),
+        description: arguments
+            .value_of("desc-col")
+            .map_or(defaults.description, Column::parse),
+        tags: arguments.value_of("tags-col").map(Column::parse),
+        formats: parse_date_formats(arguments),
+        header: !arguments.is_present("no-header"),
+    };
+    Ok(Action::Import(path.into(), options))
+}
+
+fn parse_report(arguments: &ArgMatches) -> Result<Action, ParseError> {
+    let format = match arguments.value_of("format") {
+        Some("html") => ReportFormat::Html,
+        _ => ReportFormat::Markdown,
+    };
+    Ok(Action::Report(ReportOptions {
+        format,
+        title: arguments
+            .value_of("title")
+            .unwrap_or(report::DEFAULT_TITLE)
+            .into(),
+        output: arguments.value_of("output").map(PathBuf::from),
+    }))
+}
+
+fn parse_search(arguments: &ArgMatches) -> Result<Action, ParseError> {
+    let query = arguments
+        .value_of("query")
+        .ok_or(ParseError::MissingQuery)?;
+    let mode = if arguments.is_present("regex") {
+        SearchMode::Regex
+    } else if arguments.is_present("fuzzy") {
+        SearchMode::Fuzzy
+    } else {
+        SearchMode::Substring
+    };
+    Ok(Action::Search(query.into(), mode))
+}
+
+fn parse_cal(arguments: &ArgMatches) -> Result<Action, ParseError> {
+    if let Some(weeks) = arguments.value_of("weeks") {
+        let weeks = weeks.parse().map_err(|_| ParseError::InvalidWeeks)?;
+        Ok(Action::Calendar(CalendarView::Weeks(weeks)))
+    } else if let Some(month) = arguments.value_of("month") {
+        let date = Date::try_from(&format!("{}-01", month))?;
+        Ok(Action::Calendar(CalendarView::Month(Some((
+            date.year(),
+            date.month(),
+        )))))
+    } else {
+        Ok(Action::Calendar(CalendarView::Month(None)))
+    }
+}
@@ -0,0 +1,90 @@
+/*
+   TU - Time's Up!
+   Copyright (C) 2020  Julio Biason
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use unicode_width::UnicodeWidthStr;
+
+use tu::calendar::Calendar;
+use tu::calendar::CalendarDay;
+use tu::eta::Eta;
+use tu::Clock;
+use tu::Event;
+
+use crate::render::pad_left;
+use crate::render::Renderer;
+use crate::render::Style;
+
+/// Print the calendar grid, like cal(1), followed by the events in it.
+pub fn print_calendar(calendar: &Calendar, clock: &dyn Clock, renderer: &Renderer) {
+    println!("{}", format!("{:^20}", calendar.title).trim_end());
+    println!("Mo Tu We Th Fr Sa Su");
+    for week in &calendar.weeks {
+        let days: String = week.iter().map(|day| cell(day, renderer)).collect();
+        println!("{}", days.trim_end());
+    }
+
+    // The ID and ETA columns as wide as their longest values.
+    let rows: Vec<(&Event, Eta)> = calendar
+        .events()
+        .map(|event| (event, event.eta(clock)))
+        .collect();
+    let id_width = rows
+        .iter()
+        .map(|(event, _)| event.id.width())
+        .max()
+        .unwrap_or(0);
+    let eta_width = rows
+        .iter()
+        .map(|(_, eta)| eta.to_string().width())
+        .max()
+        .unwrap_or(0);
+
+    if !rows.is_empty() {
+        println!();
+    }
+    for (event, eta) in rows {
+        let line = format!(
+            "{} | {} | {} | {}",
+            event.due.date().format("%b %d").unwrap_or_default(),
+            pad_left(&event.id, id_width),
+            pad_left(&eta.to_string(), eta_width),
+            event.description
+        );
+        let style = Style::for_urgency(eta.urgency());
+        println!("{}", renderer.paint(&line, style.as_slice()));
+    }
+}
+
+/// A day in the grid, with the space after it: days with events in reverse
+/// video and marked with `*`, so they stand out without colors too, and
+/// today underlined.
+fn cell(day: &CalendarDay, renderer: &Renderer) -> String {
+    if day.outside {
+        return "   ".into();
+    }
+
+    let mut styles = Vec::new();
+    if !day.events.is_empty() {
+        styles.push(Style::Reverse);
+    }
+    if day.today {
+        styles.push(Style::Underline);
+    }
+    let marker = if day.events.is_empty() { ' ' } else { '*' };
+    let number = renderer.paint(&format!("{:>2}", day.date.day()), &styles);
+    format!("{}{}", number, marker)
+}
@@ -0,0 +1,964 @@
+/*
+   TU - Time's Up!
+   Copyright (C) 2020  Julio Biason
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Two-way synchronization with a CalDAV collection.
+//!
+//! Each event is a calendar object in the collection, with the event ID as
+//! the VEVENT UID. The state of the last synchronization (the ETag of each
+//! object and the event as it was) is kept, so each side can tell what
+//! changed since: changes in one side only are copied to the other; when
+//! both sides changed the same event, the [`Policy`] picks which one stays.
+
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+
+use base64::Engine;
+use dirs::config_dir;
+use regex::Regex;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+
+use crate::clock::Clock;
+use crate::datetime::DateTime;
+use crate::event::Event;
+use crate::eventlist::EventListError;
+use crate::ics;
+use crate::ics::IcsError;
+use crate::storage::Storage;
+
+static STATE_FILENAME: &str = "caldav.toml";
+
+/// Asks for the ETag and content of all events in the collection.
+static CALENDAR_QUERY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<c:calendar-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+  <d:prop><d:getetag/><c:calendar-data/></d:prop>
+  <c:filter><c:comp-filter name="VCALENDAR"><c:comp-filter name="VEVENT"/></c:comp-filter></c:filter>
+</c:calendar-query>"#;
+
+/// Which side stays when an event changed in both since the last
+/// synchronization.
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Policy {
+    /// The local event is kept.
+    Local,
+    /// The event in the collection is kept.
+    Remote,
+    /// The one changed last is kept; the local one if that can't be told.
+    #[default]
+    Newest,
+}
+
+impl fmt::Display for Policy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Policy::Local => write!(f, "local"),
+            Policy::Remote => write!(f, "remote"),
+            Policy::Newest => write!(f, "newest"),
+        }
+    }
+}
+
+/// Errors while synchronizing.
+#[derive(Debug)]
+pub enum SyncError {
+    /// The server can't be reached, or refused the request.
+    Http(String),
+    /// The object changed in the server while synchronizing.
+    Changed(String),
+    /// An object in the collection is not a valid calendar.
+    InvalidCalendar(String, IcsError),
+    /// The local events can't be read or written.
+    Storage(EventListError),
+    /// The synchronization state can't be read or written.
+    State(String),
+}
+
+impl fmt::Display for SyncError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SyncError::Http(reason) => write!(f, "CalDAV request failed: {}", reason),
+            SyncError::Changed(href) => {
+                write!(f, "{} changed in the server meanwhile; sync again", href)
+            }
+            SyncError::InvalidCalendar(href, error) => write!(f, "{}: {}", href, error),
+            SyncError::Storage(error) => write!(f, "{}", error),
+            SyncError::State(reason) => write!(f, "broken synchronization state: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for SyncError {}
+
+impl From<EventListError> for SyncError {
+    fn from(error: EventListError) -> SyncError {
+        SyncError::Storage(error)
+    }
+}
+
+impl From<ureq::Error> for SyncError {
+    fn from(error: ureq::Error) -> SyncError {
+        match error {
+            ureq::Error::Status(code, response) => {
+                SyncError::Http(format!("{} answered {}", response.get_url(), code))
+            }
+            ureq::Error::Transport(transport) => SyncError::Http(transport.to_string()),
+        }
+    }
+}
+
+/// A calendar object in the collection.
+#[derive(Debug, Clone)]
+pub struct Remote {
+    /// Where the object is, as the server wrote it.
+    pub href: String,
+    /// Version of the object, if the server tells it.
+    pub etag: Option<String>,
+    /// The event in the object.
+    pub event: ics::VEvent,
+}
+
+/// Connection to a CalDAV collection.
+pub struct Client {
+    /// The collection URL, ending with a slash.
+    url: String,
+    credentials: Option<(String, String)>,
+    agent: ureq::Agent,
+}
+
+impl Client {
+    /// Use the collection at the URL.
+    pub fn new(url: &str) -> Self {
+        let url = match url.ends_with('/') {
+            true => url.to_string(),
+            false => format!("{}/", url),
+        };
+        Self {
+            url,
+            credentials: None,
+            agent: ureq::AgentBuilder::new().build(),
+        }
+    }
+
+    /// The same client, authenticating with the user and password.
+    pub fn with_credentials(self, username: &str, password: &str) -> Self {
+        Self {
+            credentials: Some((username.into(), password.into())),
+            ..self
+        }
+    }
+
+    /// The collection URL.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Where a new object for the event goes.
+    fn href_for(&self, id: &str) -> String {
+        // Percent-encoded, so different IDs never share an object.
+        let safe: String = id
+            .bytes()
+            .map(
+                |byte| match byte.is_ascii_alphanumeric() || b"-_.~@".contains(&byte) {
+                    true => (byte as char).to_string(),
+                    false => format!("%{:02X}", byte),
+                },
+            )
+            .collect();
+        format!("{}{}.ics", self.url, safe)
+    }
+
+    /// Full URL of an href returned by the server.
+    fn resolve(&self, href: &str) -> String {
+        if href.starts_with("http://") || href.starts_with("https://") {
+            return href.into();
+        }
+        match href.strip_prefix('/') {
+            Some(path) => {
+                let host_start = self.url.find("://").map_or(0, |position| position + 3);
+                let origin = match self.url[host_start..].find('/') {
+                    Some(position) => &self.url[..host_start + position],
+                    None => &self.url,
+                };
+                format!("{}/{}", origin, path)
+            }
+            None => format!("{}{}", self.url, href),
+        }
+    }
+
+    fn request(&self, method: &str, url: &str) -> ureq::Request {
+        let request = self.agent.request(method, url);
+        match &self.credentials {
+            Some((username, password)) => {
+                let encoded = base64::engine::general_purpose::STANDARD
+                    .encode(format!("{}:{}", username, password));
+                request.set("Authorization", &format!("Basic {}", encoded))
+            }
+            None => request,
+        }
+    }
+
+    /// All the events in the collection.
+    pub fn list(&self) -> Result<Vec<Remote>, SyncError> {
+        let body = self
+            .request("REPORT", &self.url)
+            .set("Depth", "1")
+            .set("Content-Type", "application/xml; charset=utf-8")
+            .send_string(CALENDAR_QUERY)?
+            .into_string()
+            .map_err(|error| SyncError::Http(error.to_string()))?;
+        parse_multistatus(&body)
+    }
+
+    /// Write the calendar to the object at the href, only if it still has
+    /// the ETag or, without one, if there is no such object yet; returns the
+    /// new ETag, if the server tells it.
+    pub fn put(
+        &self,
+        href: &str,
+        content: &str,
+        etag: Option<&str>,
+    ) -> Result<Option<String>, SyncError> {
+        let request = self
+            .request("PUT", &self.resolve(href))
+            .set("Content-Type", "text/calendar; charset=utf-8");
+        let request = match etag {
+            Some(etag) => request.set("If-Match", etag),
+            None => request.set("If-None-Match", "*"),
+        };
+        match request.send_string(content) {
+            Ok(response) => Ok(response.header("ETag").map(String::from)),
+            Err(ureq::Error::Status(412, _)) => Err(SyncError::Changed(href.into())),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Remove the object at the href, if it still has the ETag.
+    pub fn delete(&self, href: &str, etag: Option<&str>) -> Result<(), SyncError> {
+        let request = self.request("DELETE", &self.resolve(href));
+        let request = match etag {
+            Some(etag) => request.set("If-Match", etag),
+            None => request,
+        };
+        match request.call() {
+            Ok(_) | Err(ureq::Error::Status(404, _)) => Ok(()),
+            Err(ureq::Error::Status(412, _)) => Err(SyncError::Changed(href.into())),
+            Err(error) => Err(error.into()),
+        }
+    }
+}
+
+/// Undo the XML escapes.
+fn unescape_xml(text: &str) -> String {
+    let text = text
+        .trim()
+        .trim_start_matches("<![CDATA[")
+        .trim_end_matches("]]>");
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&#13;", "\r")
+        .replace("&#xD;", "\r")
+        .replace("&amp;", "&")
+}
+
+/// The objects in a multistatus answer to a calendar query; elements may
+/// use any namespace prefix.
+fn parse_multistatus(body: &str) -> Result<Vec<Remote>, SyncError> {
+    let element = |name: &str| {
+        Regex::new(&format!(
+            r"(?s)<(?:[\w-]+:)?{0}(?:\s[^>]*)?>(.*?)</(?:[\w-]+:)?{0}>",
+            name
+        ))
+        .expect("element pattern")
+    };
+    let (response, href, etag, data) = (
+        element("response"),
+        element("href"),
+        element("getetag"),
+        element("calendar-data"),
+    );
+
+    let mut remotes = Vec::new();
+    for found in response.captures_iter(body) {
+        let inner = &found[1];
+        let value = |pattern: &Regex| {
+            pattern
+                .captures(inner)
+                .map(|captures| unescape_xml(&captures[1]))
+        };
+        let (href, data) = match (value(&href), value(&data)) {
+            (Some(href), Some(data)) if !data.is_empty() => (href, data),
+            _ => continue,
+        };
+        let events =
+            ics::parse(&data).map_err(|error| SyncError::InvalidCalendar(href.clone(), error))?;
+        if let Some(event) = events.into_iter().next() {
+            remotes.push(Remote {
+                href,
+                etag: value(&etag),
+                event,
+            });
+        }
+    }
+    Ok(remotes)
+}
+
+/// An event as it was in both sides after the last synchronization.
+// Keep `event` as the last field: TOML needs plain values before tables.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Synced {
+    /// Where the object is in the collection.
+    pub href: String,
+    /// Version of the object, as it was after the synchronization.
+    pub etag: Option<String>,
+    /// The event, as it was in both sides.
+    pub event: Event,
+}
+
+/// What was synchronized last time.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct SyncState {
+    /// The collection URL; the state of another collection is not used.
+    pub url: String,
+    /// The events in both sides.
+    #[serde(default)]
+    pub events: Vec<Synced>,
+}
+
+impl SyncState {
+    /// Default location of the state, inside the user configuration
+    /// directory.
+    pub fn default_path() -> Result<PathBuf, EventListError> {
+        let mut path = config_dir().ok_or(EventListError::NoStorage)?;
+        path.push(STATE_FILENAME);
+        Ok(path)
+    }
+
+    /// Load the state from the file; without one, nothing was synchronized.
+    pub fn load(path: &Path) -> Result<Self, SyncError> {
+        match fs::read_to_string(path) {
+            Ok(content) => {
+                toml::from_str(&content).map_err(|error| SyncError::State(error.to_string()))
+            }
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(SyncState::default()),
+            Err(error) => Err(SyncError::State(error.to_string())),
+        }
+    }
+
+    /// Write the state to the file.
+    pub fn save(&self, path: &Path) -> Result<(), SyncError> {
+        let content = toml::to_string(self).map_err(|error| SyncError::State(error.to_string()))?;
+        fs::write(path, content).map_err(|error| SyncError::State(error.to_string()))
+    }
+}
+
+/// How many events went each way.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SyncReport {
+    /// Events sent to the collection.
+    pub pushed: usize,
+    /// Events taken from the collection.
+    pub pulled: usize,
+    /// Events removed here, because they were removed in the collection.
+    pub deleted_local: usize,
+    /// Events removed from the collection, because they were removed here.
+    pub deleted_remote: usize,
+    /// Events changed in both sides, solved by the policy.
+    pub conflicts: usize,
+}
+
+impl fmt::Display for SyncReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} sent, {} received, {} removed here, {} removed there, {} conflicts",
+            self.pushed, self.pulled, self.deleted_local, self.deleted_remote, self.conflicts
+        )
+    }
+}
+
+/// What to do with an event.
+enum Step {
+    Push,
+    Pull,
+    DeleteLocal,
+    DeleteRemote,
+    /// Both sides agree; only the state changes.
+    Keep,
+    /// Gone from both sides.
+    Forget,
+}
+
+/// Which side of a conflict stays.
+fn local_wins(policy: Policy, local: Option<i64>, remote: Option<&DateTime>) -> bool {
+    match policy {
+        Policy::Local => true,
+        Policy::Remote => false,
+        Policy::Newest => match (local, remote) {
+            (Some(local), Some(remote)) => local >= remote.timestamp(),
+            _ => true,
+        },
+    }
+}
+
+/// Synchronize the events in the storage with the collection, updating the
+/// state as each event is synchronized; the state should be saved even if
+/// this fails midway.
+pub fn sync(
+    storage: &mut dyn Storage,
+    client: &Client,
+    state: &mut SyncState,
+    policy: Policy,
+    clock: &dyn Clock,
+) -> Result<SyncReport, SyncError> {
+    if state.url != client.url() {
+        *state = SyncState {
+            url: client.url().into(),
+            events: Vec::new(),
+        };
+    }
+    let remotes: HashMap<String, Remote> = client
+        .list()?
+        .into_iter()
+        .map(|remote| (remote.event.event.id.clone(), remote))
+        .collect();
+    let mut list = storage.load()?;
+    // Events changed before tu kept their time of change fall back to the
+    // time of change of the whole storage.
+    let storage_modified = storage
+        .modified()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|since| since.as_secs() as i64);
+    let mut base: HashMap<String, Synced> = state
+        .events
+        .drain(..)
+        .map(|synced| (synced.event.id.clone(), synced))
+        .collect();
+
+    let ids: BTreeSet<String> = list
+        .iter()
+        .map(|event| event.id.clone())
+        .chain(remotes.keys().cloned())
+        .chain(base.keys().cloned())
+        .collect();
+
+    let mut report = SyncReport::default();
+    let mut steps = Vec::new();
+    for id in ids {
+        let local = list.get(&id);
+        let remote = remotes.get(&id);
+        let synced = base.remove(&id);
+        let local_changed = match (local, &synced) {
+            (Some(local), Some(synced)) => !local.same_as(&synced.event),
+            _ => true,
+        };
+        let remote_changed = match (remote, &synced) {
+            (Some(remote), Some(synced)) => remote.etag.is_none() || remote.etag != synced.etag,
+            _ => true,
+        };
+        let remote_time = remote.and_then(|remote| remote.event.modified.as_ref());
+        let step = match (local, remote, &synced) {
+            (None, None, _) => Step::Forget,
+            (Some(_), None, None) => Step::Push,
+            (None, Some(_), None) => Step::Pull,
+            (Some(local), Some(remote), _) if local.same_as(&remote.event.event) => Step::Keep,
+            (Some(_), Some(_), Some(_)) if !remote_changed => Step::Push,
+            (Some(_), Some(_), Some(_)) if !local_changed => Step::Pull,
+            (None, Some(_), Some(_)) if !remote_changed => Step::DeleteRemote,
+            (Some(_), None, Some(_)) if !local_changed => Step::DeleteLocal,
+            // Changed in both sides, or removed in one and changed in the
+            // other; removals have no time, so they lose to any change.
+            (Some(_), None, _) => {
+                report.conflicts += 1;
+                Step::Push
+            }
+            (None, Some(_), _) => {
+                report.conflicts += 1;
+                match policy {
+                    Policy::Local => Step::DeleteRemote,
+                    Policy::Remote | Policy::Newest => Step::Pull,
+                }
+            }
+            (Some(_), Some(_), _) => {
+                report.conflicts += 1;
+                let local_modified = local
+                    .and_then(|local| local.updated_at)
+                    .map(|updated| updated.timestamp())
+                    .or(storage_modified);
+                match local_wins(policy, local_modified, remote_time) {
+                    true => Step::Push,
+                    false => Step::Pull,
+                }
+            }
+        };
+        steps.push((id, step, synced));
+    }
+
+    // Local changes first, all at once, so the events are safe even if the
+    // server fails.
+    for (id, step, _) in &steps {
+        match step {
+            Step::Pull => {
+                let mut event = remotes[id].event.event.clone();
+                if let Some(old) = list.remove(id) {
+                    event.created_at = event.created_at.or(old.created_at);
+                    event.created_by = event.created_by.or(old.created_by);
+                }
+                list.push(event);
+                report.pulled += 1;
+            }
+            Step::DeleteLocal => {
+                list.remove(id);
+                report.deleted_local += 1;
+            }
+            _ => {}
+        }
+    }
+    if report.pulled > 0 || report.deleted_local > 0 {
+        if let Err(error) = storage.save(&list) {
+            state
+                .events
+                .extend(steps.into_iter().filter_map(|(_, _, synced)| synced));
+            return Err(error.into());
+        }
+    }
+
+    let mut steps = steps.into_iter();
+    while let Some((id, step, synced)) = steps.next() {
+        // What failed and what is left keep their base, so the next
+        // synchronization still tells what changed in each side.
+        let mut keep_base = |state: &mut SyncState, synced: Option<Synced>| {
+            state.events.extend(synced);
+            state
+                .events
+                .extend(steps.by_ref().filter_map(|(_, _, synced)| synced));
+        };
+        let remote = remotes.get(&id);
+        let href = remote
+            .map(|remote| remote.href.clone())
+            .or_else(|| synced.as_ref().map(|synced| synced.href.clone()))
+            .unwrap_or_else(|| client.href_for(&id));
+        let etag = remote.and_then(|remote| remote.etag.clone());
+        match step {
+            Step::Push => {
+                let event = list.get(&id).expect("pushing a local event").clone();
+                let content = ics::calendar(vec![&event], clock);
+                match client.put(&href, &content, etag.as_deref()) {
+                    Ok(etag) => state.events.push(Synced { href, etag, event }),
+                    Err(error) => {
+                        keep_base(state, synced);
+                        return Err(error);
+                    }
+                }
+                report.pushed += 1;
+            }
+            Step::DeleteRemote => {
+                if let Err(error) = client.delete(&href, etag.as_deref()) {
+                    keep_base(state, synced);
+                    return Err(error);
+                }
+                report.deleted_remote += 1;
+            }
+            Step::Pull | Step::Keep => {
+                if let Some(event) = list.get(&id) {
+                    state.events.push(Synced {
+                        href,
+                        etag,
+                        event: event.clone(),
+                    });
+                }
+            }
+            Step::DeleteLocal | Step::Forget => {}
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod caldav_test {
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+    use std::thread;
+
+    use tiny_http::Header;
+    use tiny_http::Response;
+    use tiny_http::Server;
+
+    use super::parse_multistatus;
+    use super::sync;
+    use super::Client;
+    use super::Policy;
+    use super::SyncReport;
+    use super::SyncState;
+    use crate::clock::FixedClock;
+    use crate::date::Date;
+    use crate::event::NewEvent;
+    use crate::eventlist::EventList;
+    use crate::idgen::Sequential;
+    use crate::storage::Memory;
+    use crate::storage::Storage;
+
+    /// Objects in the stand-in collection, by path, with their ETag.
+    type Objects = Arc<Mutex<BTreeMap<String, (String, String)>>>;
+
+    fn header(request: &tiny_http::Request, name: &'static str) -> Option<String> {
+        request
+            .headers()
+            .iter()
+            .find(|header| header.field.equiv(name))
+            .map(|header| header.value.to_string())
+    }
+
+    /// A tiny CalDAV server, enough for synchronizing, in `/cal/`.
+    fn stand_in() -> (Client, Objects) {
+        failing_stand_in(None)
+    }
+
+    /// The stand-in server, failing the request with this number, starting
+    /// at 1.
+    fn failing_stand_in(failing: Option<usize>) -> (Client, Objects) {
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/cal/", server.server_addr().to_ip().unwrap());
+        let objects = Objects::default();
+        let shared = objects.clone();
+        thread::spawn(move || {
+            let mut version = 100;
+            for (number, mut request) in server.incoming_requests().enumerate() {
+                if Some(number + 1) == failing {
+                    request
+                        .respond(Response::from_string("").with_status_code(500))
+                        .unwrap();
+                    continue;
+                }
+                let mut body = String::new();
+                request.as_reader().read_to_string(&mut body).unwrap();
+                let path = request.url().to_string();
+                let mut objects = shared.lock().unwrap();
+                let current = objects.get(&path).map(|(etag, _)| etag.clone());
+                let precondition = match (header(&request, "If-Match"), &current) {
+                    (Some(expected), Some(current)) => expected == *current,
+                    (Some(_), None) => false,
+                    (None, _) => header(&request, "If-None-Match").is_none() || current.is_none(),
+                };
+                let response = match request.method().as_str() {
+                    "REPORT" => {
+                        let mut answer = String::from(r#"<D:multistatus xmlns:D="DAV:">"#);
+                        for (href, (etag, content)) in objects.iter() {
+                            answer.push_str(&format!(
+                                "<D:response><D:href>{}</D:href><D:propstat><D:prop>\
+                                 <D:getetag>{}</D:getetag>\
+                                 <C:calendar-data xmlns:C=\"urn:ietf:params:xml:ns:caldav\">{}\
+                                 </C:calendar-data></D:prop></D:propstat></D:response>",
+                                href,
+                                etag.replace('"', "&quot;"),
+                                content.replace('&', "&amp;").replace('<', "&lt;")
+                            ));
+                        }
+                        answer.push_str("</D:multistatus>");
+                        Response::from_string(answer).with_status_code(207)
+                    }
+                    _ if !precondition => Response::from_string("").with_status_code(412),
+                    "PUT" => {
+                        version += 1;
+                        let etag = format!("\"{}\"", version);
+                        objects.insert(path, (etag.clone(), body));
+                        Response::from_string("")
+                            .with_status_code(201)
+                            .with_header(Header::from_bytes("ETag", etag).unwrap())
+                    }
+                    "DELETE" => {
+                        objects.remove(&path);
+                        Response::from_string("").with_status_code(204)
+                    }
+                    _ => Response::from_string("").with_status_code(405),
+                };
+                drop(objects);
+                request.respond(response).unwrap();
+            }
+        });
+        (Client::new(&url), objects)
+    }
+
+    /// Put an event straight in the collection, as another client would.
+    fn remote_event(objects: &Objects, uid: &str, summary: &str, modified: &str) {
+        let content = format!(
+            "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VEVENT\r\nUID:{}\r\n\
+             DTSTART;VALUE=DATE:20200720\r\nSUMMARY:{}\r\nLAST-MODIFIED:{}\r\n\
+             END:VEVENT\r\nEND:VCALENDAR\r\n",
+            uid, summary, modified
+        );
+        let etag = format!("\"{}-{}\"", uid, summary.replace(' ', "-"));
+        let mut objects = objects.lock().unwrap();
+        objects.insert(format!("/cal/{}.ics", uid), (etag, content));
+    }
+
+    fn add(storage: &mut dyn Storage, description: &str) -> String {
+        let new = NewEvent::on_date(description, &Date::new(2020, 7, 1).unwrap());
+        EventList::add(
+            storage,
+            &mut Sequential,
+            &FixedClock::at(2020, 6, 10, 12, 0),
+            new,
+        )
+        .unwrap()
+    }
+
+    fn description(storage: &dyn Storage, id: &str) -> Option<String> {
+        let list = storage.load().unwrap();
+        list.get(id).map(|event| event.description.clone())
+    }
+
+    fn run(storage: &mut dyn Storage, client: &Client, state: &mut SyncState) -> SyncReport {
+        sync(
+            storage,
+            client,
+            state,
+            Policy::Newest,
+            &FixedClock::at(2020, 6, 10, 12, 0),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    pub fn both_ways() {
+        let (client, objects) = stand_in();
+        let mut storage = Memory::new();
+        let mut state = SyncState::default();
+        let id = add(&mut storage, "Local");
+        remote_event(&objects, "remote@example.com", "Remote", "20200601T000000Z");
+
+        let report = run(&mut storage, &client, &mut state);
+        assert_eq!(report.pushed, 1);
+        assert_eq!(report.pulled, 1);
+        assert_eq!(
+            description(&storage, "remote@example.com").as_deref(),
+            Some("Remote")
+        );
+        let objects_now = objects.lock().unwrap().clone();
+        let pushed = &objects_now[&format!("/cal/{}.ics", id)].1;
+        assert!(pushed.contains(&format!("UID:{}\r\n", id)));
+        assert!(pushed.contains("SUMMARY:Local\r\n"));
+
+        assert_eq!(
+            run(&mut storage, &client, &mut state),
+            SyncReport::default()
+        );
+    }
+
+    #[test]
+    pub fn deletions() {
+        let (client, objects) = stand_in();
+        let mut storage = Memory::new();
+        let mut state = SyncState::default();
+        let id = add(&mut storage, "Local");
+        remote_event(&objects, "gone", "Remote", "20200601T000000Z");
+        run(&mut storage, &client, &mut state);
+
+        EventList::remove_by_id(&mut storage, &id).unwrap();
+        objects.lock().unwrap().remove("/cal/gone.ics");
+        let report = run(&mut storage, &client, &mut state);
+        assert_eq!(report.deleted_local, 1);
+        assert_eq!(report.deleted_remote, 1);
+        assert!(storage.load().unwrap().is_empty());
+        assert!(objects.lock().unwrap().is_empty());
+        assert!(state.events.is_empty());
+    }
+
+    #[test]
+    pub fn failure_keeps_the_base() {
+        // The first synchronization lists and pushes three events; the
+        // second one fails right after listing.
+        let (client, objects) = failing_stand_in(Some(6));
+        let mut storage = Memory::new();
+        let mut state = SyncState::default();
+        let first = add(&mut storage, "First");
+        add(&mut storage, "Second");
+        let gone = add(&mut storage, "Gone");
+        run(&mut storage, &client, &mut state);
+
+        let mut event = storage.load().unwrap().get(&first).unwrap().clone();
+        event.description = "Edited".into();
+        EventList::edit(&mut storage, &FixedClock::at(2020, 6, 10, 12, 0), event).unwrap();
+        EventList::remove_by_id(&mut storage, &gone).unwrap();
+        assert!(sync(
+            &mut storage,
+            &client,
+            &mut state,
+            Policy::Newest,
+            &FixedClock::at(2020, 6, 10, 12, 0)
+        )
+        .is_err());
+        assert_eq!(state.events.len(), 3);
+
+        let report = run(&mut storage, &client, &mut state);
+        assert_eq!((report.pushed, report.deleted_remote), (1, 1));
+        assert_eq!(report.conflicts, 0);
+        assert!(description(&storage, &gone).is_none());
+        assert_eq!(objects.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    pub fn one_side_changes() {
+        let (client, objects) = stand_in();
+        let mut storage = Memory::new();
+        let mut state = SyncState::default();
+        remote_event(&objects, "shared", "Before", "20200601T000000Z");
+        run(&mut storage, &client, &mut state);
+
+        remote_event(&objects, "shared", "After", "20200602T000000Z");
+        let report = run(&mut storage, &client, &mut state);
+        assert_eq!((report.pulled, report.conflicts), (1, 0));
+        assert_eq!(description(&storage, "shared").as_deref(), Some("After"));
+
+        let mut event = storage.load().unwrap().get("shared").unwrap().clone();
+        event.description = "Local edit".into();
+        EventList::edit(&mut storage, &FixedClock::at(2020, 6, 10, 12, 0), event).unwrap();
+        let report = run(&mut storage, &client, &mut state);
+        assert_eq!((report.pushed, report.conflicts), (1, 0));
+        assert!(objects.lock().unwrap()["/cal/shared.ics"]
+            .1
+            .contains("SUMMARY:Local edit"));
+    }
+
+    /// Change the event in both sides after a first synchronization, and
+    /// synchronize again with the policy; returns the description that
+    /// stayed.
+    fn conflict(storage: &mut dyn Storage, policy: Policy, remote_modified: &str) -> String {
+        let (client, objects) = stand_in();
+        let mut state = SyncState::default();
+        remote_event(&objects, "shared", "Original", "20200601T000000Z");
+        run(storage, &client, &mut state);
+
+        remote_event(&objects, "shared", "Remote edit", remote_modified);
+        let mut event = storage.load().unwrap().get("shared").unwrap().clone();
+        event.description = "Local edit".into();
+        EventList::edit(storage, &FixedClock::at(2020, 6, 10, 12, 0), event).unwrap();
+
+        let report = sync(
+            storage,
+            &client,
+            &mut state,
+            policy,
+            &FixedClock::at(2020, 6, 10, 12, 0),
+        )
+        .unwrap();
+        assert_eq!(report.conflicts, 1);
+        let local = description(storage, "shared").unwrap();
+        let remote = objects.lock().unwrap()["/cal/shared.ics"].1.clone();
+        assert!(remote.contains(&format!("SUMMARY:{}\r\n", local)));
+        local
+    }
+
+    #[test]
+    pub fn policies() {
+        let modified = "20200601T000000Z";
+        assert_eq!(
+            conflict(&mut Memory::new(), Policy::Local, modified),
+            "Local edit"
+        );
+        assert_eq!(
+            conflict(&mut Memory::new(), Policy::Remote, modified),
+            "Remote edit"
+        );
+
+        // The local time of change is when the event was edited.
+        assert_eq!(
+            conflict(&mut Memory::new(), Policy::Newest, "20990101T000000Z"),
+            "Remote edit"
+        );
+        assert_eq!(
+            conflict(&mut Memory::new(), Policy::Newest, "20000101T000000Z"),
+            "Local edit"
+        );
+    }
+
+    #[test]
+    pub fn state_round_trip() {
+        let (client, objects) = stand_in();
+        let mut storage = Memory::new();
+        let mut state = SyncState::default();
+        remote_event(&objects, "a", "Remote", "20200601T000000Z");
+        run(&mut storage, &client, &mut state);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("caldav.toml");
+        state.save(&path).unwrap();
+        let mut state = SyncState::load(&path).unwrap();
+        assert_eq!(state.url, client.url());
+        assert_eq!(
+            run(&mut storage, &client, &mut state),
+            SyncReport::default()
+        );
+    }
+
+    #[test]
+    pub fn multistatus() {
+        let body = r#"<?xml version="1.0"?>
+<multistatus xmlns="DAV:" xmlns:cal="urn:ietf:params:xml:ns:caldav">
+ <response><href>/c/</href><propstat><prop><getetag/></prop></propstat></response>
+ <response>
+  <href>/c/a.ics</href>
+  <propstat><prop>
+   <getetag>"x&amp;1"</getetag>
+   <cal:calendar-data>BEGIN:VCALENDAR&#13;
+BEGIN:VEVENT&#13;
+UID:a&amp;b&#13;
+DTSTART:20200601T100000Z&#13;
+SUMMARY:&lt;tea&gt;&#13;
+END:VEVENT&#13;
+END:VCALENDAR&#13;
+</cal:calendar-data>
+  </prop></propstat>
+ </response>
+</multistatus>"#;
+        let remotes = parse_multistatus(body).unwrap();
+        assert_eq!(remotes.len(), 1);
+        assert_eq!(remotes[0].href, "/c/a.ics");
+        assert_eq!(remotes[0].etag.as_deref(), Some("\"x&1\""));
+        assert_eq!(remotes[0].event.event.id, "a&b");
+        assert_eq!(remotes[0].event.event.description, "<tea>");
+    }
+
+    #[test]
+    pub fn hrefs() {
+        let client = Client::new("https://dav.example.com:5232/user/cal");
+        assert_eq!(client.url(), "https://dav.example.com:5232/user/cal/");
+        assert_eq!(
+            client.resolve("/user/cal/x.ics"),
+            "https://dav.example.com:5232/user/cal/x.ics"
+        );
+        assert_eq!(
+            client.resolve("x.ics"),
+            "https://dav.example.com:5232/user/cal/x.ics"
+        );
+        assert_eq!(
+            client.href_for("a b/c"),
+            "https://dav.example.com:5232/user/cal/a%20b%2Fc.ics"
+        );
+        assert_ne!(client.href_for("a b"), client.href_for("a_b"));
+    }
+}
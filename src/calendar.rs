@@ -0,0 +1,210 @@
+/*
+   TU - Time's Up!
+   Copyright (C) 2020  Julio Biason
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Events laid out in a calendar grid.
+
+use chrono::prelude::*;
+use chrono::Duration;
+
+use crate::clock::Clock;
+use crate::date::Date;
+use crate::date_errors::DateError;
+use crate::event::Event;
+use crate::eventlist::EventList;
+
+/// A day in the calendar grid.
+#[derive(Debug)]
+pub struct CalendarDay<'a> {
+    /// The day.
+    pub date: Date,
+    /// True if the day is only in the grid to fill the week, and is not part
+    /// of the period being shown.
+    pub outside: bool,
+    /// True if the day is today.
+    pub today: bool,
+    /// Events due in the day.
+    pub events: Vec<&'a Event>,
+}
+
+/// A grid of weeks, from Monday to Sunday.
+#[derive(Debug)]
+pub struct Calendar<'a> {
+    /// What the calendar shows, like "June 2020".
+    pub title: String,
+    /// The weeks, each with seven days.
+    pub weeks: Vec<Vec<CalendarDay<'a>>>,
+}
+
+impl<'a> Calendar<'a> {
+    /// The calendar for a month.
+    pub fn month(
+        list: &'a EventList,
+        year: u16,
+        month: u8,
+        clock: &dyn Clock,
+    ) -> Result<Self, DateError> {
+        let first = Date::new(year, month, 1)?.0;
+        // December ends the same way every year, even the last one.
+        let last = if month == 12 {
+            Date::new(year, 12, 31)?.0
+        } else {
+            Date::new(year, month + 1, 1)?.0 - Duration::days(1)
+        };
+        Ok(Calendar::build(
+            list,
+            first.format("%B %Y").to_string(),
+            first,
+            last,
+            clock,
+        ))
+    }
+
+    /// The calendar for a number of weeks, starting in the current one.
+    pub fn weeks(list: &'a EventList, count: u16, clock: &dyn Clock) -> Self {
+        let today = clock.today();
+        let first = monday(today);
+        let last = first + Duration::weeks(count.max(1) as i64) - Duration::days(1);
+        Calendar::build(
+            list,
+            format!(
+                "{} to {}",
+                first.format("%Y-%m-%d"),
+                last.format("%Y-%m-%d")
+            ),
+            first,
+            last,
+            clock,
+        )
+    }
+
+    /// The events in the days of the period, in due order.
+    pub fn events(&self) -> impl Iterator<Item = &'a Event> + '_ {
+        self.weeks
+            .iter()
+            .flatten()
+            .filter(|day| !day.outside)
+            .flat_map(|day| day.events.iter().copied())
+    }
+
+    fn build(
+        list: &'a EventList,
+        title: String,
+        first: NaiveDate,
+        last: NaiveDate,
+        clock: &dyn Clock,
+    ) -> Self {
+        let today = clock.today();
+        let mut weeks = Vec::new();
+        let mut day = monday(first);
+        while day <= last {
+            let week = (0..7)
+                .map(|offset| {
+                    let date = day + Duration::days(offset);
+                    CalendarDay {
+                        date: Date(date),
+                        outside: date < first || date > last,
+                        today: date == today,
+                        events: list
+                            .iter()
+                            .filter(|event| event.due.date().0 == date)
+                            .collect(),
+                    }
+                })
+                .collect();
+            weeks.push(week);
+            day += Duration::weeks(1);
+        }
+        Self { title, weeks }
+    }
+}
+
+/// The Monday of the week the date is in.
+fn monday(date: NaiveDate) -> NaiveDate {
+    date - Duration::days(date.weekday().num_days_from_monday() as i64)
+}
+
+#[cfg(test)]
+mod calendar_test {
+    use super::Calendar;
+    use crate::clock::FixedClock;
+    use crate::date::Date;
+    use crate::datetime::DateTime;
+    use crate::event::Event;
+    use crate::eventlist::EventList;
+
+    fn list() -> EventList {
+        vec![
+            Event::new_on_date("1", "first", &Date::new(2020, 6, 1).unwrap()).unwrap(),
+            Event::new_on_date_time("2", "second", &DateTime::new(2020, 6, 30, 9, 0).unwrap())
+                .unwrap(),
+            Event::new_on_date("3", "outside", &Date::new(2020, 7, 1).unwrap()).unwrap(),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    #[test]
+    pub fn month() {
+        let list = list();
+        let calendar =
+            Calendar::month(&list, 2020, 6, &FixedClock::at(2020, 6, 10, 12, 0)).unwrap();
+        assert_eq!(calendar.title, "June 2020");
+        // June 2020 starts on a Monday and ends on a Tuesday.
+        assert_eq!(calendar.weeks.len(), 5);
+        assert!(calendar.weeks.iter().all(|week| week.len() == 7));
+        assert_eq!(calendar.weeks[0][0].date.day(), 1);
+        assert!(!calendar.weeks[0][0].outside);
+        assert_eq!(calendar.weeks[0][0].events.len(), 1);
+        assert!(calendar.weeks[1][2].today);
+        assert!(calendar.weeks[4][2].outside);
+
+        let ids: Vec<&str> = calendar.events().map(|event| event.id.as_str()).collect();
+        assert_eq!(ids, vec!["1", "2"]);
+    }
+
+    #[test]
+    pub fn december() {
+        let list = list();
+        let calendar =
+            Calendar::month(&list, 2020, 12, &FixedClock::at(2020, 6, 10, 12, 0)).unwrap();
+        assert_eq!(calendar.title, "December 2020");
+        assert!(calendar.events().next().is_none());
+        let last = calendar.weeks.last().unwrap();
+        assert!(last.iter().any(|day| !day.outside && day.date.day() == 31));
+
+        let calendar =
+            Calendar::month(&list, u16::MAX, 12, &FixedClock::at(2020, 6, 10, 12, 0)).unwrap();
+        assert!(calendar.title.ends_with("65535"));
+    }
+
+    #[test]
+    pub fn invalid_month() {
+        let list = list();
+        assert!(Calendar::month(&list, 2020, 13, &FixedClock::at(2020, 6, 10, 12, 0)).is_err());
+    }
+
+    #[test]
+    pub fn weeks() {
+        let list = list();
+        let calendar = Calendar::weeks(&list, 4, &FixedClock::at(2020, 6, 10, 12, 0));
+        assert_eq!(calendar.title, "2020-06-08 to 2020-07-05");
+        assert_eq!(calendar.weeks.len(), 4);
+        let ids: Vec<&str> = calendar.events().map(|event| event.id.as_str()).collect();
+        assert_eq!(ids, vec!["2", "3"]);
+    }
+}
@@ -0,0 +1,88 @@
+/*
+   TU - Time's Up!
+   Copyright (C) 2020  Julio Biason
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Where the current time comes from.
+//!
+//! Everything that depends on "now" (ETAs, removing outdated events) asks a
+//! [`Clock`], so the time can be fixed for tests or to see the events as of
+//! another moment.
+
+use chrono::prelude::*;
+
+use crate::datetime::DateTime;
+
+/// A source for the current time.
+pub trait Clock {
+    /// The current date and time.
+    fn now(&self) -> chrono::DateTime<Local>;
+
+    /// The current day.
+    fn today(&self) -> NaiveDate {
+        self.now().date().naive_local()
+    }
+}
+
+/// The system clock.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> chrono::DateTime<Local> {
+        Local::now()
+    }
+}
+
+/// A clock stopped at a specific moment.
+#[derive(Debug, Copy, Clone)]
+pub struct FixedClock(chrono::DateTime<Local>);
+
+impl FixedClock {
+    /// A clock that is always at the date and time.
+    pub fn new(at: &DateTime) -> Self {
+        Self(at.0)
+    }
+}
+
+#[cfg(test)]
+impl FixedClock {
+    /// A clock at the date and time, for tests.
+    pub(crate) fn at(year: u16, month: u8, day: u8, hour: u8, minute: u8) -> Self {
+        Self::new(&DateTime::new(year, month, day, hour, minute).unwrap())
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> chrono::DateTime<Local> {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod clock_test {
+    use chrono::prelude::*;
+
+    use super::Clock;
+    use super::FixedClock;
+
+    #[test]
+    pub fn fixed() {
+        let clock = FixedClock::at(2020, 6, 2, 23, 59);
+        assert_eq!(clock.today(), NaiveDate::from_ymd(2020, 6, 2));
+        assert_eq!(clock.now().hour(), 23);
+    }
+}
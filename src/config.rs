@@ -0,0 +1,314 @@
+/*
+   TU - Time's Up!
+   Copyright (C) 2020  Julio Biason
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! User configuration.
+//!
+//! The configuration lives in `tu.toml`, inside the user configuration
+//! directory. Everything is optional; a missing file means the defaults.
+//!
+//! ```toml
+//! id_scheme = "words"
+//!
+//! [storage]
+//! backend = "sqlite"
+//! path = "/home/me/events.sqlite"
+//!
+//! [caldav]
+//! url = "https://dav.example.com/me/calendar/"
+//! username = "me"
+//! policy = "newest"
+//!
+//! [sync]
+//! remote = "git@example.com:team/countdowns.git"
+//! ```
+
+use std::fmt;
+use std::fs::File;
+use std::io::{ErrorKind, Read};
+use std::path::PathBuf;
+
+use dirs::config_dir;
+use serde_derive::Deserialize;
+
+use crate::caldav::Policy;
+use crate::eventlist::EventListError;
+use crate::gitsync::Committing;
+use crate::idgen::IdScheme;
+use crate::storage::Sqlite;
+use crate::storage::Storage;
+use crate::storage::TomlFile;
+
+static FILENAME: &str = "tu.toml";
+
+/// The user configuration.
+#[derive(Deserialize, Debug, Default)]
+pub struct Config {
+    /// How IDs for new events are generated.
+    #[serde(default)]
+    pub id_scheme: IdScheme,
+    /// Where the events are stored.
+    #[serde(default)]
+    pub storage: StorageConfig,
+    /// The CalDAV collection to synchronize with, if any.
+    pub caldav: Option<CalDavConfig>,
+    /// The git remote to share the events with, if any.
+    pub sync: Option<SyncConfig>,
+}
+
+/// Storage options.
+#[derive(Deserialize, Debug, Default)]
+pub struct StorageConfig {
+    /// Which backend keeps the events.
+    #[serde(default)]
+    pub backend: Backend,
+    /// Where the backend keeps the events; each backend has its own default.
+    pub path: Option<PathBuf>,
+}
+
+/// CalDAV synchronization options.
+#[derive(Deserialize, Debug)]
+pub struct CalDavConfig {
+    /// URL of the calendar collection.
+    pub url: String,
+    /// User for the server, if it needs authentication.
+    pub username: Option<String>,
+    /// Password for the user; better kept in `TU_CALDAV_PASSWORD`.
+    pub password: Option<String>,
+    /// Which side stays when an event changed in both.
+    #[serde(default)]
+    pub policy: Policy,
+}
+
+/// Git synchronization options.
+#[derive(Deserialize, Debug)]
+pub struct SyncConfig {
+    /// The remote repository, as git takes it: an URL or a path.
+    pub remote: String,
+    /// The branch in the remote.
+    #[serde(default = "default_branch")]
+    pub branch: String,
+}
+
+fn default_branch() -> String {
+    "main".into()
+}
+
+/// Available storage backends.
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// A single TOML file.
+    #[default]
+    Toml,
+    /// A SQLite database.
+    Sqlite,
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Backend::Toml => write!(f, "toml"),
+            Backend::Sqlite => write!(f, "sqlite"),
+        }
+    }
+}
+
+/// Errors when reading the configuration.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The configuration file can't be read.
+    Unreadable,
+    /// The configuration file is not valid.
+    BrokenFormat(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Unreadable => write!(f, "can't read the configuration file"),
+            ConfigError::BrokenFormat(reason) => {
+                write!(f, "the configuration file is broken: {}", reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<std::io::Error> for ConfigError {
+    fn from(_: std::io::Error) -> ConfigError {
+        ConfigError::Unreadable
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(error: toml::de::Error) -> ConfigError {
+        ConfigError::BrokenFormat(error.to_string())
+    }
+}
+
+impl Config {
+    /// Load the configuration from the user configuration directory.
+    pub fn load() -> Result<Self, ConfigError> {
+        match config_dir() {
+            Some(mut path) => {
+                path.push(FILENAME);
+                Config::load_from(path)
+            }
+            None => Ok(Config::default()),
+        }
+    }
+
+    /// Load the configuration from a specific file.
+    pub fn load_from(path: PathBuf) -> Result<Self, ConfigError> {
+        let mut fp = match File::open(path) {
+            Ok(fp) => fp,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(Config::default()),
+            Err(error) => return Err(error.into()),
+        };
+        let mut content = String::new();
+        fp.read_to_string(&mut content)?;
+        Config::parse(&content)
+    }
+
+    /// Parse the configuration from a string.
+    pub fn parse(content: &str) -> Result<Self, ConfigError> {
+        Ok(toml::from_str(content)?)
+    }
+
+    /// Open the configured storage.
+    pub fn open_storage(&self) -> Result<Box<dyn Storage>, EventListError> {
+        self.open_backend(self.storage.backend)
+    }
+
+    /// Open a specific storage backend, at the path given by
+    /// [`backend_path`](Config::backend_path).
+    pub fn open_backend(&self, backend: Backend) -> Result<Box<dyn Storage>, EventListError> {
+        let path = self.backend_path(backend)?;
+        match backend {
+            Backend::Toml if self.commits(backend) => {
+                Ok(Box::new(Committing::new(TomlFile::new(path))))
+            }
+            Backend::Toml => Ok(Box::new(TomlFile::new(path))),
+            Backend::Sqlite => Ok(Box::new(Sqlite::open(path)?)),
+        }
+    }
+
+    /// True if changes to the backend are committed for `tu sync`: only the
+    /// configured toml file, and only when its path is set, so the commits
+    /// never land in a repository of the whole config directory.
+    fn commits(&self, backend: Backend) -> bool {
+        self.sync.is_some()
+            && backend == Backend::Toml
+            && self.storage.backend == Backend::Toml
+            && self.storage.path.is_some()
+    }
+
+    /// Where a backend keeps its events: the configured path if it is the
+    /// configured backend, or its default location otherwise.
+    pub fn backend_path(&self, backend: Backend) -> Result<PathBuf, EventListError> {
+        match &self.storage.path {
+            Some(path) if backend == self.storage.backend => Ok(path.clone()),
+            _ => match backend {
+                Backend::Toml => TomlFile::default_path(),
+                Backend::Sqlite => Sqlite::default_path(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod config_test {
+    use super::Backend;
+    use super::Config;
+    use crate::caldav::Policy;
+    use crate::idgen::IdScheme;
+
+    #[test]
+    pub fn empty_is_default() {
+        let config = Config::parse("").unwrap();
+        assert_eq!(config.storage.backend, Backend::Toml);
+        assert!(config.storage.path.is_none());
+        assert_eq!(config.id_scheme, IdScheme::Short);
+    }
+
+    #[test]
+    pub fn id_scheme() {
+        let config = Config::parse("id_scheme = \"sequential\"").unwrap();
+        assert_eq!(config.id_scheme, IdScheme::Sequential);
+    }
+
+    #[test]
+    pub fn storage_path() {
+        let config =
+            Config::parse("[storage]\nbackend = \"toml\"\npath = \"/tmp/x.toml\"").unwrap();
+        assert_eq!(config.storage.path.unwrap().to_str(), Some("/tmp/x.toml"));
+    }
+
+    #[test]
+    pub fn path_only_for_configured_backend() {
+        let config =
+            Config::parse("[storage]\nbackend = \"sqlite\"\npath = \"/tmp/x.db\"").unwrap();
+        assert_eq!(
+            config.backend_path(Backend::Sqlite).unwrap().to_str(),
+            Some("/tmp/x.db")
+        );
+        assert_ne!(
+            config.backend_path(Backend::Toml).unwrap().to_str(),
+            Some("/tmp/x.db")
+        );
+    }
+
+    #[test]
+    pub fn unknown_backend() {
+        assert!(Config::parse("[storage]\nbackend = \"floppy\"").is_err());
+    }
+
+    #[test]
+    pub fn caldav() {
+        assert!(Config::parse("").unwrap().caldav.is_none());
+        let config =
+            Config::parse("[caldav]\nurl = \"http://localhost:5232/me/cal/\"\npolicy = \"remote\"")
+                .unwrap();
+        let caldav = config.caldav.unwrap();
+        assert_eq!(caldav.url, "http://localhost:5232/me/cal/");
+        assert!(caldav.username.is_none());
+        assert_eq!(caldav.policy, Policy::Remote);
+    }
+
+    #[test]
+    pub fn sync() {
+        assert!(Config::parse("").unwrap().sync.is_none());
+        let config = Config::parse("[sync]\nremote = \"/srv/events.git\"").unwrap();
+        let sync = config.sync.unwrap();
+        assert_eq!(sync.remote, "/srv/events.git");
+        assert_eq!(sync.branch, "main");
+    }
+
+    #[test]
+    pub fn commits_only_with_a_path() {
+        let config = Config::parse("[sync]\nremote = \"/srv/events.git\"").unwrap();
+        assert!(!config.commits(Backend::Toml));
+        let config = Config::parse(
+            "[storage]\npath = \"/srv/events/events.toml\"\n[sync]\nremote = \"/srv/events.git\"",
+        )
+        .unwrap();
+        assert!(config.commits(Backend::Toml));
+        assert!(!config.commits(Backend::Sqlite));
+    }
+}
@@ -0,0 +1,565 @@
+/*
+   TU - Time's Up!
+   Copyright (C) 2020  Julio Biason
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Moving events in and out of CSV files, like the ones saved by
+//! spreadsheets.
+//!
+//! Exported files have a header and the columns `id`, `date`, `time`,
+//! `description`, `tags`, `created` and `priority`; the default import
+//! options read the same columns back, so an exported file can be imported
+//! as is.
+
+use std::fmt;
+use std::fmt::Write as _;
+use std::io;
+
+use csv::ReaderBuilder;
+use csv::StringRecord;
+use csv::Writer;
+
+use crate::date::Date;
+use crate::date_errors::DateError;
+use crate::datetime::DateTime;
+use crate::event::NewEvent;
+use crate::eventlist::EventList;
+use crate::eventtype::EventType;
+use crate::priority::Priority;
+
+static HEADER: [&str; 7] = [
+    "id",
+    "date",
+    "time",
+    "description",
+    "tags",
+    "created",
+    "priority",
+];
+
+/// Formats of the dates and times in the file, in strftime-like syntax;
+/// when not set, dates are `YYYY-MM-DD` and times `HH:MM`.
+#[derive(Debug, Clone, Default)]
+pub struct DateFormats {
+    /// Format of the dates.
+    pub date: Option<String>,
+    /// Format of the times.
+    pub time: Option<String>,
+}
+
+impl DateFormats {
+    fn parse_date(&self, date: &str) -> Result<Date, DateError> {
+        match &self.date {
+            None => Date::try_from(date),
+            Some(format) => Date::parse(date, format),
+        }
+    }
+
+    fn parse_date_time(&self, date: &str, time: &str) -> Result<DateTime, DateError> {
+        match (&self.date, &self.time) {
+            (None, None) => DateTime::try_from(date, time),
+            (date_format, time_format) => DateTime::parse(
+                date,
+                time,
+                date_format.as_deref().unwrap_or("%Y-%m-%d"),
+                time_format.as_deref().unwrap_or("%H:%M"),
+            ),
+        }
+    }
+
+    fn format_date(&self, date: &Date) -> Result<String, CsvError> {
+        let format = self.date.as_deref().unwrap_or("%Y-%m-%d");
+        date.format(format)
+            .ok_or_else(|| CsvError::InvalidFormat(format.into()))
+    }
+
+    fn format_time(&self, datetime: &DateTime) -> Result<String, CsvError> {
+        let format = self.time.as_deref().unwrap_or("%H:%M");
+        let mut text = String::new();
+        write!(text, "{}", datetime.0.format(format))
+            .map_err(|_| CsvError::InvalidFormat(format.into()))?;
+        Ok(text)
+    }
+}
+
+/// A column of the file, either by its name in the header or by its
+/// position, starting at 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Column {
+    /// The column with this name in the header, ignoring case.
+    Name(String),
+    /// The column in this position, starting at 1.
+    Number(usize),
+}
+
+impl Column {
+    /// A column by number if the value is a positive number, by name
+    /// otherwise.
+    pub fn parse(value: &str) -> Self {
+        match value.trim().parse() {
+            Ok(number) if number > 0 => Column::Number(number),
+            _ => Column::Name(value.trim().into()),
+        }
+    }
+
+    /// Position of the column in the records, starting at 0.
+    fn position(&self, header: Option<&StringRecord>) -> Option<usize> {
+        match self {
+            Column::Number(number) => Some(number - 1),
+            Column::Name(name) => header?
+                .iter()
+                .position(|title| title.trim().eq_ignore_ascii_case(name)),
+        }
+    }
+}
+
+impl fmt::Display for Column {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Column::Name(name) => write!(f, "'{}'", name),
+            Column::Number(number) => write!(f, "{}", number),
+        }
+    }
+}
+
+/// Where to find the parts of the events when importing.
+#[derive(Debug, Clone)]
+pub struct ImportOptions {
+    /// Column with the day of the event.
+    pub date: Column,
+    /// Column with the time of the event; events without a time are all
+    /// day events. If not set, the `time` column is used if there is one.
+    pub time: Option<Column>,
+    /// Column with what the event is about.
+    pub description: Column,
+    /// Column with the tags, separated by commas. If not set, the `tags`
+    /// column is used if there is one.
+    pub tags: Option<Column>,
+    /// Formats of the dates and times.
+    pub formats: DateFormats,
+    /// The first line of the file is a header with the column names.
+    pub header: bool,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            date: Column::Name("date".into()),
+            time: None,
+            description: Column::Name("description".into()),
+            tags: None,
+            formats: DateFormats::default(),
+            header: true,
+        }
+    }
+}
+
+/// What is wrong with a row of the file.
+#[derive(Debug, PartialEq, Eq)]
+pub enum RowProblem {
+    /// The date or time can't be read.
+    InvalidDate(DateError),
+    /// There is no date.
+    MissingDate,
+    /// There is no description.
+    MissingDescription,
+    /// The priority is not one of the known ones.
+    InvalidPriority(String),
+}
+
+/// A row of the file that can't be imported.
+#[derive(Debug, PartialEq, Eq)]
+pub struct RowError {
+    /// Number of the row, counting the header, as spreadsheets show it.
+    pub row: u64,
+    /// What is wrong with it.
+    pub problem: RowProblem,
+}
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "row {}: ", self.row)?;
+        match &self.problem {
+            RowProblem::InvalidDate(error) => write!(f, "{}", error),
+            RowProblem::MissingDate => write!(f, "no date"),
+            RowProblem::MissingDescription => write!(f, "no description"),
+            RowProblem::InvalidPriority(priority) => write!(
+                f,
+                "invalid priority '{}'; expected low, normal, high or critical",
+                priority
+            ),
+        }
+    }
+}
+
+/// Errors when importing or exporting events.
+#[derive(Debug)]
+pub enum CsvError {
+    /// The file can't be read or written.
+    Unreadable(String),
+    /// The column is not in the file.
+    MissingColumn(Column),
+    /// Some rows can't be turned into events, so nothing was imported.
+    InvalidRows(Vec<RowError>),
+    /// The date or time format can't be used to write the events, like a
+    /// time in the date format.
+    InvalidFormat(String),
+}
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CsvError::Unreadable(reason) => write!(f, "{}", reason),
+            CsvError::MissingColumn(column) => write!(f, "there is no column {}", column),
+            CsvError::InvalidFormat(format) => {
+                write!(f, "can't write the dates with the format '{}'", format)
+            }
+            CsvError::InvalidRows(errors) => {
+                write!(f, "nothing was imported; fix these rows and try again:")?;
+                for error in errors {
+                    write!(f, "\n  {}", error)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for CsvError {}
+
+impl From<csv::Error> for CsvError {
+    fn from(error: csv::Error) -> CsvError {
+        CsvError::Unreadable(error.to_string())
+    }
+}
+
+impl From<io::Error> for CsvError {
+    fn from(error: io::Error) -> CsvError {
+        CsvError::Unreadable(error.to_string())
+    }
+}
+
+/// Write all events in the list as CSV.
+pub fn export<W: io::Write>(
+    list: &EventList,
+    writer: W,
+    formats: &DateFormats,
+) -> Result<(), CsvError> {
+    let mut writer = Writer::from_writer(writer);
+    writer.write_record(HEADER)?;
+    for event in list {
+        let time = match &event.due {
+            EventType::AllDay(_) => String::new(),
+            EventType::AtTime(datetime) => formats.format_time(datetime)?,
+        };
+        writer.write_record([
+            event.id.as_str(),
+            &formats.format_date(&event.due.date())?,
+            &time,
+            &event.description,
+            &event.tags.join(","),
+            &event
+                .created_at
+                .map(|created| created.to_rfc3339())
+                .unwrap_or_default(),
+            &event.priority.to_string(),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Read new events from CSV. Rows with all cells empty are skipped; if any
+/// other row can't be read, all the problems are returned instead.
+pub fn import<R: io::Read>(reader: R, options: &ImportOptions) -> Result<Vec<NewEvent>, CsvError> {
+    let mut reader = ReaderBuilder::new()
+        .has_headers(options.header)
+        .flexible(true)
+        .from_reader(reader);
+    let header = if options.header {
+        Some(reader.headers()?.clone())
+    } else {
+        None
+    };
+
+    let required = |column: &Column| {
+        column
+            .position(header.as_ref())
+            .ok_or_else(|| CsvError::MissingColumn(column.clone()))
+    };
+    let optional = |column: &Option<Column>, default: &str| match column {
+        Some(column) => required(column).map(Some),
+        None => Ok(Column::Name(default.into()).position(header.as_ref())),
+    };
+    let date = required(&options.date)?;
+    let description = required(&options.description)?;
+    let time = optional(&options.time, "time")?;
+    let tags = optional(&options.tags, "tags")?;
+    let priority = optional(&None, "priority")?;
+
+    let mut events = Vec::new();
+    let mut errors = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        if record.iter().all(|cell| cell.trim().is_empty()) {
+            continue;
+        }
+        let cell = |position: Option<usize>| {
+            position
+                .and_then(|position| record.get(position))
+                .map(str::trim)
+                .unwrap_or_default()
+        };
+
+        let row = record
+            .position()
+            .map_or(0, |position| position.record() + 1);
+        match new_event(
+            cell(Some(date)),
+            cell(time),
+            cell(Some(description)),
+            cell(tags),
+            cell(priority),
+            &options.formats,
+        ) {
+            Ok(new) => events.push(new),
+            Err(problem) => errors.push(RowError { row, problem }),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(events)
+    } else {
+        Err(CsvError::InvalidRows(errors))
+    }
+}
+
+fn new_event(
+    date: &str,
+    time: &str,
+    description: &str,
+    tags: &str,
+    priority: &str,
+    formats: &DateFormats,
+) -> Result<NewEvent, RowProblem> {
+    if date.is_empty() {
+        return Err(RowProblem::MissingDate);
+    }
+    if description.is_empty() {
+        return Err(RowProblem::MissingDescription);
+    }
+    let new = if time.is_empty() {
+        let date = formats.parse_date(date).map_err(RowProblem::InvalidDate)?;
+        NewEvent::on_date(description, &date)
+    } else {
+        let datetime = formats
+            .parse_date_time(date, time)
+            .map_err(RowProblem::InvalidDate)?;
+        NewEvent::at_time(description, &datetime)
+    };
+    let tags = tags
+        .split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(String::from)
+        .collect();
+    let priority = match priority {
+        "" => Priority::Normal,
+        priority => Priority::from_name(priority)
+            .ok_or_else(|| RowProblem::InvalidPriority(priority.into()))?,
+    };
+    Ok(new.with_tags(tags).with_priority(priority))
+}
+
+#[cfg(test)]
+mod csvfile_test {
+    use super::export;
+    use super::import;
+    use super::Column;
+    use super::CsvError;
+    use super::DateFormats;
+    use super::ImportOptions;
+    use super::RowError;
+    use super::RowProblem;
+    use crate::clock::FixedClock;
+    use crate::date::Date;
+    use crate::date_errors::DateError;
+    use crate::datetime::DateTime;
+    use crate::event::Event;
+    use crate::event::NewEvent;
+    use crate::eventlist::EventList;
+    use crate::eventtype::EventType;
+    use crate::priority::Priority;
+
+    fn list() -> EventList {
+        let new = vec![
+            NewEvent::on_date("Pay taxes", &Date::new(2020, 6, 13).unwrap())
+                .with_tags(vec!["home".into(), "money".into()])
+                .with_priority(Priority::High),
+            NewEvent::at_time(
+                "Call, then \"wait\"",
+                &DateTime::new(2020, 6, 11, 14, 5).unwrap(),
+            ),
+        ];
+        new.into_iter()
+            .enumerate()
+            .map(|(id, new)| {
+                Event::from_new(&id.to_string(), new, &FixedClock::at(2020, 6, 10, 12, 0))
+            })
+            .collect()
+    }
+
+    fn export_text(formats: &DateFormats) -> String {
+        let mut output = Vec::new();
+        export(&list(), &mut output, formats).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    pub fn export_events() {
+        let text = export_text(&DateFormats::default());
+        let mut lines = text.lines();
+        assert_eq!(
+            lines.next(),
+            Some("id,date,time,description,tags,created,priority")
+        );
+        let call = lines.next().unwrap();
+        assert!(
+            call.starts_with("1,2020-06-11,14:05,\"Call, then \"\"wait\"\"\",,2020-06-10T12:00:59")
+        );
+        let taxes = lines.next().unwrap();
+        assert!(taxes.starts_with("0,2020-06-13,,Pay taxes,\"home,money\","));
+        assert!(taxes.ends_with(",high"));
+    }
+
+    #[test]
+    pub fn round_trip() {
+        let formats = DateFormats {
+            date: Some("%d/%m/%Y".into()),
+            time: Some("%I:%M %p".into()),
+        };
+        let text = export_text(&formats);
+        assert!(text.contains("11/06/2020,02:05 PM"));
+        let broken = DateFormats {
+            date: Some("%H".into()),
+            time: None,
+        };
+        assert_eq!(
+            export(&list(), Vec::new(), &broken)
+                .unwrap_err()
+                .to_string(),
+            "can't write the dates with the format '%H'"
+        );
+
+        let options = ImportOptions {
+            formats,
+            ..ImportOptions::default()
+        };
+        let events = import(text.as_bytes(), &options).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].description, "Call, then \"wait\"");
+        assert!(matches!(events[0].due, EventType::AtTime(_)));
+        assert_eq!(
+            events[0].due.timestamp(),
+            list().iter().next().unwrap().due.timestamp()
+        );
+        assert_eq!(events[1].tags, vec!["home", "money"]);
+        assert_eq!(events[1].priority, Priority::High);
+        assert_eq!(events[0].priority, Priority::Normal);
+        assert!(matches!(events[1].due, EventType::AllDay(_)));
+    }
+
+    #[test]
+    pub fn column_mapping() {
+        let text = "Milestone,Owner,Deadline\nLaunch,ana,2020-07-01\n\n,,\nReview,bob,2020-06-20\n";
+        let options = ImportOptions {
+            date: Column::Name("deadline".into()),
+            description: Column::Number(1),
+            ..ImportOptions::default()
+        };
+        let events = import(text.as_bytes(), &options).unwrap();
+        let descriptions: Vec<&str> = events.iter().map(|e| e.description.as_str()).collect();
+        assert_eq!(descriptions, vec!["Launch", "Review"]);
+    }
+
+    #[test]
+    pub fn missing_column() {
+        let text = "what,when\nLaunch,2020-07-01\n";
+        let options = ImportOptions {
+            date: Column::Name("when".into()),
+            time: Some(Column::Name("hour".into())),
+            description: Column::Name("what".into()),
+            ..ImportOptions::default()
+        };
+        match import(text.as_bytes(), &options) {
+            Err(CsvError::MissingColumn(column)) => assert_eq!(column, Column::Name("hour".into())),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    pub fn reports_all_bad_rows() {
+        let text = "date,time,description\n\
+                    2020-07-01,,fine\n\
+                    2020-13-01,,bad date\n\
+                    2020-07-01,25:00,bad time\n\
+                    2020-07-01,,\n";
+        match import(text.as_bytes(), &ImportOptions::default()) {
+            Err(CsvError::InvalidRows(errors)) => assert_eq!(
+                errors,
+                vec![
+                    RowError {
+                        row: 3,
+                        problem: RowProblem::InvalidDate(DateError::NoSuchDate(
+                            "2020-13-01".into()
+                        )),
+                    },
+                    RowError {
+                        row: 4,
+                        problem: RowProblem::InvalidDate(DateError::NoSuchTime(
+                            "25:00 on 2020-07-01".into()
+                        )),
+                    },
+                    RowError {
+                        row: 5,
+                        problem: RowProblem::MissingDescription,
+                    },
+                ]
+            ),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    pub fn without_header() {
+        let text = "2020-07-01,Launch\n";
+        let options = ImportOptions {
+            date: Column::Number(1),
+            description: Column::Number(2),
+            header: false,
+            ..ImportOptions::default()
+        };
+        let events = import(text.as_bytes(), &options).unwrap();
+        assert_eq!(events[0].description, "Launch");
+    }
+
+    #[test]
+    pub fn column_parse() {
+        assert_eq!(Column::parse("3"), Column::Number(3));
+        assert_eq!(Column::parse("Due date"), Column::Name("Due date".into()));
+        assert_eq!(Column::parse("0"), Column::Name("0".into()));
+    }
+}
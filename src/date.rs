@@ -16,71 +16,104 @@
    along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
-#![deny(missing_docs)]
+//! Dates for all day events.
 
 // TODO trait TryFrom
 
+use std::convert::TryFrom;
+use std::fmt;
+use std::fmt::Write;
+
 use chrono::prelude::*;
 use chrono::LocalResult;
 use serde_derive::Deserialize;
 use serde_derive::Serialize;
 
+use crate::clock::Clock;
+use crate::date_errors::split_numbers;
 use crate::date_errors::DateError;
 
-#[derive(Debug, Serialize, Deserialize, Copy, Clone)]
-pub struct Date(chrono::NaiveDate);
+/// A day in the calendar, used for events that take the whole day.
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+pub struct Date(pub(crate) chrono::NaiveDate);
 
 impl Date {
     /// Returns Ok with the Date or Error in an invalid Date.
     pub fn new(year: u16, month: u8, day: u8) -> Result<Date, DateError> {
         match Local.ymd_opt(year as i32, month as u32, day as u32) {
             LocalResult::Single(x) => Ok(Date(x.naive_local())),
-            LocalResult::None => Err(DateError::InvalidDate),
-            LocalResult::Ambiguous(_, _) => Err(DateError::InvalidDate),
+            LocalResult::None | LocalResult::Ambiguous(_, _) => Err(DateError::NoSuchDate(
+                format!("{:04}-{:02}-{:02}", year, month, day),
+            )),
         }
     }
 
+    /// The year of the date.
     pub fn year(&self) -> u16 {
         self.0.year() as u16
     }
 
+    /// The month of the date, starting at 1.
     pub fn month(&self) -> u8 {
         self.0.month() as u8
     }
 
+    /// The day of the month.
     pub fn day(&self) -> u8 {
         self.0.day() as u8
     }
 
-    /// Try to convert a string to a Date.
+    /// Try to convert a string in the YYYY-MM-DD format to a Date.
     pub fn try_from(value: &str) -> Result<Self, DateError> {
-        let mut frags = value.split("-");
-        Date::new(
-            frags.next().ok_or(DateError::InvalidDate)?.parse()?,
-            frags.next().ok_or(DateError::InvalidDate)?.parse()?,
-            frags.next().ok_or(DateError::InvalidDate)?.parse()?,
-        )
+        let parts: Vec<u16> = split_numbers(value, '-', 3, "YYYY-MM-DD")?;
+        let small =
+            |part: u16| u8::try_from(part).map_err(|_| DateError::malformed(value, "YYYY-MM-DD"));
+        Date::new(parts[0], small(parts[1])?, small(parts[2])?)
+    }
+
+    /// Convert a string in a strftime-like format to a Date, like
+    /// `Date::parse("26/05/2025", "%d/%m/%Y")`.
+    pub fn parse(value: &str, format: &str) -> Result<Self, DateError> {
+        NaiveDate::parse_from_str(value.trim(), format)
+            .map(Date)
+            .map_err(|error| DateError::from_chrono(error, value, format, DateError::NoSuchDate))
     }
 
     /// Number of days till the date; None if the date is in the past.
-    pub fn eta(&self) -> Option<u16> {
-        let days = (self.0 - Local::today().naive_local()).num_days();
+    pub fn eta(&self, clock: &dyn Clock) -> Option<u16> {
+        let days = (self.0 - clock.today()).num_days();
         if days >= 0 {
-            Some(days as u16)
+            Some(u16::try_from(days).unwrap_or(u16::MAX))
         } else {
             None
         }
     }
 
+    /// The date formatted with a strftime-like format string; None if the
+    /// format is not valid or asks for a time.
+    pub fn format(&self, format: &str) -> Option<String> {
+        let mut text = String::new();
+        write!(text, "{}", self.0.format(format)).ok()?;
+        Some(text)
+    }
+
+    /// Unix timestamp of the last second of the day.
     pub fn timestamp(&self) -> i64 {
         self.0.and_hms(23, 59, 59).timestamp()
     }
 }
 
+impl fmt::Display for Date {
+    /// The date in YYYY-MM-DD format, the same accepted by `try_from`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0.format("%Y-%m-%d"))
+    }
+}
+
 #[cfg(test)]
 mod date_test {
-    use chrono::prelude::*;
-    use chrono::Duration;
+    use crate::clock::FixedClock;
+    use crate::date_errors::DateError;
 
     #[test]
     pub fn invalid_date() {
@@ -105,44 +138,63 @@ mod date_test {
         }
     }
 
+    #[test]
+    pub fn display() {
+        let date = super::Date::new(2025, 5, 6).unwrap();
+        assert_eq!(date.to_string(), "2025-05-06");
+    }
+
     #[test]
     pub fn failed_from_string() {
+        assert_eq!(
+            super::Date::try_from("2020-1270-26").err(),
+            Some(DateError::Malformed {
+                value: "2020-1270-26".into(),
+                expected: "YYYY-MM-DD".into()
+            })
+        );
+        assert!(super::Date::try_from("2020-12").is_err());
+        assert!(super::Date::try_from("2020-12-01-01").is_err());
         assert!(super::Date::try_from("2020-127-26").is_err());
+        assert_eq!(
+            super::Date::try_from("2021-02-30").err(),
+            Some(DateError::NoSuchDate("2021-02-30".into()))
+        );
+    }
+
+    #[test]
+    pub fn parse_with_format() {
+        let date = super::Date::parse("26/05/2025", "%d/%m/%Y").unwrap();
+        assert_eq!(date.to_string(), "2025-05-26");
+        assert!(super::Date::parse("2025-05-26", "%d/%m/%Y").is_err());
+        assert_eq!(
+            super::Date::parse("31/02/2025", "%d/%m/%Y").err(),
+            Some(DateError::NoSuchDate("31/02/2025".into()))
+        );
     }
 
     #[test]
     pub fn eta_tomorrow() {
-        let future = Local::today() + Duration::days(1);
-        let date = super::Date::new(
-            future.year() as u16,
-            future.month() as u8,
-            future.day() as u8,
-        )
-        .unwrap();
-        assert_eq!(date.eta(), Some(1));
+        let date = super::Date::new(2020, 6, 3).unwrap();
+        assert_eq!(date.eta(&FixedClock::at(2020, 6, 2, 12, 0)), Some(1));
     }
 
     #[test]
     pub fn eta_today() {
-        let future = Local::today();
-        let date = super::Date::new(
-            future.year() as u16,
-            future.month() as u8,
-            future.day() as u8,
-        )
-        .unwrap();
-        assert_eq!(date.eta(), Some(0));
+        let date = super::Date::new(2020, 6, 2).unwrap();
+        assert_eq!(date.eta(&FixedClock::at(2020, 6, 2, 12, 0)), Some(0));
     }
 
     #[test]
     pub fn eta_yesterday() {
-        let future = Local::today() - Duration::days(1);
-        let date = super::Date::new(
-            future.year() as u16,
-            future.month() as u8,
-            future.day() as u8,
-        )
-        .unwrap();
-        assert_eq!(date.eta(), None);
+        let date = super::Date::new(2020, 6, 1).unwrap();
+        assert_eq!(date.eta(&FixedClock::at(2020, 6, 2, 12, 0)), None);
+    }
+
+    #[test]
+    pub fn eta_just_before_midnight() {
+        let clock = FixedClock::at(2020, 6, 2, 23, 59);
+        let date = super::Date::new(2020, 6, 3).unwrap();
+        assert_eq!(date.eta(&clock), Some(1));
     }
 }
@@ -16,14 +16,94 @@
    along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
-#[derive(Debug, Eq, PartialEq)]
+//! Errors when building dates.
+
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::format::ParseErrorKind;
+
+/// Errors when building a [`Date`](crate::Date) or a
+/// [`DateTime`](crate::DateTime).
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub enum DateError {
-    /// The date is not valid
+    /// The date is not valid; kept for code matching on it, the other
+    /// variants tell what is wrong.
     InvalidDate,
+    /// The text doesn't follow the expected format.
+    Malformed {
+        /// The text that was parsed.
+        value: String,
+        /// The format it should follow, like `YYYY-MM-DD`.
+        expected: String,
+    },
+    /// The parts are all there, but there is no such day, like 2020-02-30.
+    NoSuchDate(String),
+    /// There is no such time on that day, like 25:00 or a time skipped by a
+    /// daylight saving change.
+    NoSuchTime(String),
+}
+
+impl DateError {
+    pub(crate) fn malformed(value: &str, expected: &str) -> DateError {
+        DateError::Malformed {
+            value: value.into(),
+            expected: expected.into(),
+        }
+    }
+
+    /// Tell apart text in the wrong format from impossible values, like the
+    /// 31st of February, when parsing with a chrono format; `impossible`
+    /// builds the error for the latter.
+    pub(crate) fn from_chrono(
+        error: chrono::ParseError,
+        value: &str,
+        expected: &str,
+        impossible: fn(String) -> DateError,
+    ) -> DateError {
+        match error.kind() {
+            ParseErrorKind::OutOfRange | ParseErrorKind::Impossible => {
+                impossible(value.trim().into())
+            }
+            _ => DateError::malformed(value, expected),
+        }
+    }
+}
+
+impl fmt::Display for DateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DateError::InvalidDate => write!(f, "invalid date"),
+            DateError::Malformed { value, expected } => {
+                write!(f, "'{}' is not in the {} format", value, expected)
+            }
+            DateError::NoSuchDate(date) => write!(f, "there is no day {}", date),
+            DateError::NoSuchTime(time) => write!(f, "there is no time {}", time),
+        }
+    }
 }
 
+impl std::error::Error for DateError {}
+
 impl From<std::num::ParseIntError> for DateError {
     fn from(_: std::num::ParseIntError) -> DateError {
         DateError::InvalidDate
     }
 }
+
+/// Split the value in exactly `count` numbers separated by `separator`,
+/// like the parts of `YYYY-MM-DD`.
+pub(crate) fn split_numbers<T: FromStr>(
+    value: &str,
+    separator: char,
+    count: usize,
+    expected: &str,
+) -> Result<Vec<T>, DateError> {
+    let numbers = value
+        .trim()
+        .split(separator)
+        .map(|part| part.parse::<T>().ok())
+        .collect::<Option<Vec<T>>>()
+        .filter(|numbers| numbers.len() == count);
+    numbers.ok_or_else(|| DateError::malformed(value, expected))
+}
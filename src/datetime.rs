@@ -16,74 +16,144 @@
    along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+//! Dates with time, for events at a specific time.
+
+use std::convert::TryFrom;
+
 use chrono::prelude::*;
 use chrono::LocalResult;
 use serde_derive::Deserialize;
 use serde_derive::Serialize;
 
+use crate::clock::Clock;
+use crate::date::Date;
+use crate::date_errors::split_numbers;
 use crate::date_errors::DateError;
 
-#[derive(Debug, Serialize, Deserialize, Copy, Clone)]
-pub struct DateTime(chrono::DateTime<Local>);
+/// A date and time in the local timezone.
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+pub struct DateTime(pub(crate) chrono::DateTime<Local>);
 
 impl DateTime {
+    /// Returns Ok with the DateTime or Error in an invalid date or time.
     pub fn new(year: u16, month: u8, day: u8, hour: u8, minute: u8) -> Result<Self, DateError> {
+        let date = format!("{:04}-{:02}-{:02}", year, month, day);
         match Local.ymd_opt(year as i32, month as u32, day as u32) {
-            LocalResult::None => Err(DateError::InvalidDate),
-            LocalResult::Ambiguous(_, _) => Err(DateError::InvalidDate),
+            LocalResult::None | LocalResult::Ambiguous(_, _) => Err(DateError::NoSuchDate(date)),
             LocalResult::Single(x) => match x.and_hms_opt(hour as u32, minute as u32, 59) {
                 Some(x) => Ok(DateTime(x)),
-                None => Err(DateError::InvalidDate),
+                None => Err(DateError::NoSuchTime(format!(
+                    "{:02}:{:02} on {}",
+                    hour, minute, date
+                ))),
             },
         }
     }
 
+    /// The first second of the day, for the moments without a time.
+    pub fn start_of_day(date: &Date) -> Result<Self, DateError> {
+        match Local
+            .from_local_datetime(&date.0.and_hms(0, 0, 0))
+            .earliest()
+        {
+            Some(datetime) => Ok(DateTime(datetime)),
+            None => Err(DateError::NoSuchTime(format!("00:00 on {}", date))),
+        }
+    }
+
+    /// The year of the date.
     pub fn year(&self) -> u16 {
         self.0.date().year() as u16
     }
 
+    /// The month of the date, starting at 1.
     pub fn month(&self) -> u8 {
         self.0.date().month() as u8
     }
 
+    /// The day of the month.
     pub fn day(&self) -> u8 {
         self.0.date().day() as u8
     }
 
+    /// The hour, in 24h format.
     pub fn hour(&self) -> u8 {
         self.0.time().hour() as u8
     }
 
+    /// The minutes of the hour.
     pub fn minute(&self) -> u8 {
         self.0.time().minute() as u8
     }
 
-    /// Try to convert a string to a Date.
+    /// Try to convert a date in the YYYY-MM-DD format and a time in the
+    /// HH:MM format to a DateTime.
     pub fn try_from(date: &str, time: &str) -> Result<Self, DateError> {
-        let mut date_frags = date.split("-");
-        let mut time_frags = time.split(":");
+        let date_parts: Vec<u16> = split_numbers(date, '-', 3, "YYYY-MM-DD")?;
+        let time_parts: Vec<u8> = split_numbers(time, ':', 2, "HH:MM")?;
+        let small =
+            |part: u16| u8::try_from(part).map_err(|_| DateError::malformed(date, "YYYY-MM-DD"));
 
         DateTime::new(
-            date_frags.next().ok_or(DateError::InvalidDate)?.parse()?,
-            date_frags.next().ok_or(DateError::InvalidDate)?.parse()?,
-            date_frags.next().ok_or(DateError::InvalidDate)?.parse()?,
-            time_frags.next().ok_or(DateError::InvalidDate)?.parse()?,
-            time_frags.next().ok_or(DateError::InvalidDate)?.parse()?,
+            date_parts[0],
+            small(date_parts[1])?,
+            small(date_parts[2])?,
+            time_parts[0],
+            time_parts[1],
         )
     }
 
-    pub fn eta(&self) -> Option<(u16, u16)> {
-        let diff = self.0 - Local::now();
-        let days = diff.num_days();
-        let hours = diff.num_hours() - (24 * diff.num_days());
+    /// Convert a date and a time in strftime-like formats to a DateTime,
+    /// like `DateTime::parse("26/05/2025", "8:30 PM", "%d/%m/%Y", "%I:%M %p")`.
+    pub fn parse(
+        date: &str,
+        time: &str,
+        date_format: &str,
+        time_format: &str,
+    ) -> Result<Self, DateError> {
+        let date = NaiveDate::parse_from_str(date.trim(), date_format).map_err(|error| {
+            DateError::from_chrono(error, date, date_format, DateError::NoSuchDate)
+        })?;
+        let time = NaiveTime::parse_from_str(time.trim(), time_format).map_err(|error| {
+            DateError::from_chrono(error, time, time_format, DateError::NoSuchTime)
+        })?;
+        DateTime::new(
+            date.year() as u16,
+            date.month() as u8,
+            date.day() as u8,
+            time.hour() as u8,
+            time.minute() as u8,
+        )
+    }
 
-        if hours >= 0 {
-            Some((days as u16, hours as u16))
-        } else {
-            None
+    /// The date and time in RFC 3339 format, including the timezone offset.
+    pub fn to_rfc3339(&self) -> String {
+        self.0.to_rfc3339()
+    }
+
+    /// Convert a RFC 3339 string, like the ones from `to_rfc3339`, back to a
+    /// DateTime.
+    pub fn from_rfc3339(value: &str) -> Result<Self, DateError> {
+        let datetime = chrono::DateTime::parse_from_rfc3339(value)
+            .map_err(|_| DateError::malformed(value, "RFC 3339"))?;
+        Ok(DateTime(datetime.with_timezone(&Local)))
+    }
+
+    /// Days and hours till the date; None if the date is in the past.
+    pub fn eta(&self, clock: &dyn Clock) -> Option<(u16, u16)> {
+        let now = clock.now();
+        if self.0 <= now {
+            return None;
         }
+        let diff = self.0 - now;
+        let days = diff.num_days();
+        let hours = diff.num_hours() - (24 * days);
+        // Too far away to count is as far as it can be counted.
+        let days = u16::try_from(days).unwrap_or(u16::MAX);
+        Some((days, u16::try_from(hours).unwrap_or_default()))
     }
 
+    /// Unix timestamp of the date and time.
     pub fn timestamp(&self) -> i64 {
         self.0.timestamp()
     }
@@ -91,8 +161,8 @@ impl DateTime {
 
 #[cfg(test)]
 mod datetime_test {
-    use chrono::prelude::*;
-    use chrono::Duration;
+    use crate::clock::FixedClock;
+    use crate::date_errors::DateError;
 
     #[test]
     pub fn invalid_date_time() {
@@ -135,17 +205,78 @@ mod datetime_test {
     }
 
     #[test]
-    pub fn eta_two_hours() {
-        let future = Local::now() + Duration::hours(2);
-        let datetime = super::DateTime::new(
-            future.year() as u16,
-            future.month() as u8,
-            future.day() as u8,
-            future.hour() as u8,
-            future.minute() as u8,
+    pub fn failed_from_string() {
+        assert_eq!(
+            super::DateTime::try_from("2020-06-02", "8h30").err(),
+            Some(DateError::Malformed {
+                value: "8h30".into(),
+                expected: "HH:MM".into()
+            })
+        );
+        assert_eq!(
+            super::DateTime::try_from("2020-06-02", "25:00").err(),
+            Some(DateError::NoSuchTime("25:00 on 2020-06-02".into()))
+        );
+    }
+
+    #[test]
+    pub fn parse_with_format() {
+        let x = super::DateTime::parse("26/05/2025", "8:30 PM", "%d/%m/%Y", "%I:%M %p").unwrap();
+        assert_eq!((x.day(), x.month(), x.hour(), x.minute()), (26, 5, 20, 30));
+        assert!(super::DateTime::parse("26/05/2025", "20:30", "%d/%m/%Y", "%I:%M %p").is_err());
+        assert_eq!(
+            super::DateTime::parse("26/05/2025", "13:30 PM", "%d/%m/%Y", "%I:%M %p").err(),
+            Some(DateError::NoSuchTime("13:30 PM".into()))
         );
+    }
+
+    #[test]
+    pub fn start_of_day() {
+        let date = crate::date::Date::new(2030, 1, 1).unwrap();
+        let start = super::DateTime::start_of_day(&date).unwrap();
+        assert_eq!(start.to_rfc3339()[..19], *"2030-01-01T00:00:00");
+    }
+
+    #[test]
+    pub fn rfc3339_round_trip() {
+        let datetime = super::DateTime::new(2020, 6, 2, 20, 17).unwrap();
+        let back = super::DateTime::from_rfc3339(&datetime.to_rfc3339()).unwrap();
+        assert_eq!(back.timestamp(), datetime.timestamp());
+        assert!(super::DateTime::from_rfc3339("2020-06-02").is_err());
+    }
+
+    #[test]
+    pub fn eta_two_hours() {
+        let clock = FixedClock::at(2020, 6, 2, 20, 17);
+        let datetime = super::DateTime::new(2020, 6, 2, 22, 17).unwrap();
+        assert_eq!(datetime.eta(&clock), Some((0, 2)));
+    }
 
-        assert!(datetime.is_ok());
-        assert_eq!(datetime.unwrap().eta(), Some((0, 2)));
+    #[test]
+    pub fn eta_over_midnight() {
+        let clock = FixedClock::at(2020, 6, 2, 23, 30);
+        let datetime = super::DateTime::new(2020, 6, 4, 1, 30).unwrap();
+        assert_eq!(datetime.eta(&clock), Some((1, 2)));
+    }
+
+    #[test]
+    pub fn eta_past() {
+        let clock = FixedClock::at(2020, 6, 2, 20, 17);
+        let datetime = super::DateTime::new(2020, 6, 2, 18, 17).unwrap();
+        assert_eq!(datetime.eta(&clock), None);
+    }
+
+    #[test]
+    pub fn eta_less_than_an_hour_ago() {
+        let clock = FixedClock::at(2020, 6, 2, 20, 17);
+        let datetime = super::DateTime::new(2020, 6, 2, 19, 47).unwrap();
+        assert_eq!(datetime.eta(&clock), None);
+    }
+
+    #[test]
+    pub fn eta_more_than_a_day_ago() {
+        let clock = FixedClock::at(2020, 6, 2, 20, 17);
+        let datetime = super::DateTime::new(2020, 6, 1, 19, 47).unwrap();
+        assert_eq!(datetime.eta(&clock), None);
     }
 }
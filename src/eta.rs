@@ -0,0 +1,127 @@
+/*
+   TU - Time's Up!
+   Copyright (C) 2020  Julio Biason
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! How long till an event is up.
+
+use std::fmt;
+
+/// Time left till an event.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Eta {
+    /// The event is already in the past.
+    Over,
+    /// An all day event, this many days ahead (0 is today).
+    Days(u16),
+    /// An event at a specific time, this many days and hours ahead.
+    DaysAndHours(u16, u16),
+}
+
+/// How close an event is, for highlighting.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Urgency {
+    /// The event is already over.
+    Overdue,
+    /// The event is due today, or in less than a day.
+    Today,
+    /// The event is due in the next few days.
+    Soon,
+    /// The event is further away.
+    Later,
+}
+
+/// Events due in up to this many days are [`Urgency::Soon`].
+const SOON_DAYS: u16 = 3;
+
+impl Eta {
+    /// True if the event is already in the past.
+    pub fn is_over(&self) -> bool {
+        *self == Eta::Over
+    }
+
+    /// How close the event is.
+    pub fn urgency(&self) -> Urgency {
+        match self {
+            Eta::Over => Urgency::Overdue,
+            Eta::Days(0) | Eta::DaysAndHours(0, _) => Urgency::Today,
+            Eta::Days(days) | Eta::DaysAndHours(days, _) if *days <= SOON_DAYS => Urgency::Soon,
+            _ => Urgency::Later,
+        }
+    }
+}
+
+impl fmt::Display for Eta {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Eta::Over => f.pad("Over"),
+            Eta::Days(0) => f.pad("Today"),
+            Eta::Days(1) => f.pad("Tomorrow"),
+            Eta::Days(days) => f.pad(&format!("{}d", days)),
+            Eta::DaysAndHours(0, hours) => f.pad(&format!("{}h", hours)),
+            Eta::DaysAndHours(days, hours) => f.pad(&format!("{}d {}h", days, hours)),
+        }
+    }
+}
+
+/// Lowercase names, for machine readable output.
+impl fmt::Display for Urgency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Urgency::Overdue => f.pad("overdue"),
+            Urgency::Today => f.pad("today"),
+            Urgency::Soon => f.pad("soon"),
+            Urgency::Later => f.pad("later"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod eta_test {
+    use super::Eta;
+    use super::Urgency;
+
+    #[test]
+    pub fn display() {
+        assert_eq!(Eta::Over.to_string(), "Over");
+        assert_eq!(Eta::Days(0).to_string(), "Today");
+        assert_eq!(Eta::Days(1).to_string(), "Tomorrow");
+        assert_eq!(Eta::Days(15).to_string(), "15d");
+        assert_eq!(Eta::DaysAndHours(0, 2).to_string(), "2h");
+        assert_eq!(Eta::DaysAndHours(3, 2).to_string(), "3d 2h");
+    }
+
+    #[test]
+    pub fn urgency() {
+        assert_eq!(Eta::Over.urgency(), Urgency::Overdue);
+        assert_eq!(Eta::Days(0).urgency(), Urgency::Today);
+        assert_eq!(Eta::DaysAndHours(0, 20).urgency(), Urgency::Today);
+        assert_eq!(Eta::Days(3).urgency(), Urgency::Soon);
+        assert_eq!(Eta::DaysAndHours(1, 2).urgency(), Urgency::Soon);
+        assert_eq!(Eta::Days(4).urgency(), Urgency::Later);
+    }
+
+    #[test]
+    pub fn urgency_names() {
+        assert_eq!(Urgency::Overdue.to_string(), "overdue");
+        assert_eq!(Urgency::Later.to_string(), "later");
+    }
+
+    #[test]
+    pub fn padding() {
+        assert_eq!(format!("{:>7}", Eta::Days(15)), "    15d");
+    }
+}
@@ -16,51 +16,224 @@
    along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+//! A single event.
+
 use std::cmp::Ordering;
+use std::env;
+use std::fmt;
 
+use chrono::Duration;
 use serde_derive::Deserialize;
 use serde_derive::Serialize;
-use uuid::Uuid;
 
+use crate::clock::Clock;
 use crate::date::Date;
 use crate::datetime::DateTime;
+use crate::eta::Eta;
 use crate::eventtype::EventType;
+use crate::priority::Priority;
 
-#[derive(Serialize, Deserialize, Debug)]
+/// An event, something that will be up at some point.
+// Keep `due` as the last field: TOML needs plain values before tables.
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Event {
+    /// Short identifier of the event.
     pub id: String,
+    /// What the event is about.
     pub description: String,
+    /// Free form labels for the event.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// How important the event is.
+    #[serde(default)]
+    pub priority: Priority,
+    /// When the event was created; unknown for events created before tu
+    /// kept track of it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime>,
+    /// When the event last changed; unknown for events not changed since tu
+    /// kept track of it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTime>,
+    /// Who created the event, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<String>,
+    /// When the event was done; None while it is not.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<DateTime>,
+    /// When the event is up.
     pub due: EventType,
 }
 
-/// TODO inject this
-fn uuid() -> String {
-    let (id, _, _, _) = Uuid::new_v4().as_fields();
-    format!("{:x}", id)
+/// The parts of an event chosen by the user, before it gets an ID.
+#[derive(Debug, Clone)]
+pub struct NewEvent {
+    /// What the event is about.
+    pub description: String,
+    /// When the event is up.
+    pub due: EventType,
+    /// Free form labels for the event.
+    pub tags: Vec<String>,
+    /// How important the event is.
+    pub priority: Priority,
 }
 
+impl NewEvent {
+    /// A new all day event.
+    pub fn on_date(description: &str, date: &Date) -> Self {
+        Self {
+            description: description.into(),
+            due: EventType::AllDay(*date),
+            tags: Vec::new(),
+            priority: Priority::Normal,
+        }
+    }
+
+    /// A new event at a specific time.
+    pub fn at_time(description: &str, datetime: &DateTime) -> Self {
+        Self {
+            description: description.into(),
+            due: EventType::AtTime(*datetime),
+            tags: Vec::new(),
+            priority: Priority::Normal,
+        }
+    }
+
+    /// The same event, with tags.
+    pub fn with_tags(self, tags: Vec<String>) -> Self {
+        Self { tags, ..self }
+    }
+
+    /// The same event, with a priority.
+    pub fn with_priority(self, priority: Priority) -> Self {
+        Self { priority, ..self }
+    }
+}
+
+/// Errors when creating events.
 #[derive(Debug)]
 pub enum EventError {
+    /// The date for the event is not valid.
     InvalidDate(String),
+    /// The event is already in the past.
     TooOld,
 }
 
+impl fmt::Display for EventError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EventError::InvalidDate(date) => write!(f, "invalid date: {}", date),
+            EventError::TooOld => write!(f, "event is already in the past"),
+        }
+    }
+}
+
+impl std::error::Error for EventError {}
+
+/// Name of the user running tu, from the environment.
+fn current_user() -> Option<String> {
+    env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .ok()
+        .filter(|user| !user.is_empty())
+}
+
 impl Event {
-    pub fn new_on_date(description: &str, date: &Date) -> Result<Self, EventError> {
+    /// Create a new all day event.
+    pub fn new_on_date(id: &str, description: &str, date: &Date) -> Result<Self, EventError> {
         Ok(Self {
-            id: uuid(),
+            id: id.into(),
             description: description.into(),
-            due: EventType::AllDay(date.clone()),
+            tags: Vec::new(),
+            priority: Priority::Normal,
+            created_at: None,
+            updated_at: None,
+            created_by: None,
+            completed_at: None,
+            due: EventType::AllDay(*date),
         })
     }
 
-    pub fn new_on_date_time(description: &str, datetime: &DateTime) -> Result<Self, EventError> {
+    /// Create a new event at a specific time.
+    pub fn new_on_date_time(
+        id: &str,
+        description: &str,
+        datetime: &DateTime,
+    ) -> Result<Self, EventError> {
         Ok(Self {
-            id: uuid(),
+            id: id.into(),
             description: description.into(),
-            due: EventType::AtTime(datetime.clone()),
+            tags: Vec::new(),
+            priority: Priority::Normal,
+            created_at: None,
+            updated_at: None,
+            created_by: None,
+            completed_at: None,
+            due: EventType::AtTime(*datetime),
+        })
+    }
+
+    /// Create an event from the user choices, created now by the current
+    /// user.
+    pub fn from_new(id: &str, new: NewEvent, clock: &dyn Clock) -> Self {
+        let now = DateTime(clock.now());
+        Self {
+            id: id.into(),
+            description: new.description,
+            tags: new.tags,
+            priority: new.priority,
+            created_at: Some(now),
+            updated_at: Some(now),
+            created_by: current_user(),
+            completed_at: None,
+            due: new.due,
+        }
+    }
+
+    /// Record that the event changed now.
+    pub fn touch(&mut self, clock: &dyn Clock) {
+        self.updated_at = Some(DateTime(clock.now()));
+    }
+
+    /// True if the event was done.
+    pub fn is_done(&self) -> bool {
+        self.completed_at.is_some()
+    }
+
+    /// How long before the event was due it was done, negative if it was
+    /// done late; None if it is not done. All day events count whole days,
+    /// so events done on the day they were due were done on time.
+    pub fn ahead_of_due(&self) -> Option<Duration> {
+        let completed = self.completed_at?;
+        Some(match &self.due {
+            EventType::AllDay(date) => date.0 - completed.0.date_naive(),
+            EventType::AtTime(datetime) => datetime.0 - completed.0,
         })
     }
+
+    /// Time left till the event.
+    pub fn eta(&self, clock: &dyn Clock) -> Eta {
+        self.due.eta(clock)
+    }
+
+    /// Whether both events say the same, regardless of their IDs and when
+    /// they were created; comparing events only compares when they are due.
+    pub fn same_as(&self, other: &Event) -> bool {
+        let completed = |event: &Event| event.completed_at.map(|completed| completed.timestamp());
+        self.description == other.description
+            && self.tags == other.tags
+            && self.priority == other.priority
+            && completed(self) == completed(other)
+            && self.due == other.due
+    }
+}
+
+#[cfg(test)]
+impl Event {
+    /// An all day event on the day, for tests.
+    pub(crate) fn on_day(id: &str, description: &str, year: u16, month: u8, day: u8) -> Event {
+        Event::new_on_date(id, description, &Date::new(year, month, day).unwrap()).unwrap()
+    }
 }
 
 impl Eq for Event {}
@@ -79,6 +252,6 @@ impl Ord for Event {
 
 impl PartialOrd for Event {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.due.timestamp().cmp(&other.due.timestamp()))
+        Some(self.cmp(other))
     }
 }
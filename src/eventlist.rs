@@ -16,38 +16,81 @@
    along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
-use std::ffi::OsString;
-use std::fs::File;
-use std::io::{Read, Write};
-use std::path::PathBuf;
+//! The list of events.
+
+use std::fmt;
+use std::iter::FromIterator;
 
-use dirs::config_dir;
 use serde_derive::Deserialize;
 use serde_derive::Serialize;
-use toml;
 
-use crate::date::Date;
+use crate::clock::Clock;
 use crate::datetime::DateTime;
 use crate::event::Event;
 use crate::event::EventError;
-use crate::eventtype::EventType;
-
-static FILENAME: &str = "events.toml";
+use crate::event::NewEvent;
+use crate::idgen::new_id;
+use crate::idgen::IdGenerator;
+use crate::priority::Priority;
+use crate::storage::Storage;
 
-#[derive(Serialize, Deserialize, Debug)]
+/// The list of events, sorted by their due date.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct EventList {
+    #[serde(default)]
     events: Vec<Event>,
 }
 
+/// Errors when dealing with the event list.
 #[derive(Debug)]
 pub enum EventListError {
+    /// The date for the event is not valid.
     InvalidDate,
+    /// The event is already in the past.
     TooOld,
+    /// There is no place to store the events.
     NoStorage,
+    /// The stored events can't be read or written.
     BrokenFormat,
+    /// There is no event with the requested ID.
     NoSuchEvent,
+    /// The storage backend failed.
+    StorageFailure(String),
+    /// The ID generator couldn't come up with an unused ID.
+    NoFreeId,
+    /// The stored events were written by a newer version of tu, using a
+    /// format version this one doesn't know about.
+    NewerFormat(u32),
+    /// The event is already done.
+    AlreadyDone,
+    /// The event is not done.
+    NotDone,
+}
+
+impl fmt::Display for EventListError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EventListError::InvalidDate => write!(f, "invalid date"),
+            EventListError::TooOld => write!(f, "event is already in the past"),
+            EventListError::NoStorage => write!(f, "can't find a place to store events"),
+            EventListError::BrokenFormat => write!(f, "the event file is broken"),
+            EventListError::NoSuchEvent => write!(f, "no such event"),
+            EventListError::StorageFailure(reason) => write!(f, "storage failure: {}", reason),
+            EventListError::NoFreeId => write!(f, "can't find an unused event ID"),
+            EventListError::NewerFormat(version) => write!(
+                f,
+                "the events were written by a newer version of tu (format version {}); \
+                 please upgrade tu",
+                version
+            ),
+            EventListError::AlreadyDone => write!(f, "the event is already done"),
+            EventListError::NotDone => write!(f, "the event is not done"),
+        }
+    }
 }
 
+impl std::error::Error for EventListError {}
+
 impl From<EventError> for EventListError {
     fn from(error: EventError) -> EventListError {
         match error {
@@ -69,112 +112,175 @@ impl From<toml::de::Error> for EventListError {
     }
 }
 
+impl From<toml::ser::Error> for EventListError {
+    fn from(_: toml::ser::Error) -> EventListError {
+        EventListError::BrokenFormat
+    }
+}
+
 impl EventList {
-    pub fn load() -> Result<Self, EventListError> {
-        if let Ok(mut fp) = File::open(EventList::event_file()?) {
-            let mut content = String::new();
-            fp.read_to_string(&mut content)?;
-            let data = toml::from_str(&content)?;
-            Ok(data)
-        } else {
-            Ok(EventList::empty())
-        }
+    /// Create an empty event list.
+    pub fn empty() -> Self {
+        Self { events: Vec::new() }
     }
 
+    /// Add an event to the list, keeping it sorted.
     pub fn push(&mut self, event: Event) {
         self.events.push(event);
         self.events.sort();
     }
 
-    // TODO turn this into the destructor
-    // TODO if so, track changes
-    pub fn save(&self) -> Result<(), EventListError> {
-        let content = toml::to_string(&self).unwrap();
-        if let Ok(mut fp) = File::create(EventList::event_file()?) {
-            fp.write_all(content.as_bytes()).unwrap();
-        }
-        Ok(())
+    /// Take an event out of the list by its ID.
+    pub fn remove(&mut self, id: &str) -> Option<Event> {
+        let position = self.events.iter().position(|event| event.id == id)?;
+        Some(self.events.remove(position))
     }
 
-    /// Load the event list, add an all day event, and save it back.
-    /// Returns the ID of the new event.
-    pub fn add_event_with_date(description: &str, date: &Date) -> Result<String, EventListError> {
-        let mut list = EventList::load()?;
-        let event = Event::new_on_date(description, date)?;
-        let id = String::from(&event.id);
-        list.push(event);
-        list.save()?;
-        Ok(id)
+    /// Find an event by its ID.
+    pub fn get(&self, id: &str) -> Option<&Event> {
+        self.events.iter().find(|event| event.id == id)
+    }
+
+    /// Number of events in the list.
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// True if there are no events in the list.
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// The events that are not done, in due order.
+    pub fn pending(&self) -> EventList {
+        self.iter()
+            .filter(|event| !event.is_done())
+            .cloned()
+            .collect()
+    }
+
+    /// Iterate over the events, in due order.
+    pub fn iter(&self) -> std::slice::Iter<'_, Event> {
+        self.events.iter()
     }
 
-    /// Load the event list, add an event with date and time, and save it back.
+    /// Add a new event to the storage.
     /// Returns the ID of the new event.
-    pub fn add_event_with_date_and_time(
-        description: &str,
-        datetime: &DateTime,
+    pub fn add(
+        storage: &mut dyn Storage,
+        ids: &mut dyn IdGenerator,
+        clock: &dyn Clock,
+        new: NewEvent,
     ) -> Result<String, EventListError> {
-        let mut list = EventList::load()?;
-        let event = Event::new_on_date_time(description, datetime)?;
-        let id = String::from(&event.id);
-        list.push(event);
-        list.save()?;
+        let id = new_id(ids, &storage.load()?)?;
+        storage.insert(Event::from_new(&id, new, clock))?;
         Ok(id)
     }
 
-    /// Remove an event by its ID
-    pub fn remove_by_id(id: &str) -> Result<String, EventListError> {
-        let mut list = EventList::load()?;
-        let item: Vec<&Event> = list
-            .events
-            .iter()
-            .filter(|event| event.id == id)
-            .take(1)
-            .collect();
+    /// Add several new events to the storage at once; either all of them
+    /// are stored or none is.
+    /// Returns the IDs of the new events, in the same order.
+    pub fn add_all(
+        storage: &mut dyn Storage,
+        ids: &mut dyn IdGenerator,
+        clock: &dyn Clock,
+        new: Vec<NewEvent>,
+    ) -> Result<Vec<String>, EventListError> {
+        let mut list = storage.load()?;
+        let mut added = Vec::with_capacity(new.len());
+        for new in new {
+            let id = new_id(ids, &list)?;
+            list.push(Event::from_new(&id, new, clock));
+            added.push(id);
+        }
+        storage.save(&list)?;
+        Ok(added)
+    }
 
-        if item.len() == 0 {
-            Err(EventListError::NoSuchEvent)
-        } else {
-            let description = item.first().unwrap().description.to_string();
-            list.events = list
-                .events
-                .into_iter()
-                .filter(|event| event.id != id)
-                .collect();
-            list.save()?;
-            Ok(description)
+    /// Remove an event from the storage by its ID.
+    /// Returns the description of the removed event.
+    pub fn remove_by_id(storage: &mut dyn Storage, id: &str) -> Result<String, EventListError> {
+        let event = storage.remove(id)?;
+        Ok(event.description)
+    }
+
+    /// Replace an event in the storage with an edited version of it, changed
+    /// now; the event is found by its ID.
+    pub fn edit(
+        storage: &mut dyn Storage,
+        clock: &dyn Clock,
+        mut event: Event,
+    ) -> Result<(), EventListError> {
+        event.touch(clock);
+        storage.update(event)
+    }
+
+    /// Mark an event as done now.
+    /// Returns the event, as done.
+    pub fn complete(
+        storage: &mut dyn Storage,
+        clock: &dyn Clock,
+        id: &str,
+    ) -> Result<Event, EventListError> {
+        let mut event = storage
+            .load()?
+            .get(id)
+            .cloned()
+            .ok_or(EventListError::NoSuchEvent)?;
+        if event.is_done() {
+            return Err(EventListError::AlreadyDone);
+        }
+        event.completed_at = Some(DateTime(clock.now()));
+        event.touch(clock);
+        storage.update(event.clone())?;
+        Ok(event)
+    }
+
+    /// Mark a done event as not done.
+    /// Returns the event, as not done.
+    pub fn reopen(
+        storage: &mut dyn Storage,
+        clock: &dyn Clock,
+        id: &str,
+    ) -> Result<Event, EventListError> {
+        let mut event = storage
+            .load()?
+            .get(id)
+            .cloned()
+            .ok_or(EventListError::NoSuchEvent)?;
+        if !event.is_done() {
+            return Err(EventListError::NotDone);
         }
+        event.completed_at = None;
+        event.touch(clock);
+        storage.update(event.clone())?;
+        Ok(event)
     }
 
-    /// Remove all outdated events
-    pub fn remove_outdated() -> Result<usize, EventListError> {
-        let mut list = EventList::load()?;
+    /// Remove the outdated events from the storage; if a priority is given,
+    /// only the outdated events less important than it are removed.
+    /// Returns the number of removed events.
+    pub fn remove_outdated(
+        storage: &mut dyn Storage,
+        clock: &dyn Clock,
+        below: Option<Priority>,
+    ) -> Result<usize, EventListError> {
+        let mut list = storage.load()?;
         let initial_elements = list.events.len();
-        list.events = list
-            .events
-            .into_iter()
-            .filter(|event| match event.due {
-                EventType::AllDay(date) => date.eta().is_some(),
-                EventType::AtTime(datetime) => datetime.eta().is_some(),
-            })
-            .collect();
+        list.events.retain(|event| {
+            !event.eta(clock).is_over() || below.is_some_and(|below| event.priority >= below)
+        });
         let final_elements = list.events.len();
-        list.save()?;
+        storage.save(&list)?;
         Ok(initial_elements - final_elements)
     }
+}
 
-    /// Full path for the event file.
-    fn event_file() -> Result<OsString, EventListError> {
-        let base = config_dir().ok_or(EventListError::NoStorage)?;
-        let mut path = PathBuf::new();
-        path.push(base);
-        path.push(FILENAME);
-
-        Ok(path.into_os_string())
-    }
-
-    /// Create an empty event list.
-    fn empty() -> Self {
-        Self { events: Vec::new() }
+impl FromIterator<Event> for EventList {
+    fn from_iter<I: IntoIterator<Item = Event>>(iter: I) -> Self {
+        let mut events: Vec<Event> = iter.into_iter().collect();
+        events.sort();
+        Self { events }
     }
 }
 
@@ -183,6 +289,275 @@ impl<'a> IntoIterator for &'a EventList {
     type IntoIter = std::slice::Iter<'a, Event>;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.events.iter()
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod eventlist_test {
+    use chrono::Duration;
+
+    use super::EventList;
+    use super::EventListError;
+    use crate::clock::Clock;
+    use crate::clock::FixedClock;
+    use crate::date::Date;
+    use crate::datetime::DateTime;
+    use crate::event::NewEvent;
+    use crate::idgen::Sequential;
+    use crate::priority::Priority;
+    use crate::storage::Memory;
+    use crate::storage::Storage;
+
+    fn date_from_today(days: u8) -> Date {
+        Date::new(2020, 6, 10 + days).unwrap()
+    }
+
+    fn date_before_today(days: u8) -> Date {
+        Date::new(2020, 6, 10 - days).unwrap()
+    }
+
+    #[test]
+    pub fn add_and_remove() {
+        let mut storage = Memory::new();
+        let id = EventList::add(
+            &mut storage,
+            &mut Sequential,
+            &FixedClock::at(2020, 6, 10, 12, 0),
+            NewEvent::on_date("event", &date_from_today(2)),
+        )
+        .unwrap();
+        assert_eq!(storage.load().unwrap().len(), 1);
+
+        let description = EventList::remove_by_id(&mut storage, &id).unwrap();
+        assert_eq!(description, "event");
+        assert!(storage.load().unwrap().is_empty());
+    }
+
+    #[test]
+    pub fn unique_ids() {
+        let mut storage = Memory::new();
+        let first = EventList::add(
+            &mut storage,
+            &mut Sequential,
+            &FixedClock::at(2020, 6, 10, 12, 0),
+            NewEvent::on_date("1", &date_from_today(1)),
+        )
+        .unwrap();
+        let second = EventList::add(
+            &mut storage,
+            &mut Sequential,
+            &FixedClock::at(2020, 6, 10, 12, 0),
+            NewEvent::on_date("2", &date_from_today(1)),
+        )
+        .unwrap();
+        assert_eq!(first, "1");
+        assert_eq!(second, "2");
+    }
+
+    #[test]
+    pub fn add_all() {
+        let mut storage = Memory::new();
+        EventList::add(
+            &mut storage,
+            &mut Sequential,
+            &FixedClock::at(2020, 6, 10, 12, 0),
+            NewEvent::on_date("existing", &date_from_today(1)),
+        )
+        .unwrap();
+
+        let ids = EventList::add_all(
+            &mut storage,
+            &mut Sequential,
+            &FixedClock::at(2020, 6, 10, 12, 0),
+            vec![
+                NewEvent::on_date("a", &date_from_today(2)),
+                NewEvent::on_date("b", &date_from_today(3)),
+            ],
+        )
+        .unwrap();
+        assert_eq!(ids, vec!["2", "3"]);
+        assert_eq!(storage.load().unwrap().get("3").unwrap().description, "b");
+    }
+
+    #[test]
+    pub fn remove_missing() {
+        let mut storage = Memory::new();
+        assert!(EventList::remove_by_id(&mut storage, "nope").is_err());
+    }
+
+    #[test]
+    pub fn edit() {
+        let mut storage = Memory::new();
+        let id = EventList::add(
+            &mut storage,
+            &mut Sequential,
+            &FixedClock::at(2020, 6, 10, 12, 0),
+            NewEvent::on_date("event", &date_from_today(2)),
+        )
+        .unwrap();
+
+        let mut event = storage.load().unwrap().get(&id).unwrap().clone();
+        assert_eq!(event.updated_at, event.created_at);
+        event.description = "edited".into();
+        let later = DateTime::new(2020, 6, 11, 8, 0).unwrap();
+        EventList::edit(&mut storage, &FixedClock::new(&later), event).unwrap();
+
+        let event = storage.load().unwrap().get(&id).unwrap().clone();
+        assert_eq!(event.description, "edited");
+        assert_eq!(event.updated_at, Some(later));
+        assert_eq!(
+            event.created_at,
+            Some(DateTime(FixedClock::at(2020, 6, 10, 12, 0).now()))
+        );
+    }
+
+    #[test]
+    pub fn edit_missing() {
+        let mut storage = Memory::new();
+        let event = crate::event::Event::new_on_date("nope", "event", &date_from_today(1)).unwrap();
+        assert!(EventList::edit(&mut storage, &FixedClock::at(2020, 6, 10, 12, 0), event).is_err());
+    }
+
+    #[test]
+    pub fn sorted_by_due() {
+        let mut storage = Memory::new();
+        EventList::add(
+            &mut storage,
+            &mut Sequential,
+            &FixedClock::at(2020, 6, 10, 12, 0),
+            NewEvent::on_date("later", &date_from_today(5)),
+        )
+        .unwrap();
+        EventList::add(
+            &mut storage,
+            &mut Sequential,
+            &FixedClock::at(2020, 6, 10, 12, 0),
+            NewEvent::on_date("sooner", &date_from_today(1)),
+        )
+        .unwrap();
+
+        let list = storage.load().unwrap();
+        let descriptions: Vec<&str> = list.iter().map(|e| e.description.as_str()).collect();
+        assert_eq!(descriptions, vec!["sooner", "later"]);
+    }
+
+    #[test]
+    pub fn remove_outdated() {
+        let mut storage = Memory::new();
+        EventList::add(
+            &mut storage,
+            &mut Sequential,
+            &FixedClock::at(2020, 6, 10, 12, 0),
+            NewEvent::on_date("past", &date_before_today(2)),
+        )
+        .unwrap();
+        EventList::add(
+            &mut storage,
+            &mut Sequential,
+            &FixedClock::at(2020, 6, 10, 12, 0),
+            NewEvent::on_date("future", &date_from_today(2)),
+        )
+        .unwrap();
+
+        assert_eq!(
+            EventList::remove_outdated(&mut storage, &FixedClock::at(2020, 6, 10, 12, 0), None)
+                .unwrap(),
+            1
+        );
+        let list = storage.load().unwrap();
+        assert_eq!(list.iter().next().unwrap().description, "future");
+    }
+
+    #[test]
+    pub fn complete_and_reopen() {
+        let mut storage = Memory::new();
+        let id = EventList::add(
+            &mut storage,
+            &mut Sequential,
+            &FixedClock::at(2020, 6, 10, 12, 0),
+            NewEvent::on_date("taxes", &date_from_today(3)),
+        )
+        .unwrap();
+
+        let done =
+            EventList::complete(&mut storage, &FixedClock::at(2020, 6, 10, 12, 0), &id).unwrap();
+        assert_eq!(
+            done.completed_at,
+            Some(DateTime(FixedClock::at(2020, 6, 10, 12, 0).now()))
+        );
+        assert_eq!(done.ahead_of_due(), Some(Duration::days(3)));
+        let list = storage.load().unwrap();
+        assert!(list.get(&id).unwrap().is_done());
+        assert!(list.pending().is_empty());
+        assert!(matches!(
+            EventList::complete(&mut storage, &FixedClock::at(2020, 6, 10, 12, 0), &id),
+            Err(EventListError::AlreadyDone)
+        ));
+
+        let reopened =
+            EventList::reopen(&mut storage, &FixedClock::at(2020, 6, 10, 12, 0), &id).unwrap();
+        assert!(reopened.ahead_of_due().is_none());
+        assert_eq!(storage.load().unwrap().pending().len(), 1);
+        assert!(matches!(
+            EventList::reopen(&mut storage, &FixedClock::at(2020, 6, 10, 12, 0), &id),
+            Err(EventListError::NotDone)
+        ));
+        assert!(matches!(
+            EventList::complete(&mut storage, &FixedClock::at(2020, 6, 10, 12, 0), "nope"),
+            Err(EventListError::NoSuchEvent)
+        ));
+    }
+
+    #[test]
+    pub fn done_late() {
+        let mut storage = Memory::new();
+        let due = DateTime::new(2020, 6, 9, 18, 0).unwrap();
+        let id = EventList::add(
+            &mut storage,
+            &mut Sequential,
+            &FixedClock::at(2020, 6, 10, 12, 0),
+            NewEvent::at_time("call", &due),
+        )
+        .unwrap();
+        let done =
+            EventList::complete(&mut storage, &FixedClock::at(2020, 6, 10, 12, 0), &id).unwrap();
+        assert_eq!(done.ahead_of_due(), Some(Duration::hours(-18)));
+    }
+
+    #[test]
+    pub fn remove_outdated_below_priority() {
+        let mut storage = Memory::new();
+        for (description, priority) in [
+            ("low", Priority::Low),
+            ("high", Priority::High),
+            ("critical", Priority::Critical),
+        ] {
+            let new = NewEvent::on_date(description, &date_before_today(2)).with_priority(priority);
+            EventList::add(
+                &mut storage,
+                &mut Sequential,
+                &FixedClock::at(2020, 6, 10, 12, 0),
+                new,
+            )
+            .unwrap();
+        }
+
+        assert_eq!(
+            EventList::remove_outdated(
+                &mut storage,
+                &FixedClock::at(2020, 6, 10, 12, 0),
+                Some(Priority::High)
+            )
+            .unwrap(),
+            1
+        );
+        let list = storage.load().unwrap();
+        let mut left: Vec<&str> = list
+            .iter()
+            .map(|event| event.description.as_str())
+            .collect();
+        left.sort();
+        assert_eq!(left, vec!["critical", "high"]);
     }
 }
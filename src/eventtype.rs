@@ -16,20 +16,50 @@
    along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+//! When an event happens.
+
 use serde_derive::Deserialize;
 use serde_derive::Serialize;
 
+use crate::clock::Clock;
 use crate::date::Date;
 use crate::datetime::DateTime;
+use crate::eta::Eta;
 
-#[derive(Serialize, Deserialize, Debug)]
+/// The due date of an event.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(tag = "due", content = "datetime")]
 pub enum EventType {
+    /// The event takes the whole day.
     AllDay(Date),
+    /// The event happens at a specific time.
     AtTime(DateTime),
 }
 
 impl EventType {
+    /// Time left till the event.
+    pub fn eta(&self, clock: &dyn Clock) -> Eta {
+        match self {
+            EventType::AllDay(date) => match date.eta(clock) {
+                None => Eta::Over,
+                Some(days) => Eta::Days(days),
+            },
+            EventType::AtTime(datetime) => match datetime.eta(clock) {
+                None => Eta::Over,
+                Some((days, hours)) => Eta::DaysAndHours(days, hours),
+            },
+        }
+    }
+
+    /// The day the event is due.
+    pub fn date(&self) -> Date {
+        match self {
+            EventType::AllDay(date) => *date,
+            EventType::AtTime(datetime) => Date(datetime.0.naive_local().date()),
+        }
+    }
+
+    /// Unix timestamp of the moment the event is up.
     pub fn timestamp(&self) -> i64 {
         match self {
             EventType::AllDay(date) => date.timestamp(),
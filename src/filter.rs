@@ -0,0 +1,252 @@
+/*
+   TU - Time's Up!
+   Copyright (C) 2020  Julio Biason
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Picking events by when they are due and how important they are.
+
+use std::fmt;
+
+use chrono::prelude::*;
+use chrono::Duration;
+
+use crate::clock::Clock;
+use crate::date::Date;
+use crate::event::Event;
+use crate::eventtype::EventType;
+use crate::priority::Priority;
+
+/// A condition on the due date or the priority of an event.
+#[derive(Debug, Clone)]
+pub enum Filter {
+    /// Not over yet, and due before this much time from now.
+    Within(Duration),
+    /// Due on this day or later.
+    After(Date),
+    /// Due on this day or earlier.
+    Before(Date),
+    /// Already over.
+    Overdue,
+    /// Due today.
+    Today,
+    /// Due in the current week (Monday to Sunday).
+    ThisWeek,
+    /// At least this important.
+    Priority(Priority),
+}
+
+/// Errors when building filters.
+#[derive(Debug, PartialEq, Eq)]
+pub enum FilterError {
+    /// The duration is not a positive number followed by a unit.
+    InvalidDuration(String),
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FilterError::InvalidDuration(value) => write!(
+                f,
+                "invalid duration '{}'; expected a positive number followed by s, m, h, d or w",
+                value
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FilterError {}
+
+impl Filter {
+    /// True if the event passes the filter.
+    pub fn matches(&self, event: &Event, clock: &dyn Clock) -> bool {
+        let day = event.due.date().0;
+        match self {
+            Filter::Within(duration) => {
+                // A limit too far away to be a date is no limit at all.
+                let limit = clock.now().checked_add_signed(*duration);
+                !event.eta(clock).is_over()
+                    && match (&event.due, limit) {
+                        (_, None) => true,
+                        (EventType::AllDay(date), Some(limit)) => {
+                            date.0 <= limit.date().naive_local()
+                        }
+                        (EventType::AtTime(datetime), Some(limit)) => datetime.0 <= limit,
+                    }
+            }
+            Filter::After(date) => day >= date.0,
+            Filter::Before(date) => day <= date.0,
+            Filter::Overdue => event.eta(clock).is_over(),
+            Filter::Today => day == clock.today(),
+            Filter::ThisWeek => day.iso_week() == clock.today().iso_week(),
+            Filter::Priority(priority) => event.priority >= *priority,
+        }
+    }
+}
+
+/// True if the event passes all the filters.
+pub fn matches_all(filters: &[Filter], event: &Event, clock: &dyn Clock) -> bool {
+    filters.iter().all(|filter| filter.matches(event, clock))
+}
+
+/// The longest duration, in seconds, that can be parsed.
+const MAX_SECONDS: i64 = i64::MAX / 1000;
+
+/// Parse a duration like `30s`, `30m`, `12h`, `7d` or `2w`.
+pub fn parse_duration(value: &str) -> Result<Duration, FilterError> {
+    let invalid = || FilterError::InvalidDuration(value.into());
+    let unit = value.chars().last().ok_or_else(invalid)?;
+    let amount: i64 = value[..value.len() - unit.len_utf8()]
+        .parse()
+        .map_err(|_| invalid())?;
+    let seconds_per_unit = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        'w' => 7 * 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    // Durations are kept in milliseconds, so not every i64 of seconds fits.
+    amount
+        .checked_mul(seconds_per_unit)
+        .filter(|seconds| *seconds > 0 && *seconds <= MAX_SECONDS)
+        .map(Duration::seconds)
+        .ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod filter_test {
+    use chrono::Duration;
+
+    use super::matches_all;
+    use super::parse_duration;
+    use super::Filter;
+    use crate::clock::FixedClock;
+    use crate::date::Date;
+    use crate::datetime::DateTime;
+    use crate::event::Event;
+    use crate::priority::Priority;
+
+    fn on(day: u8) -> Event {
+        Event::new_on_date("1", "event", &Date::new(2020, 6, day).unwrap()).unwrap()
+    }
+
+    fn at(day: u8, hour: u8) -> Event {
+        let datetime = DateTime::new(2020, 6, day, hour, 0).unwrap();
+        Event::new_on_date_time("1", "event", &datetime).unwrap()
+    }
+
+    #[test]
+    pub fn durations() {
+        assert_eq!(parse_duration("7d"), Ok(Duration::days(7)));
+        assert_eq!(parse_duration("12h"), Ok(Duration::hours(12)));
+        assert_eq!(parse_duration("2w"), Ok(Duration::weeks(2)));
+        assert_eq!(parse_duration("30s"), Ok(Duration::seconds(30)));
+        assert_eq!(parse_duration("30m"), Ok(Duration::minutes(30)));
+        assert!(parse_duration("7").is_err());
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("7y").is_err());
+        assert!(parse_duration("9999999999999999w").is_err());
+        assert!(parse_duration("99999999999w").is_err());
+        assert!(parse_duration("-7d").is_err());
+        assert!(parse_duration("0h").is_err());
+    }
+
+    #[test]
+    pub fn within() {
+        let filter = Filter::Within(Duration::days(7));
+        assert!(filter.matches(&on(10), &FixedClock::at(2020, 6, 10, 12, 0)));
+        assert!(filter.matches(&on(17), &FixedClock::at(2020, 6, 10, 12, 0)));
+        assert!(!filter.matches(&on(18), &FixedClock::at(2020, 6, 10, 12, 0)));
+        assert!(!filter.matches(&on(9), &FixedClock::at(2020, 6, 10, 12, 0)));
+
+        let filter = Filter::Within(Duration::hours(2));
+        assert!(filter.matches(&at(10, 13), &FixedClock::at(2020, 6, 10, 12, 0)));
+        assert!(!filter.matches(&at(10, 15), &FixedClock::at(2020, 6, 10, 12, 0)));
+        assert!(!filter.matches(&at(10, 11), &FixedClock::at(2020, 6, 10, 12, 0)));
+
+        let filter = Filter::Within(parse_duration("99999999d").unwrap());
+        assert!(filter.matches(&on(18), &FixedClock::at(2020, 6, 10, 12, 0)));
+        assert!(!filter.matches(&on(9), &FixedClock::at(2020, 6, 10, 12, 0)));
+    }
+
+    #[test]
+    pub fn range() {
+        let filters = vec![
+            Filter::After(Date::new(2020, 6, 12).unwrap()),
+            Filter::Before(Date::new(2020, 6, 14).unwrap()),
+        ];
+        assert!(!matches_all(
+            &filters,
+            &on(11),
+            &FixedClock::at(2020, 6, 10, 12, 0)
+        ));
+        assert!(matches_all(
+            &filters,
+            &on(12),
+            &FixedClock::at(2020, 6, 10, 12, 0)
+        ));
+        assert!(matches_all(
+            &filters,
+            &at(14, 23),
+            &FixedClock::at(2020, 6, 10, 12, 0)
+        ));
+        assert!(!matches_all(
+            &filters,
+            &on(15),
+            &FixedClock::at(2020, 6, 10, 12, 0)
+        ));
+    }
+
+    #[test]
+    pub fn overdue() {
+        assert!(Filter::Overdue.matches(&on(9), &FixedClock::at(2020, 6, 10, 12, 0)));
+        assert!(Filter::Overdue.matches(&at(10, 9), &FixedClock::at(2020, 6, 10, 12, 0)));
+        assert!(!Filter::Overdue.matches(&on(10), &FixedClock::at(2020, 6, 10, 12, 0)));
+        // Less than an hour and more than a day ago.
+        let recent = DateTime::new(2020, 6, 10, 11, 30).unwrap();
+        let recent = Event::new_on_date_time("1", "event", &recent).unwrap();
+        assert!(Filter::Overdue.matches(&recent, &FixedClock::at(2020, 6, 10, 12, 0)));
+        assert!(Filter::Overdue.matches(&at(9, 11), &FixedClock::at(2020, 6, 10, 12, 0)));
+    }
+
+    #[test]
+    pub fn today() {
+        assert!(Filter::Today.matches(&on(10), &FixedClock::at(2020, 6, 10, 12, 0)));
+        assert!(Filter::Today.matches(&at(10, 18), &FixedClock::at(2020, 6, 10, 12, 0)));
+        assert!(!Filter::Today.matches(&on(11), &FixedClock::at(2020, 6, 10, 12, 0)));
+    }
+
+    #[test]
+    pub fn this_week() {
+        assert!(!Filter::ThisWeek.matches(&on(7), &FixedClock::at(2020, 6, 10, 12, 0)));
+        assert!(Filter::ThisWeek.matches(&on(8), &FixedClock::at(2020, 6, 10, 12, 0)));
+        assert!(Filter::ThisWeek.matches(&at(14, 20), &FixedClock::at(2020, 6, 10, 12, 0)));
+        assert!(!Filter::ThisWeek.matches(&on(15), &FixedClock::at(2020, 6, 10, 12, 0)));
+    }
+
+    #[test]
+    pub fn priority() {
+        let filter = Filter::Priority(Priority::High);
+        let mut event = on(12);
+        assert!(!filter.matches(&event, &FixedClock::at(2020, 6, 10, 12, 0)));
+        event.priority = Priority::High;
+        assert!(filter.matches(&event, &FixedClock::at(2020, 6, 10, 12, 0)));
+        event.priority = Priority::Critical;
+        assert!(filter.matches(&event, &FixedClock::at(2020, 6, 10, 12, 0)));
+    }
+}
@@ -0,0 +1,582 @@
+/*
+   TU - Time's Up!
+   Copyright (C) 2020  Julio Biason
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Sharing the event file through git.
+//!
+//! The directory of the event file is a git repository. With
+//! [`Committing`], each change to the events is a commit describing it;
+//! [`sync`] then takes the commits in the remote and sends the local ones.
+//! Instead of a textual merge of the file, local commits are replayed on top
+//! of the remote ones with an event by event [`merge`], so changes to
+//! different events, or to different fields of an event, never conflict.
+
+use std::env;
+use std::fmt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::SystemTime;
+
+use crate::event::Event;
+use crate::eventlist::EventList;
+use crate::eventlist::EventListError;
+use crate::merge::merge;
+use crate::merge::Conflict;
+use crate::storage::Storage;
+use crate::storage::TomlFile;
+
+/// Errors when using git.
+#[derive(Debug)]
+pub enum GitError {
+    /// git can't be run.
+    Unavailable(String),
+    /// A git command failed, with the command and what git said.
+    Failed(String, String),
+    /// The events in the repository can't be read or written.
+    Storage(EventListError),
+}
+
+impl fmt::Display for GitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GitError::Unavailable(reason) => write!(f, "can't run git: {}", reason),
+            GitError::Failed(command, output) => write!(f, "`{}` failed: {}", command, output),
+            GitError::Storage(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for GitError {}
+
+impl From<EventListError> for GitError {
+    fn from(error: EventListError) -> GitError {
+        GitError::Storage(error)
+    }
+}
+
+/// A git repository, used through the git command.
+#[derive(Debug)]
+pub struct Repository {
+    dir: PathBuf,
+}
+
+impl Repository {
+    /// The repository in the directory; it may not exist yet.
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Whether the directory is a repository already.
+    pub fn exists(&self) -> bool {
+        self.dir.join(".git").exists()
+    }
+
+    /// Run git in the repository, returning its output.
+    fn git(&self, args: &[&str]) -> Result<String, GitError> {
+        log::debug!("git {}", args.join(" "));
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(&self.dir)
+            .args(args)
+            .output()
+            .map_err(|error| GitError::Unavailable(error.to_string()))?;
+        match output.status.success() {
+            true => Ok(String::from_utf8_lossy(&output.stdout).trim_end().into()),
+            false => Err(GitError::Failed(
+                format!("git {}", args.join(" ")),
+                String::from_utf8_lossy(&output.stderr).trim().into(),
+            )),
+        }
+    }
+
+    /// Whether the git command succeeds.
+    fn succeeds(&self, args: &[&str]) -> bool {
+        self.git(args).is_ok()
+    }
+
+    /// Create the repository, if needed, with the remote as `origin` and
+    /// the branch as the current one.
+    pub fn init(&self, remote: &str, branch: &str) -> Result<(), GitError> {
+        if !self.exists() {
+            self.git(&["init", "-q"])?;
+            self.git(&["symbolic-ref", "HEAD", &format!("refs/heads/{}", branch)])?;
+        }
+        if !self.succeeds(&["config", "user.name"]) {
+            let user = env::var("USER").unwrap_or_else(|_| "tu".into());
+            self.git(&["config", "user.name", &user])?;
+            self.git(&["config", "user.email", &format!("{}@localhost", user)])?;
+        }
+        match self.git(&["remote", "get-url", "origin"]) {
+            Ok(url) if url == remote => Ok(()),
+            Ok(_) => self
+                .git(&["remote", "set-url", "origin", remote])
+                .map(|_| ()),
+            Err(_) => self.git(&["remote", "add", "origin", remote]).map(|_| ()),
+        }
+    }
+
+    /// Commit the file with the message; false if it had no changes.
+    pub fn commit(&self, file: &str, message: &str) -> Result<bool, GitError> {
+        self.commit_as(file, message, None)
+    }
+
+    fn commit_as(&self, file: &str, message: &str, author: Option<&str>) -> Result<bool, GitError> {
+        self.git(&["add", "--", file])?;
+        if self.succeeds(&["diff", "--cached", "--quiet", "--", file]) {
+            return Ok(false);
+        }
+        let mut args = vec!["commit", "-q", "-m", message];
+        if let Some(author) = author {
+            args.push("--author");
+            args.push(author);
+        }
+        self.git(&args)?;
+        Ok(true)
+    }
+
+    /// The events in the file as of a revision; none if it had no such file.
+    fn events_at(&self, revision: &str, file: &str) -> Result<EventList, GitError> {
+        match self.git(&["show", &format!("{}:{}", revision, file)]) {
+            Ok(content) => Ok(TomlFile::parse(&content)?),
+            Err(_) => Ok(EventList::empty()),
+        }
+    }
+
+    /// Number of commits in the range.
+    fn count(&self, range: &str) -> Result<usize, GitError> {
+        Ok(self
+            .git(&["rev-list", "--count", range])?
+            .parse()
+            .unwrap_or_default())
+    }
+}
+
+/// One line about an event.
+fn mention(verb: &str, event: &Event) -> String {
+    format!("{} '{}' ({})", verb, event.description, event.id)
+}
+
+/// A commit message for the changes between the lists; none if they have
+/// the same events.
+pub fn describe(before: &EventList, after: &EventList) -> Option<String> {
+    let added: Vec<&Event> = after
+        .iter()
+        .filter(|event| before.get(&event.id).is_none())
+        .collect();
+    let removed: Vec<&Event> = before
+        .iter()
+        .filter(|event| after.get(&event.id).is_none())
+        .collect();
+    let changed: Vec<&Event> = after
+        .iter()
+        .filter(|event| before.get(&event.id).is_some_and(|old| !old.same_as(event)))
+        .collect();
+
+    let lines: Vec<String> = added
+        .iter()
+        .map(|event| mention("Add", event))
+        .chain(removed.iter().map(|event| mention("Remove", event)))
+        .chain(changed.iter().map(|event| mention("Change", event)))
+        .collect();
+    if lines.len() <= 1 {
+        return lines.into_iter().next();
+    }
+
+    let count = |verb: &str, events: &[&Event]| match events.len() {
+        0 => None,
+        1 => Some(format!("{} 1 event", verb)),
+        count => Some(format!("{} {} events", verb, count)),
+    };
+    let summary: Vec<String> = vec![
+        count("add", &added),
+        count("remove", &removed),
+        count("change", &changed),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    let summary = summary.join(", ");
+    let mut message = summary[..1].to_uppercase() + &summary[1..];
+    message.push('\n');
+    for line in lines {
+        message.push_str(&format!("\n- {}", line));
+    }
+    Some(message)
+}
+
+/// An event file in a git repository; each change is committed, once the
+/// repository exists.
+#[derive(Debug)]
+pub struct Committing {
+    file: TomlFile,
+    repository: Repository,
+    name: String,
+}
+
+impl Committing {
+    /// Commit the changes to the file, in the repository of its directory.
+    pub fn new(file: TomlFile) -> Self {
+        let (dir, name) = split_path(file.path());
+        Self {
+            file,
+            repository: Repository::new(dir),
+            name,
+        }
+    }
+}
+
+/// The directory of the event file and its name in there.
+fn split_path(path: &Path) -> (PathBuf, String) {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    (dir, name)
+}
+
+impl Storage for Committing {
+    fn load(&self) -> Result<EventList, EventListError> {
+        self.file.load()
+    }
+
+    fn save(&mut self, list: &EventList) -> Result<(), EventListError> {
+        let before = self.file.load().unwrap_or_default();
+        self.file.save(list)?;
+        if !self.repository.exists() {
+            return Ok(());
+        }
+        match describe(&before, list) {
+            Some(message) => match self.repository.commit(&self.name, &message) {
+                Ok(_) => Ok(()),
+                Err(error) => Err(EventListError::StorageFailure(format!(
+                    "the events were saved, but not committed: {}",
+                    error
+                ))),
+            },
+            None => Ok(()),
+        }
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        self.file.modified()
+    }
+}
+
+/// What a synchronization did.
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    /// Commits taken from the remote.
+    pub received: usize,
+    /// Commits sent to the remote.
+    pub sent: usize,
+    /// Events changed in both sides; the local change stays.
+    pub conflicts: Vec<Conflict>,
+}
+
+impl fmt::Display for SyncReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} changes received, {} sent", self.received, self.sent)?;
+        for conflict in &self.conflicts {
+            write!(f, "\nConflict, kept the local version: {}", conflict)?;
+        }
+        Ok(())
+    }
+}
+
+/// Synchronize the event file in the path with the branch of the remote,
+/// creating the repository if needed.
+pub fn sync(path: &Path, remote: &str, branch: &str) -> Result<SyncReport, GitError> {
+    let (dir, name) = split_path(path);
+    let repository = Repository::new(&dir);
+    repository.init(remote, branch)?;
+
+    if path.exists() {
+        let before = repository.events_at("HEAD", &name)?;
+        let message = describe(&before, &TomlFile::new(path).load()?)
+            .unwrap_or_else(|| "Update events".into());
+        repository.commit(&name, &message)?;
+    }
+
+    repository.git(&["fetch", "-q", "origin"])?;
+    let upstream = format!("refs/remotes/origin/{}", branch);
+    let has_upstream = repository.succeeds(&["rev-parse", "--verify", "-q", &upstream]);
+    let has_head = repository.succeeds(&["rev-parse", "--verify", "-q", "HEAD"]);
+    let mut report = SyncReport::default();
+    match (has_head, has_upstream) {
+        (false, false) => return Ok(report),
+        (false, true) => {
+            repository.git(&["reset", "-q", "--hard", &upstream])?;
+            report.received = repository.count("HEAD")?;
+            return Ok(report);
+        }
+        (true, false) => report.sent = repository.count("HEAD")?,
+        (true, true) => {
+            report.received = repository.count(&format!("HEAD..{}", upstream))?;
+            let local =
+                repository.git(&["rev-list", "--reverse", &format!("{}..HEAD", upstream)])?;
+            let local: Vec<&str> = local.lines().collect();
+            report.sent = local.len();
+            match (local.is_empty(), report.received) {
+                (true, 0) => return Ok(report),
+                (true, _) => {
+                    repository.git(&["merge", "-q", "--ff-only", &upstream])?;
+                    return Ok(report);
+                }
+                (false, 0) => {}
+                (false, _) => {
+                    report.conflicts = replay(&repository, path, &name, &upstream, &local)?;
+                }
+            }
+        }
+    }
+    repository.git(&[
+        "push",
+        "-q",
+        "origin",
+        &format!("HEAD:refs/heads/{}", branch),
+    ])?;
+    Ok(report)
+}
+
+/// Move the branch to the upstream, and commit again the changes of each
+/// local commit on top of it, merging them event by event.
+fn replay(
+    repository: &Repository,
+    path: &Path,
+    name: &str,
+    upstream: &str,
+    commits: &[&str],
+) -> Result<Vec<Conflict>, GitError> {
+    struct Change {
+        base: EventList,
+        changed: EventList,
+        message: String,
+        author: String,
+    }
+    let mut changes = Vec::new();
+    for commit in commits {
+        changes.push(Change {
+            base: repository.events_at(&format!("{}^", commit), name)?,
+            changed: repository.events_at(commit, name)?,
+            message: repository.git(&["log", "-1", "--format=%B", commit])?,
+            author: repository.git(&["log", "-1", "--format=%an <%ae>", commit])?,
+        });
+    }
+
+    // If anything fails midway, the branch goes back to the local commits,
+    // instead of leaving them only in the reflog.
+    let original = repository.git(&["rev-parse", "HEAD"])?;
+    let replayed = (|| {
+        repository.git(&["reset", "-q", "--hard", upstream])?;
+        let mut file = TomlFile::new(path);
+        let mut conflicts = Vec::new();
+        for change in changes {
+            // The local change is "ours", so it stays in conflicts.
+            let merged = merge(&change.base, &change.changed, &file.load()?);
+            file.save(&merged.list)?;
+            repository.commit_as(name, &change.message, Some(&change.author))?;
+            conflicts.extend(merged.conflicts);
+        }
+        Ok(conflicts)
+    })();
+    if replayed.is_err() {
+        if let Err(error) = repository.git(&["reset", "-q", "--hard", &original]) {
+            log::error!("can't restore the local commits at {}: {}", original, error);
+        }
+    }
+    replayed
+}
+
+#[cfg(test)]
+mod gitsync_test {
+    use std::path::Path;
+    use std::path::PathBuf;
+    use std::process::Command;
+
+    use super::describe;
+    use super::sync;
+    use super::Committing;
+    use super::Repository;
+    use crate::event::Event;
+    use crate::eventlist::EventList;
+    use crate::merge::ConflictKind;
+    use crate::merge::Field;
+    use crate::storage::Storage;
+    use crate::storage::TomlFile;
+
+    /// A bare repository to share the events, and the event file of two
+    /// users, with their repositories created by a first synchronization.
+    fn setup(root: &Path) -> (String, PathBuf, PathBuf) {
+        let remote = root.join("remote.git");
+        let status = Command::new("git")
+            .args(["init", "-q", "--bare"])
+            .arg(&remote)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let remote = remote.to_string_lossy().into_owned();
+        let alice = root.join("alice").join("events.toml");
+        let bob = root.join("bob").join("events.toml");
+        for path in [&alice, &bob] {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            sync(path, &remote, "main").unwrap();
+        }
+        (remote, alice, bob)
+    }
+
+    fn log(path: &Path) -> Vec<String> {
+        let repository = Repository::new(path.parent().unwrap());
+        let log = repository.git(&["log", "--format=%s"]).unwrap();
+        log.lines().map(String::from).collect()
+    }
+
+    fn descriptions(path: &Path) -> Vec<String> {
+        let list = TomlFile::new(path).load().unwrap();
+        let mut descriptions: Vec<String> =
+            list.iter().map(|event| event.description.clone()).collect();
+        descriptions.sort();
+        descriptions
+    }
+
+    #[test]
+    pub fn commits_each_change() {
+        let root = tempfile::tempdir().unwrap();
+        let (_, alice, _) = setup(root.path());
+        let mut storage = Committing::new(TomlFile::new(&alice));
+        storage
+            .insert(Event::on_day("a1", "Launch", 2030, 1, 1))
+            .unwrap();
+        let mut changed = Event::on_day("a1", "Launch v2", 2030, 1, 1);
+        changed.tags = vec!["work".into()];
+        storage.update(changed).unwrap();
+        storage.remove("a1").unwrap();
+
+        assert_eq!(
+            log(&alice),
+            vec![
+                "Remove 'Launch v2' (a1)",
+                "Change 'Launch v2' (a1)",
+                "Add 'Launch' (a1)"
+            ]
+        );
+    }
+
+    #[test]
+    pub fn no_repository_no_commits() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.toml");
+        let mut storage = Committing::new(TomlFile::new(&path));
+        storage
+            .insert(Event::on_day("a1", "Launch", 2030, 1, 1))
+            .unwrap();
+        assert_eq!(storage.load().unwrap().len(), 1);
+        assert!(!dir.path().join(".git").exists());
+    }
+
+    #[test]
+    pub fn concurrent_changes() {
+        let root = tempfile::tempdir().unwrap();
+        let (remote, alice, bob) = setup(root.path());
+        let mut alice_storage = Committing::new(TomlFile::new(&alice));
+        let mut bob_storage = Committing::new(TomlFile::new(&bob));
+
+        alice_storage
+            .insert(Event::on_day("a1", "Launch", 2030, 1, 1))
+            .unwrap();
+        let report = sync(&alice, &remote, "main").unwrap();
+        assert_eq!((report.received, report.sent), (0, 1));
+        let report = sync(&bob, &remote, "main").unwrap();
+        assert_eq!((report.received, report.sent), (1, 0));
+        assert_eq!(descriptions(&bob), vec!["Launch"]);
+
+        // Both change the same file at once, but not the same events.
+        alice_storage
+            .insert(Event::on_day("a2", "Party", 2030, 1, 1))
+            .unwrap();
+        bob_storage
+            .insert(Event::on_day("b1", "Review", 2030, 1, 1))
+            .unwrap();
+        bob_storage.remove("a1").unwrap();
+        sync(&alice, &remote, "main").unwrap();
+        let report = sync(&bob, &remote, "main").unwrap();
+        assert_eq!((report.received, report.sent), (1, 2));
+        assert!(report.conflicts.is_empty());
+        sync(&alice, &remote, "main").unwrap();
+
+        assert_eq!(descriptions(&alice), vec!["Party", "Review"]);
+        assert_eq!(descriptions(&bob), vec!["Party", "Review"]);
+        assert_eq!(
+            log(&bob)[..3],
+            [
+                "Remove 'Launch' (a1)",
+                "Add 'Review' (b1)",
+                "Add 'Party' (a2)"
+            ]
+        );
+    }
+
+    #[test]
+    pub fn conflicts_keep_local() {
+        let root = tempfile::tempdir().unwrap();
+        let (remote, alice, bob) = setup(root.path());
+        Committing::new(TomlFile::new(&alice))
+            .insert(Event::on_day("a1", "Launch", 2030, 1, 1))
+            .unwrap();
+        sync(&alice, &remote, "main").unwrap();
+        sync(&bob, &remote, "main").unwrap();
+
+        Committing::new(TomlFile::new(&alice))
+            .update(Event::on_day("a1", "Launch on Monday", 2030, 1, 1))
+            .unwrap();
+        Committing::new(TomlFile::new(&bob))
+            .update(Event::on_day("a1", "Launch on Friday", 2030, 1, 1))
+            .unwrap();
+        sync(&alice, &remote, "main").unwrap();
+        let report = sync(&bob, &remote, "main").unwrap();
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(
+            report.conflicts[0].kind,
+            ConflictKind::BothChanged(Field::Description)
+        );
+        assert_eq!(descriptions(&bob), vec!["Launch on Friday"]);
+    }
+
+    #[test]
+    pub fn messages() {
+        let mut before = EventList::empty();
+        before.push(Event::on_day("a1", "Launch", 2030, 1, 1));
+        before.push(Event::on_day("a2", "Party", 2030, 1, 1));
+        let mut after = EventList::empty();
+        after.push(Event::on_day("a2", "Party", 2030, 1, 1));
+        after.push(Event::on_day("b1", "Review", 2030, 1, 1));
+        after.push(Event::on_day("b2", "Retro", 2030, 1, 1));
+
+        assert!(describe(&before, &before).is_none());
+        assert_eq!(
+            describe(&before, &after).unwrap(),
+            "Add 2 events, remove 1 event\n\n\
+             - Add 'Review' (b1)\n- Add 'Retro' (b2)\n- Remove 'Launch' (a1)"
+        );
+    }
+}
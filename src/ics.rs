@@ -0,0 +1,524 @@
+/*
+   TU - Time's Up!
+   Copyright (C) 2020  Julio Biason
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Events as iCalendar (RFC 5545), for calendar applications.
+//!
+//! Each event is a VEVENT with the event ID as its UID; all day events take
+//! the whole day, events at a specific time are instants, in UTC.
+//!
+//! Reading goes the other way, taking the events of calendars written by
+//! other programs; times with a timezone other than UTC are taken as local
+//! times, and only the first occurrence of repeating events is kept.
+
+use std::fmt;
+
+use chrono::Duration;
+use chrono::Local;
+use chrono::NaiveDate;
+use chrono::NaiveDateTime;
+use chrono::TimeZone;
+use chrono::Utc;
+
+use crate::clock::Clock;
+use crate::date::Date;
+use crate::datetime::DateTime;
+use crate::event::Event;
+use crate::eventtype::EventType;
+use crate::priority::Priority;
+
+/// Identifies tu as the program that wrote the calendar.
+const PRODID: &str = "-//Julio Biason//Time's Up!//EN";
+
+/// Lines can't be longer than this, in bytes, without the line break.
+const LINE_LENGTH: usize = 75;
+
+/// Escape the characters with special meaning in text values.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Break a long content line into lines of up to [`LINE_LENGTH`] bytes, the
+/// continuations starting with a space; never splits a character.
+fn fold(line: &str, into: &mut String) {
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > LINE_LENGTH {
+            into.push_str("\r\n ");
+            length = 1;
+        }
+        into.push(c);
+        length += c.len_utf8();
+    }
+    into.push_str("\r\n");
+}
+
+/// A moment, in UTC.
+fn utc(moment: &chrono::DateTime<chrono::Local>) -> String {
+    moment
+        .with_timezone(&Utc)
+        .format("%Y%m%dT%H%M%SZ")
+        .to_string()
+}
+
+/// The content lines of an event.
+fn vevent(event: &Event, clock: &dyn Clock) -> Vec<String> {
+    let mut lines = vec![
+        "BEGIN:VEVENT".to_string(),
+        format!("UID:{}", escape(&event.id)),
+        format!("DTSTAMP:{}", utc(&clock.now())),
+    ];
+    match &event.due {
+        EventType::AllDay(date) => {
+            lines.push(format!("DTSTART;VALUE=DATE:{}", date.0.format("%Y%m%d")));
+            let next = date.0 + Duration::days(1);
+            lines.push(format!("DTEND;VALUE=DATE:{}", next.format("%Y%m%d")));
+        }
+        EventType::AtTime(datetime) => lines.push(format!("DTSTART:{}", utc(&datetime.0))),
+    }
+    lines.push(format!("SUMMARY:{}", escape(&event.description)));
+    if !event.tags.is_empty() {
+        let tags: Vec<String> = event.tags.iter().map(|tag| escape(tag)).collect();
+        lines.push(format!("CATEGORIES:{}", tags.join(",")));
+    }
+    if let Some(level) = priority_level(event.priority) {
+        lines.push(format!("PRIORITY:{}", level));
+    }
+    // Events have no completion in iCalendar; only tasks do.
+    if let Some(completed) = &event.completed_at {
+        lines.push(format!("X-TU-COMPLETED:{}", utc(&completed.0)));
+    }
+    if let Some(created) = &event.created_at {
+        lines.push(format!("CREATED:{}", utc(&created.0)));
+    }
+    if let Some(updated) = &event.updated_at {
+        lines.push(format!("LAST-MODIFIED:{}", utc(&updated.0)));
+    }
+    lines.push("END:VEVENT".to_string());
+    lines
+}
+
+/// A calendar with the events.
+pub fn calendar<'a, I>(events: I, clock: &dyn Clock) -> String
+where
+    I: IntoIterator<Item = &'a Event>,
+{
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:{}", PRODID),
+        "CALSCALE:GREGORIAN".to_string(),
+    ];
+    for event in events {
+        lines.extend(vevent(event, clock));
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    let mut content = String::new();
+    for line in lines {
+        fold(&line, &mut content);
+    }
+    content
+}
+
+/// An event read from a calendar.
+#[derive(Debug, Clone)]
+pub struct VEvent {
+    /// The event, with the UID as its ID.
+    pub event: Event,
+    /// When the event was last changed, if the calendar says so
+    /// (`LAST-MODIFIED`, or `DTSTAMP` without it).
+    pub modified: Option<DateTime>,
+}
+
+/// Errors when reading calendars.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IcsError {
+    /// There is an event without UID.
+    MissingUid,
+    /// The event with the UID has no start.
+    MissingStart(String),
+    /// The value is not an iCalendar date or date and time.
+    InvalidDate(String),
+}
+
+impl fmt::Display for IcsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IcsError::MissingUid => write!(f, "there is an event without UID"),
+            IcsError::MissingStart(uid) => write!(f, "event {} has no start", uid),
+            IcsError::InvalidDate(value) => write!(f, "'{}' is not an iCalendar date", value),
+        }
+    }
+}
+
+impl std::error::Error for IcsError {}
+
+/// Undo the escapes of text values.
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => unescaped.push('\n'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Split a list of text values in the commas that are not escaped.
+fn split_list(text: &str) -> Vec<String> {
+    let mut values = Vec::new();
+    let mut current = String::new();
+    let mut escaped = false;
+    for c in text.chars() {
+        match c {
+            ',' if !escaped => values.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+        escaped = c == '\\' && !escaped;
+    }
+    values.push(current);
+    values
+        .iter()
+        .map(|value| unescape(value))
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
+/// The content lines, with the folded ones joined back.
+fn unfold(content: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in content.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        match (
+            line.strip_prefix(' ').or_else(|| line.strip_prefix('\t')),
+            lines.last_mut(),
+        ) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ if line.is_empty() => {}
+            _ => lines.push(line.into()),
+        }
+    }
+    lines
+}
+
+/// A content line split in its name (uppercase), parameters and value.
+fn split_line(line: &str) -> (String, Vec<String>, &str) {
+    let mut quoted = false;
+    let colon = line
+        .char_indices()
+        .find(|(_, c)| {
+            quoted ^= *c == '"';
+            *c == ':' && !quoted
+        })
+        .map_or(line.len(), |(position, _)| position);
+    let mut head = line[..colon].split(';');
+    let name = head.next().unwrap_or_default().to_uppercase();
+    let parameters = head.map(str::to_uppercase).collect();
+    (name, parameters, line.get(colon + 1..).unwrap_or_default())
+}
+
+/// A date (`YYYYMMDD`) or date and time (`YYYYMMDDTHHMMSS`, in UTC if it
+/// ends with `Z`, local time otherwise).
+fn parse_due(value: &str, parameters: &[String]) -> Result<EventType, IcsError> {
+    let invalid = || IcsError::InvalidDate(value.into());
+    if value.len() == 8 || parameters.iter().any(|parameter| parameter == "VALUE=DATE") {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").map_err(|_| invalid())?;
+        return Ok(EventType::AllDay(Date(date)));
+    }
+    let (time, utc) = match value.strip_suffix('Z') {
+        Some(time) => (time, true),
+        None => (value, false),
+    };
+    let time = NaiveDateTime::parse_from_str(time, "%Y%m%dT%H%M%S").map_err(|_| invalid())?;
+    let local = if utc {
+        Utc.from_utc_datetime(&time).with_timezone(&Local)
+    } else {
+        Local
+            .from_local_datetime(&time)
+            .earliest()
+            .ok_or_else(invalid)?
+    };
+    Ok(EventType::AtTime(DateTime(local)))
+}
+
+/// The PRIORITY of the event, from 1 (the highest) to 9 (the lowest);
+/// normal events go without one.
+fn priority_level(priority: Priority) -> Option<u8> {
+    match priority {
+        Priority::Critical => Some(1),
+        Priority::High => Some(3),
+        Priority::Normal => None,
+        Priority::Low => Some(9),
+    }
+}
+
+/// The priority for a PRIORITY value; 0, 5 and anything invalid are
+/// normal.
+fn parse_priority(value: &str) -> Priority {
+    match value.trim().parse::<u8>() {
+        Ok(1) => Priority::Critical,
+        Ok(2..=4) => Priority::High,
+        Ok(6..=9) => Priority::Low,
+        _ => Priority::Normal,
+    }
+}
+
+/// A moment, for the properties that are always date and time.
+fn parse_moment(value: &str) -> Result<DateTime, IcsError> {
+    match parse_due(value, &[])? {
+        EventType::AtTime(datetime) => Ok(datetime),
+        EventType::AllDay(_) => Err(IcsError::InvalidDate(value.into())),
+    }
+}
+
+/// The properties of an event being read.
+#[derive(Default)]
+struct Properties {
+    uid: Option<String>,
+    due: Option<EventType>,
+    summary: String,
+    tags: Vec<String>,
+    priority: Priority,
+    completed: Option<DateTime>,
+    created: Option<DateTime>,
+    last_modified: Option<DateTime>,
+    stamp: Option<DateTime>,
+    /// Changes a single occurrence of a repeating event.
+    recurrence: bool,
+}
+
+impl Properties {
+    fn set(&mut self, line: &str) -> Result<(), IcsError> {
+        let (name, parameters, value) = split_line(line);
+        match name.as_str() {
+            "UID" => self.uid = Some(value.into()),
+            "DTSTART" => self.due = Some(parse_due(value, &parameters)?),
+            "SUMMARY" => self.summary = unescape(value),
+            "CATEGORIES" => self.tags.extend(split_list(value)),
+            "PRIORITY" => self.priority = parse_priority(value),
+            "X-TU-COMPLETED" => self.completed = parse_moment(value).ok(),
+            "CREATED" => self.created = parse_moment(value).ok(),
+            "LAST-MODIFIED" => self.last_modified = parse_moment(value).ok(),
+            "DTSTAMP" => self.stamp = parse_moment(value).ok(),
+            "RECURRENCE-ID" => self.recurrence = true,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn into_vevent(self) -> Result<VEvent, IcsError> {
+        let uid = self.uid.ok_or(IcsError::MissingUid)?;
+        let due = self
+            .due
+            .ok_or_else(|| IcsError::MissingStart(uid.clone()))?;
+        Ok(VEvent {
+            event: Event {
+                id: uid,
+                description: self.summary,
+                tags: self.tags,
+                priority: self.priority,
+                created_at: self.created,
+                updated_at: self.last_modified,
+                created_by: None,
+                completed_at: self.completed,
+                due,
+            },
+            modified: self.last_modified.or(self.stamp),
+        })
+    }
+}
+
+/// Read the events in a calendar.
+pub fn parse(content: &str) -> Result<Vec<VEvent>, IcsError> {
+    let mut events = Vec::new();
+    let mut current: Option<Properties> = None;
+    // Components inside the event, like alarms, whose properties are not
+    // the event's.
+    let mut nested = 0;
+    for line in unfold(content) {
+        let (name, _, value) = split_line(&line);
+        match (
+            name.as_str(),
+            value.to_uppercase().as_str(),
+            current.as_mut(),
+        ) {
+            ("BEGIN", "VEVENT", None) => current = Some(Properties::default()),
+            ("BEGIN", _, Some(_)) => nested += 1,
+            ("END", "VEVENT", Some(_)) if nested == 0 => {
+                let properties = current.take().unwrap_or_default();
+                if !properties.recurrence {
+                    events.push(properties.into_vevent()?);
+                }
+            }
+            ("END", _, Some(_)) => nested -= 1,
+            (_, _, Some(properties)) if nested == 0 => properties.set(&line)?,
+            _ => {}
+        }
+    }
+    Ok(events)
+}
+
+#[cfg(test)]
+mod ics_test {
+    use super::calendar;
+    use super::fold;
+    use super::parse;
+    use super::IcsError;
+    use crate::clock::Clock;
+    use crate::clock::FixedClock;
+    use crate::date::Date;
+    use crate::datetime::DateTime;
+    use crate::event::Event;
+    use crate::eventtype::EventType;
+    use crate::priority::Priority;
+
+    #[test]
+    pub fn all_day() {
+        let mut event =
+            Event::new_on_date("a1", "Taxes; again, sigh", &Date::new(2020, 6, 30).unwrap())
+                .unwrap();
+        event.tags = vec!["home".into(), "money".into()];
+        let content = calendar(vec![&event], &FixedClock::at(2020, 6, 10, 12, 0));
+
+        assert!(content.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(content.ends_with("END:VCALENDAR\r\n"));
+        assert!(content.contains("UID:a1\r\n"));
+        assert!(content.contains("DTSTART;VALUE=DATE:20200630\r\n"));
+        assert!(content.contains("DTEND;VALUE=DATE:20200701\r\n"));
+        assert!(content.contains("SUMMARY:Taxes\\; again\\, sigh\r\n"));
+        assert!(content.contains("CATEGORIES:home,money\r\n"));
+        assert!(!content.contains("PRIORITY"));
+    }
+
+    #[test]
+    pub fn at_time_in_utc() {
+        let datetime = DateTime::new(2020, 6, 12, 14, 30).unwrap();
+        let event = Event::new_on_date_time("b2", "Call", &datetime).unwrap();
+        let content = calendar(vec![&event], &FixedClock::at(2020, 6, 10, 12, 0));
+
+        let expected = datetime
+            .0
+            .with_timezone(&chrono::Utc)
+            .format("DTSTART:%Y%m%dT%H%M%SZ\r\n")
+            .to_string();
+        assert!(content.contains(&expected));
+        assert!(!content.contains("DTEND"));
+    }
+
+    #[test]
+    pub fn folding() {
+        let mut folded = String::new();
+        fold(&"é".repeat(50), &mut folded);
+        let lines: Vec<&str> = folded.trim_end().split("\r\n").collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].len(), 74);
+        assert!(lines[1].starts_with(' '));
+        assert!(lines.iter().all(|line| line.len() <= 75));
+    }
+
+    #[test]
+    pub fn round_trip() {
+        let datetime = DateTime::new(2020, 6, 12, 14, 30).unwrap();
+        let mut event =
+            Event::new_on_date_time("b2", "Call, with \\ and\nlines", &datetime).unwrap();
+        event.tags = vec!["a,b".into(), "c".into()];
+        event.created_at = Some(DateTime::new(2020, 6, 1, 8, 0).unwrap());
+        event.priority = Priority::High;
+        event.completed_at = Some(DateTime::new(2020, 6, 11, 9, 0).unwrap());
+        let mut long =
+            Event::new_on_date("c3", &"long ".repeat(40), &Date::new(2020, 7, 1).unwrap()).unwrap();
+        long.tags = Vec::new();
+
+        let events = parse(&calendar(
+            vec![&event, &long],
+            &FixedClock::at(2020, 6, 10, 12, 0),
+        ))
+        .unwrap();
+        assert_eq!(events.len(), 2);
+        let read = &events[0].event;
+        assert_eq!(read.id, "b2");
+        assert_eq!(read.description, event.description);
+        assert_eq!(read.tags, event.tags);
+        assert_eq!(read.priority, Priority::High);
+        assert!(read.same_as(&event));
+        assert_eq!(read.due.timestamp(), datetime.timestamp());
+        assert_eq!(
+            read.created_at.unwrap().timestamp(),
+            event.created_at.unwrap().timestamp()
+        );
+        assert_eq!(
+            events[0].modified.unwrap().timestamp(),
+            FixedClock::at(2020, 6, 10, 12, 0).now().timestamp()
+        );
+        assert_eq!(events[1].event.description, long.description);
+        assert_eq!(events[1].event.priority, Priority::Normal);
+        assert!(matches!(events[1].event.due, EventType::AllDay(_)));
+    }
+
+    #[test]
+    pub fn other_calendars() {
+        let content = "BEGIN:VCALENDAR\nBEGIN:VTODO\nUID:todo\nEND:VTODO\n\
+            BEGIN:VEVENT\nUID:x@example.com\nDTSTART;TZID=\"Europe/A:B\":20200612T143000\n\
+            LAST-MODIFIED:20200101T000000Z\nSUMMARY:Meeting\n\
+            BEGIN:VALARM\nSUMMARY:Alarm\nEND:VALARM\nEND:VEVENT\n\
+            BEGIN:VEVENT\nUID:x@example.com\nRECURRENCE-ID:20200619T143000\n\
+            DTSTART:20200620T143000\nSUMMARY:Moved\nEND:VEVENT\nEND:VCALENDAR\n";
+        let events = parse(content).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event.description, "Meeting");
+        assert_eq!(
+            events[0].event.due.timestamp(),
+            DateTime::new(2020, 6, 12, 14, 30).unwrap().timestamp() - 59
+        );
+        assert_eq!(events[0].modified.unwrap().timestamp(), 1_577_836_800);
+    }
+
+    #[test]
+    pub fn broken_events() {
+        assert_eq!(
+            parse("BEGIN:VEVENT\nDTSTART:20200101\nEND:VEVENT").unwrap_err(),
+            IcsError::MissingUid
+        );
+        assert_eq!(
+            parse("BEGIN:VEVENT\nUID:a\nEND:VEVENT").unwrap_err(),
+            IcsError::MissingStart("a".into())
+        );
+        assert_eq!(
+            parse("BEGIN:VEVENT\nUID:a\nDTSTART:2020\nEND:VEVENT").unwrap_err(),
+            IcsError::InvalidDate("2020".into())
+        );
+    }
+}
@@ -0,0 +1,223 @@
+/*
+   TU - Time's Up!
+   Copyright (C) 2020  Julio Biason
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Identifiers for new events.
+//!
+//! Event IDs come from an [`IdGenerator`]; [`new_id`] keeps asking the
+//! generator till it gets one that is not used by any existing event.
+
+use serde_derive::Deserialize;
+use uuid::Uuid;
+
+use crate::eventlist::EventList;
+use crate::eventlist::EventListError;
+
+/// How many times to ask a generator for an ID before giving up.
+const MAX_ATTEMPTS: usize = 32;
+
+static ADJECTIVES: &[&str] = &[
+    "amber", "brave", "calm", "clever", "dusty", "eager", "fancy", "gentle", "happy", "icy",
+    "jolly", "keen", "lucky", "merry", "nimble", "odd", "proud", "quick", "quiet", "rapid",
+    "shiny", "silent", "sleepy", "sunny", "swift", "tidy", "vivid", "warm", "wild", "witty",
+    "young", "zesty",
+];
+
+static NOUNS: &[&str] = &[
+    "badger", "beacon", "canyon", "comet", "delta", "falcon", "forest", "glacier", "harbor",
+    "island", "jungle", "lagoon", "lantern", "meadow", "meteor", "nebula", "otter", "panda",
+    "pepper", "planet", "prairie", "puffin", "river", "rocket", "summit", "thunder", "tiger",
+    "tundra", "valley", "walrus", "willow", "zephyr",
+];
+
+/// Something that comes up with IDs for new events.
+pub trait IdGenerator {
+    /// Generate a new ID. The existing events are there for generators that
+    /// depend on them; generators are allowed to return IDs already in use,
+    /// which will make them be asked again.
+    fn generate(&mut self, existing: &EventList) -> String;
+}
+
+/// The available ID schemes.
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum IdScheme {
+    /// Eight random hex digits, like `1a919a4a`.
+    #[default]
+    Short,
+    /// Numbers, one more than the biggest numeric ID in use.
+    Sequential,
+    /// A full random UUID.
+    Uuid,
+    /// Random words and a number, like `calm-river-42`.
+    Words,
+}
+
+impl IdScheme {
+    /// A generator for the scheme.
+    pub fn generator(&self) -> Box<dyn IdGenerator> {
+        match self {
+            IdScheme::Short => Box::new(Short),
+            IdScheme::Sequential => Box::new(Sequential),
+            IdScheme::Uuid => Box::new(FullUuid),
+            IdScheme::Words => Box::new(Words),
+        }
+    }
+}
+
+/// Eight hex digits, from the first field of a random UUID.
+#[derive(Debug, Default)]
+pub struct Short;
+
+impl IdGenerator for Short {
+    fn generate(&mut self, _existing: &EventList) -> String {
+        let (id, _, _, _) = Uuid::new_v4().as_fields();
+        format!("{:x}", id)
+    }
+}
+
+/// Sequential numbers, starting at 1; once the largest number is taken,
+/// the smallest free one.
+#[derive(Debug, Default)]
+pub struct Sequential;
+
+impl IdGenerator for Sequential {
+    fn generate(&mut self, existing: &EventList) -> String {
+        let numbers = || {
+            existing
+                .iter()
+                .filter_map(|event| event.id.parse::<u64>().ok())
+        };
+        let next = numbers().max().unwrap_or(0).checked_add(1);
+        let next = next.unwrap_or_else(|| {
+            (1..u64::MAX)
+                .find(|number| numbers().all(|used| used != *number))
+                .unwrap_or(u64::MAX)
+        });
+        next.to_string()
+    }
+}
+
+/// A full random UUID.
+#[derive(Debug, Default)]
+pub struct FullUuid;
+
+impl IdGenerator for FullUuid {
+    fn generate(&mut self, _existing: &EventList) -> String {
+        Uuid::new_v4().to_string()
+    }
+}
+
+/// An adjective, a noun and a number, picked at random.
+#[derive(Debug, Default)]
+pub struct Words;
+
+impl IdGenerator for Words {
+    fn generate(&mut self, _existing: &EventList) -> String {
+        let bytes = Uuid::new_v4();
+        let bytes = bytes.as_bytes();
+        format!(
+            "{}-{}-{}",
+            ADJECTIVES[bytes[0] as usize % ADJECTIVES.len()],
+            NOUNS[bytes[1] as usize % NOUNS.len()],
+            bytes[2] % 100
+        )
+    }
+}
+
+/// Ask the generator for an ID not used by any of the existing events.
+pub fn new_id(
+    generator: &mut dyn IdGenerator,
+    existing: &EventList,
+) -> Result<String, EventListError> {
+    for _ in 0..MAX_ATTEMPTS {
+        let id = generator.generate(existing);
+        if existing.get(&id).is_none() {
+            return Ok(id);
+        }
+        log::debug!("ID {} is already in use, trying again", id);
+    }
+    Err(EventListError::NoFreeId)
+}
+
+#[cfg(test)]
+mod idgen_test {
+    use super::new_id;
+    use super::IdGenerator;
+    use super::IdScheme;
+    use super::Sequential;
+    use crate::date::Date;
+    use crate::event::Event;
+    use crate::eventlist::EventList;
+
+    /// Returns the IDs in order, repeating the last one forever.
+    struct Fixed(Vec<&'static str>);
+
+    impl IdGenerator for Fixed {
+        fn generate(&mut self, _existing: &EventList) -> String {
+            if self.0.len() > 1 {
+                self.0.remove(0).into()
+            } else {
+                self.0[0].into()
+            }
+        }
+    }
+
+    fn list_with(ids: &[&str]) -> EventList {
+        let date = Date::new(2030, 1, 1).unwrap();
+        ids.iter()
+            .map(|id| Event::new_on_date(id, "event", &date).unwrap())
+            .collect()
+    }
+
+    #[test]
+    pub fn retry_on_collision() {
+        let existing = list_with(&["a", "b"]);
+        let mut generator = Fixed(vec!["a", "b", "c"]);
+        assert_eq!(new_id(&mut generator, &existing).unwrap(), "c");
+    }
+
+    #[test]
+    pub fn give_up() {
+        let existing = list_with(&["a"]);
+        assert!(new_id(&mut Fixed(vec!["a"]), &existing).is_err());
+    }
+
+    #[test]
+    pub fn sequential() {
+        let mut generator = Sequential;
+        assert_eq!(generator.generate(&EventList::empty()), "1");
+        assert_eq!(generator.generate(&list_with(&["1", "7", "abc"])), "8");
+        let last = u64::MAX.to_string();
+        assert_eq!(generator.generate(&list_with(&["1", &last])), "2");
+    }
+
+    #[test]
+    pub fn schemes() {
+        let empty = EventList::empty();
+        assert!(IdScheme::Short.generator().generate(&empty).len() <= 8);
+        assert_eq!(IdScheme::Uuid.generator().generate(&empty).len(), 36);
+        assert_eq!(
+            IdScheme::Words
+                .generator()
+                .generate(&empty)
+                .split('-')
+                .count(),
+            3
+        );
+    }
+}
@@ -0,0 +1,271 @@
+/*
+   TU - Time's Up!
+   Copyright (C) 2020  Julio Biason
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! The JSON layout of events, used by `tu export --format json` and the
+//! HTTP API.
+//!
+//! Events are written as
+//!
+//! ```json
+//! {
+//!   "id": "1a",
+//!   "description": "Pay taxes",
+//!   "date": "2020-06-13",
+//!   "time": null,
+//!   "tags": ["home"],
+//!   "priority": "high",
+//!   "created_at": "2020-06-10T12:00:59+02:00",
+//!   "updated_at": "2020-06-11T08:30:59+02:00",
+//!   "created_by": "julio",
+//!   "completed_at": null,
+//!   "eta": "3d",
+//!   "urgency": "soon"
+//! }
+//! ```
+//!
+//! where `time` is `HH:MM`, or null for all day events, and `priority` is
+//! `low`, `normal`, `high` or `critical`. New events use the same names for
+//! `description`, `date`, `time`, `tags` and `priority`; the other fields are
+//! computed.
+
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+
+use crate::clock::Clock;
+use crate::date::Date;
+use crate::date_errors::DateError;
+use crate::datetime::DateTime;
+use crate::event::Event;
+use crate::event::NewEvent;
+use crate::eventtype::EventType;
+use crate::priority::Priority;
+
+/// An event, as written in JSON.
+#[derive(Serialize, Debug)]
+pub struct EventJson {
+    /// Short identifier of the event.
+    pub id: String,
+    /// What the event is about.
+    pub description: String,
+    /// The day the event is due, as `YYYY-MM-DD`.
+    pub date: String,
+    /// The time the event is due, as `HH:MM`; None for all day events.
+    pub time: Option<String>,
+    /// Free form labels for the event.
+    pub tags: Vec<String>,
+    /// How important the event is.
+    pub priority: Priority,
+    /// When the event was created, in RFC 3339 format, if known.
+    pub created_at: Option<String>,
+    /// When the event was last changed, in RFC 3339 format, if known.
+    pub updated_at: Option<String>,
+    /// Who created the event, if known.
+    pub created_by: Option<String>,
+    /// When the event was done, in RFC 3339 format; None while it is not.
+    pub completed_at: Option<String>,
+    /// Time left till the event, like in the listing.
+    pub eta: String,
+    /// How close the event is: overdue, today, soon or later.
+    pub urgency: String,
+}
+
+impl EventJson {
+    /// The JSON view of the event, with the ETA as of the clock time.
+    pub fn new(event: &Event, clock: &dyn Clock) -> Self {
+        let eta = event.eta(clock);
+        Self {
+            id: event.id.clone(),
+            description: event.description.clone(),
+            date: event.due.date().to_string(),
+            time: time_of(&event.due),
+            tags: event.tags.clone(),
+            priority: event.priority,
+            created_at: event.created_at.map(|created| created.to_rfc3339()),
+            updated_at: event.updated_at.map(|updated| updated.to_rfc3339()),
+            created_by: event.created_by.clone(),
+            completed_at: event.completed_at.map(|completed| completed.to_rfc3339()),
+            eta: eta.to_string(),
+            urgency: eta.urgency().to_string(),
+        }
+    }
+}
+
+fn time_of(due: &EventType) -> Option<String> {
+    match due {
+        EventType::AllDay(_) => None,
+        EventType::AtTime(datetime) => {
+            Some(format!("{:02}:{:02}", datetime.hour(), datetime.minute()))
+        }
+    }
+}
+
+/// When the event is due, from the date and the optional time; an empty
+/// time is the same as no time.
+fn due(date: &str, time: Option<&str>) -> Result<EventType, DateError> {
+    match time.filter(|time| !time.is_empty()) {
+        None => Ok(EventType::AllDay(Date::try_from(date)?)),
+        Some(time) => Ok(EventType::AtTime(DateTime::try_from(date, time)?)),
+    }
+}
+
+/// A new event, as read from JSON.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct NewEventJson {
+    /// What the event is about.
+    pub description: String,
+    /// The day the event is due, as `YYYY-MM-DD`.
+    pub date: String,
+    /// The time the event is due, as `HH:MM`; all day events have none.
+    #[serde(default)]
+    pub time: Option<String>,
+    /// Free form labels for the event.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// How important the event is; normal if not set.
+    #[serde(default)]
+    pub priority: Priority,
+}
+
+impl NewEventJson {
+    /// Check the dates and turn it into a new event.
+    pub fn into_new_event(self) -> Result<NewEvent, DateError> {
+        Ok(NewEvent {
+            due: due(&self.date, self.time.as_deref())?,
+            description: self.description,
+            tags: self.tags,
+            priority: self.priority,
+        })
+    }
+}
+
+/// Changes to an event, as read from JSON; fields that are not set are not
+/// changed. An empty `time` turns the event into an all day one.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct EventChanges {
+    /// New description.
+    pub description: Option<String>,
+    /// New day, as `YYYY-MM-DD`.
+    pub date: Option<String>,
+    /// New time, as `HH:MM`.
+    pub time: Option<String>,
+    /// New tags, replacing the old ones.
+    pub tags: Option<Vec<String>>,
+    /// New priority.
+    pub priority: Option<Priority>,
+}
+
+impl EventChanges {
+    /// Change the event; if the dates are invalid, the event is not changed.
+    pub fn apply(self, event: &mut Event) -> Result<(), DateError> {
+        if self.date.is_some() || self.time.is_some() {
+            let date = self.date.unwrap_or_else(|| event.due.date().to_string());
+            let time = self.time.or_else(|| time_of(&event.due));
+            event.due = due(&date, time.as_deref())?;
+        }
+        if let Some(description) = self.description {
+            event.description = description;
+        }
+        if let Some(tags) = self.tags {
+            event.tags = tags;
+        }
+        if let Some(priority) = self.priority {
+            event.priority = priority;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod json_test {
+    use super::EventChanges;
+    use super::EventJson;
+    use super::NewEventJson;
+    use crate::clock::FixedClock;
+    use crate::date::Date;
+    use crate::datetime::DateTime;
+    use crate::event::Event;
+    use crate::eventtype::EventType;
+    use crate::priority::Priority;
+
+    #[test]
+    pub fn write_event() {
+        let mut event =
+            Event::new_on_date("1a", "Pay taxes", &Date::new(2020, 6, 13).unwrap()).unwrap();
+        event.tags = vec!["home".into()];
+        assert_eq!(
+            serde_json::to_string(&EventJson::new(&event, &FixedClock::at(2020, 6, 10, 12, 0)))
+                .unwrap(),
+            r#"{"id":"1a","description":"Pay taxes","date":"2020-06-13","time":null,"tags":["home"],"priority":"normal","created_at":null,"updated_at":null,"created_by":null,"completed_at":null,"eta":"3d","urgency":"soon"}"#
+        );
+    }
+
+    #[test]
+    pub fn read_new_event() {
+        let new: NewEventJson = serde_json::from_str(
+            r#"{"description": "Call", "date": "2020-06-11", "time": "14:05"}"#,
+        )
+        .unwrap();
+        let new = new.into_new_event().unwrap();
+        assert_eq!(new.description, "Call");
+        assert!(matches!(new.due, EventType::AtTime(_)));
+        assert!(new.tags.is_empty());
+        assert_eq!(new.priority, Priority::Normal);
+
+        let new: NewEventJson = serde_json::from_str(
+            r#"{"description": "Taxes", "date": "2020-06-30", "priority": "critical"}"#,
+        )
+        .unwrap();
+        assert_eq!(new.into_new_event().unwrap().priority, Priority::Critical);
+
+        let bad: NewEventJson =
+            serde_json::from_str(r#"{"description": "Call", "date": "2020-02-30"}"#).unwrap();
+        assert!(bad.into_new_event().is_err());
+        assert!(serde_json::from_str::<NewEventJson>(
+            r#"{"description": "x", "dat": "2020-06-11"}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    pub fn apply_changes() {
+        let mut event =
+            Event::new_on_date_time("1", "Call", &DateTime::new(2020, 6, 11, 14, 5).unwrap())
+                .unwrap();
+
+        let changes: EventChanges = serde_json::from_str(r#"{"date": "2020-06-12"}"#).unwrap();
+        changes.apply(&mut event).unwrap();
+        let json = EventJson::new(&event, &FixedClock::at(2020, 6, 10, 12, 0));
+        assert_eq!(
+            (json.date.as_str(), json.time.as_deref()),
+            ("2020-06-12", Some("14:05"))
+        );
+
+        let changes: EventChanges =
+            serde_json::from_str(r#"{"time": "", "description": "Meet"}"#).unwrap();
+        changes.apply(&mut event).unwrap();
+        assert!(matches!(event.due, EventType::AllDay(_)));
+        assert_eq!(event.description, "Meet");
+
+        let changes: EventChanges =
+            serde_json::from_str(r#"{"date": "nope", "description": "Lost"}"#).unwrap();
+        assert!(changes.apply(&mut event).is_err());
+        assert_eq!(event.description, "Meet");
+    }
+}
@@ -0,0 +1,78 @@
+/*
+   TU - Time's Up!
+   Copyright (C) 2020  Julio Biason
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Time's Up! event store and countdown computation.
+//!
+//! This is the library behind the `tu` command line tool. It keeps a list of
+//! events (either "all day" or at a specific time) and computes how long is
+//! left till each of them. Nothing in here prints or exits; the callers
+//! decide how to present the results.
+//!
+//! ```no_run
+//! use tu::clock::SystemClock;
+//! use tu::config::Config;
+//!
+//! let storage = Config::load().unwrap().open_storage().unwrap();
+//! let list = storage.load().unwrap();
+//! for event in &list {
+//!     println!("{}: {} ({})", event.id, event.description, event.eta(&SystemClock));
+//! }
+//! ```
+
+#![warn(missing_docs)]
+
+pub mod agenda;
+pub mod caldav;
+pub mod calendar;
+pub mod clock;
+pub mod config;
+pub mod csvfile;
+pub mod date;
+pub mod date_errors;
+pub mod datetime;
+pub mod eta;
+pub mod event;
+pub mod eventlist;
+pub mod eventtype;
+pub mod filter;
+pub mod gitsync;
+pub mod ics;
+pub mod idgen;
+pub mod json;
+pub mod merge;
+pub mod priority;
+pub mod report;
+pub mod search;
+pub mod stats;
+pub mod storage;
+pub mod template;
+
+pub use clock::Clock;
+pub use date::Date;
+pub use date_errors::DateError;
+pub use datetime::DateTime;
+pub use eta::Eta;
+pub use event::Event;
+pub use event::EventError;
+pub use event::NewEvent;
+pub use eventlist::EventList;
+pub use eventlist::EventListError;
+pub use eventtype::EventType;
+pub use idgen::IdGenerator;
+pub use priority::Priority;
+pub use storage::Storage;
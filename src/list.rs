@@ -0,0 +1,73 @@
+/*
+   TU - Time's Up!
+   Copyright (C) 2020  Julio Biason
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::error::Error;
+
+use tu::agenda;
+use tu::filter;
+use tu::Clock;
+use tu::Event;
+use tu::Storage;
+
+use crate::args::ListOptions;
+use crate::render::Renderer;
+use crate::render::Style;
+
+pub fn list(
+    storage: &dyn Storage,
+    clock: &dyn Clock,
+    renderer: &Renderer,
+    options: &ListOptions,
+) -> Result<(), Box<dyn Error>> {
+    let event_list = storage.load()?;
+    let mut events: Vec<&Event> = event_list
+        .iter()
+        .filter(|event| options.all || !event.is_done())
+        .filter(|event| filter::matches_all(&options.filters, event, clock))
+        .collect();
+    agenda::sort(&mut events, options.sort, options.reverse);
+
+    let print = |events: Vec<&Event>| -> Result<(), Box<dyn Error>> {
+        match &options.template {
+            None => renderer.print_events(events.into_iter(), clock),
+            Some(template) => {
+                for event in events {
+                    println!("{}", template.render(event, clock)?);
+                }
+            }
+        }
+        Ok(())
+    };
+
+    match options.group {
+        None => print(events)?,
+        Some(by) => {
+            for (position, group) in agenda::group(&events, by, clock, options.reverse)
+                .into_iter()
+                .enumerate()
+            {
+                if position > 0 {
+                    println!();
+                }
+                println!("{}", renderer.paint(&group.title, &[Style::Underline]));
+                print(group.events)?;
+            }
+        }
+    }
+    Ok(())
+}
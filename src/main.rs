@@ -16,75 +16,326 @@
    along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
-use log;
+use std::error::Error;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::process;
 
 mod args;
-mod date;
-mod date_errors;
-mod datetime;
-mod event;
-mod eventlist;
-mod eventtype;
+mod cal;
+mod list;
+mod next;
+mod render;
+mod serve;
+mod summary;
+mod tui;
 
-use eventlist::EventList;
-use eventtype::EventType;
+use chrono::Datelike;
+
+use tu::caldav;
+use tu::calendar::Calendar;
+use tu::clock::FixedClock;
+use tu::clock::SystemClock;
+use tu::config::Backend;
+use tu::config::Config;
+use tu::csvfile;
+use tu::gitsync;
+use tu::json::EventJson;
+use tu::report::Report;
+use tu::search;
+use tu::stats::Stats;
+use tu::storage;
+use tu::storage::TomlFile;
+use tu::Clock;
+use tu::EventList;
+use tu::Storage;
+
+use crate::render::Renderer;
+
+/// The clock of the tests, like `FixedClock::at` in the library: a
+/// Wednesday at noon.
+#[cfg(test)]
+fn test_clock() -> FixedClock {
+    FixedClock::new(&tu::DateTime::new(2020, 6, 10, 12, 0).unwrap())
+}
 
 fn main() {
     env_logger::init();
 
-    if let Ok(command) = args::parse() {
-        log::debug!("Command: {:?}", command);
-        match command {
-            args::Action::List => list(),
-            args::Action::Add(description, date) => {
-                let event_id = EventList::add_event_with_date(&description, &date).unwrap();
-                println!("Created new event {}", event_id);
+    match args::parse() {
+        Ok(arguments) => {
+            log::debug!("Arguments: {:?}", arguments);
+            if let Err(error) = run(arguments) {
+                eprintln!("Error: {}", error);
+                process::exit(1);
             }
-            args::Action::AddWithTime(description, datetime) => {
-                let event_id =
-                    EventList::add_event_with_date_and_time(&description, &datetime).unwrap();
-                println!("Created new event {}", event_id);
+        }
+        Err(error) => {
+            eprintln!("Error: {}", error);
+            process::exit(2);
+        }
+    }
+}
+
+fn run(arguments: args::Arguments) -> Result<(), Box<dyn Error>> {
+    // Git runs the merge driver on the files it is merging; a broken config or
+    // storage must not get in the way.
+    if let args::Action::Merge(base, ours, theirs) = &arguments.action {
+        return merge(base, ours, theirs);
+    }
+    let config = Config::load()?;
+    if let args::Action::Sync = arguments.action {
+        return git_sync(&config);
+    }
+    let mut storage = config.open_storage()?;
+    let mut ids = config.id_scheme.generator();
+    let clock: Box<dyn Clock> = match arguments.now {
+        Some(now) => Box::new(FixedClock::new(&now)),
+        None => Box::new(SystemClock),
+    };
+    let renderer = Renderer::new(arguments.color);
+    match arguments.action {
+        args::Action::List(options) => {
+            list::list(storage.as_ref(), clock.as_ref(), &renderer, &options)?
+        }
+        args::Action::Add(new) => {
+            let event_id = EventList::add(storage.as_mut(), ids.as_mut(), clock.as_ref(), new)?;
+            println!("Created new event {}", event_id);
+        }
+        args::Action::RemoveById(id) => {
+            let description = EventList::remove_by_id(storage.as_mut(), &id)?;
+            println!("Removed event '{}'", description);
+        }
+        args::Action::RemoveOutdated(below) => {
+            let count = EventList::remove_outdated(storage.as_mut(), clock.as_ref(), below)?;
+            println!("Removed {} events", count);
+        }
+        args::Action::Done(id) => {
+            let event = EventList::complete(storage.as_mut(), clock.as_ref(), &id)?;
+            match event.ahead_of_due() {
+                Some(ahead) => println!("Done '{}', {}", event.description, timeliness(ahead)),
+                None => println!("Done '{}'", event.description),
             }
-            args::Action::RemoveById(id) => {
-                let description = EventList::remove_by_id(&id).unwrap();
-                println!("Removed event '{}'", description);
+        }
+        args::Action::Undone(id) => {
+            let event = EventList::reopen(storage.as_mut(), clock.as_ref(), &id)?;
+            println!("'{}' is not done anymore", event.description);
+        }
+        args::Action::Migrate(backend) => migrate(&config, storage.as_ref(), backend)?,
+        args::Action::Search(query, mode) => {
+            let event_list = storage.load()?.pending();
+            let found = search::search(&event_list, &query, mode)?;
+            renderer.print_events(found.iter().map(|found| found.event), clock.as_ref());
+        }
+        args::Action::Calendar(view) => {
+            let event_list = storage.load()?.pending();
+            let calendar = match view {
+                args::CalendarView::Weeks(weeks) => {
+                    Calendar::weeks(&event_list, weeks, clock.as_ref())
+                }
+                args::CalendarView::Month(Some((year, month))) => {
+                    Calendar::month(&event_list, year, month, clock.as_ref())?
+                }
+                args::CalendarView::Month(None) => {
+                    let today = clock.today();
+                    Calendar::month(
+                        &event_list,
+                        today.year() as u16,
+                        today.month() as u8,
+                        clock.as_ref(),
+                    )?
+                }
+            };
+            cal::print_calendar(&calendar, clock.as_ref(), &renderer);
+        }
+        args::Action::Tui => tui::run(storage.as_mut(), ids.as_mut(), clock.as_ref())?,
+        args::Action::Next(mut options) => {
+            // The cache knows what is next only as of the real time.
+            if arguments.now.is_some() {
+                options.max_age = None;
             }
-            args::Action::RemoveOutdated => {
-                let count = EventList::remove_outdated().unwrap();
-                println!("Removed {} events", count);
+            let source = config.backend_path(config.storage.backend)?;
+            next::next(storage.as_ref(), &source, clock.as_ref(), &options)?
+        }
+        args::Action::Export(options) => export(storage.as_ref(), clock.as_ref(), &options)?,
+        args::Action::Import(path, options) => {
+            let new = if path == "-" {
+                csvfile::import(io::stdin().lock(), &options)?
+            } else {
+                let file =
+                    File::open(&path).map_err(|error| format!("can't open {}: {}", path, error))?;
+                csvfile::import(file, &options)?
+            };
+            let added = EventList::add_all(storage.as_mut(), ids.as_mut(), clock.as_ref(), new)?;
+            println!("Imported {} events", added.len());
+        }
+        args::Action::Report(options) => {
+            let event_list = storage.load()?.pending();
+            let report = Report::new(&event_list, &options.title, clock.as_ref());
+            let content = match options.format {
+                args::ReportFormat::Markdown => report.to_markdown(),
+                args::ReportFormat::Html => report.to_html(),
+            };
+            match &options.output {
+                Some(path) => fs::write(path, content)?,
+                None => print!("{}", content),
             }
         }
-    } else {
-        println!("Error!");
+        args::Action::Stats => {
+            let event_list = storage.load()?;
+            summary::print_stats(&Stats::new(&event_list, clock.as_ref()), &renderer);
+        }
+        args::Action::CalDav(options) => {
+            caldav_sync(&config, storage.as_mut(), clock.as_ref(), options)?
+        }
+        args::Action::Sync | args::Action::Merge(..) => {
+            unreachable!("handled before opening the storage")
+        }
+        args::Action::Serve(bind, origins) => serve::serve(
+            &bind,
+            origins,
+            storage.as_mut(),
+            ids.as_mut(),
+            clock.as_ref(),
+        )?,
     }
+    Ok(())
 }
 
-fn list() {
-    // TODO hide load from outside
-    // TODO unwrap
-    let event_list = EventList::load().unwrap();
-    println!("{:^8} | {:^7} | {}", "ID", "ETA", "Description");
-    for event in event_list.into_iter() {
-        let eta = match event.due {
-            EventType::AllDay(date) => {
-                let eta = date.eta();
-                match eta {
-                    None => "Over".into(),
-                    Some(0) => "Today".into(),
-                    Some(1) => "Tomorrow".into(),
-                    Some(x) => format!("{}d", x),
-                }
-            }
-            EventType::AtTime(datetime) => {
-                let eta = datetime.eta();
-                match eta {
-                    None => "Over".into(),
-                    Some((0, hours)) => format!("{}h", hours),
-                    Some((days, hours)) => format!("{}d {}h", days, hours),
-                }
+/// How long before the deadline something was done, in words.
+fn timeliness(ahead: chrono::Duration) -> String {
+    let late = ahead < chrono::Duration::zero();
+    let (amount, unit) = match if late { -ahead } else { ahead } {
+        length if length.num_days() > 0 => (length.num_days(), "day"),
+        length if length.num_hours() > 0 => (length.num_hours(), "hour"),
+        length if length.num_minutes() > 0 => (length.num_minutes(), "minute"),
+        _ => return "right on time".into(),
+    };
+    let plural = if amount == 1 { "" } else { "s" };
+    let side = if late {
+        "late"
+    } else {
+        "ahead of the deadline"
+    };
+    format!("{} {}{} {}", amount, unit, plural, side)
+}
+
+fn export(
+    storage: &dyn Storage,
+    clock: &dyn Clock,
+    options: &args::ExportOptions,
+) -> Result<(), Box<dyn Error>> {
+    let list = storage.load()?;
+    match options.format {
+        args::ExportFormat::Csv => match &options.output {
+            Some(path) => csvfile::export(&list, File::create(path)?, &options.formats)?,
+            None => csvfile::export(&list, io::stdout().lock(), &options.formats)?,
+        },
+        args::ExportFormat::Json => {
+            let events: Vec<EventJson> = list
+                .iter()
+                .map(|event| EventJson::new(event, clock))
+                .collect();
+            let content = serde_json::to_string_pretty(&events)? + "\n";
+            match &options.output {
+                Some(path) => fs::write(path, content)?,
+                None => print!("{}", content),
             }
-        };
+        }
+    }
+    Ok(())
+}
+
+fn caldav_sync(
+    config: &Config,
+    storage: &mut dyn Storage,
+    clock: &dyn Clock,
+    options: args::CalDavOptions,
+) -> Result<(), Box<dyn Error>> {
+    let configured = config.caldav.as_ref();
+    let url = options
+        .url
+        .or_else(|| configured.map(|caldav| caldav.url.clone()))
+        .ok_or("no CalDAV calendar; set `url` in the [caldav] section of tu.toml or use --url")?;
+    let username = options
+        .username
+        .or_else(|| configured.and_then(|caldav| caldav.username.clone()));
+    let password = options
+        .password
+        .or_else(|| configured.and_then(|caldav| caldav.password.clone()));
+    let policy = options
+        .policy
+        .or_else(|| configured.map(|caldav| caldav.policy))
+        .unwrap_or_default();
 
-        println!("{:>8} | {:>7} | {}", event.id, eta, event.description);
+    let client = caldav::Client::new(&url);
+    let client = match username {
+        Some(username) => client.with_credentials(&username, &password.unwrap_or_default()),
+        None => client,
+    };
+    let path = caldav::SyncState::default_path()?;
+    let mut state = caldav::SyncState::load(&path)?;
+    let result = caldav::sync(storage, &client, &mut state, policy, clock);
+    state.save(&path)?;
+    println!("{}", result?);
+    Ok(())
+}
+
+fn git_sync(config: &Config) -> Result<(), Box<dyn Error>> {
+    let sync = config
+        .sync
+        .as_ref()
+        .ok_or("no git remote; set `remote` in the [sync] section of tu.toml")?;
+    if config.storage.backend != Backend::Toml {
+        return Err("only events in the toml backend can be shared through git".into());
+    }
+    // The directory becomes a repository; never the whole config directory.
+    let path = config.storage.path.as_ref().ok_or(
+        "set `path` in the [storage] section of tu.toml to a file in a directory of its own",
+    )?;
+    println!("{}", gitsync::sync(path, &sync.remote, &sync.branch)?);
+    Ok(())
+}
+
+fn merge(base: &Path, ours: &Path, theirs: &Path) -> Result<(), Box<dyn Error>> {
+    let load = |path: &Path| {
+        TomlFile::new(path)
+            .load()
+            .map_err(|error| format!("can't read {}: {}", path.display(), error))
+    };
+    let merged = tu::merge::merge(&load(base)?, &load(ours)?, &load(theirs)?);
+    TomlFile::new(ours).save(&merged.list)?;
+    if merged.conflicts.is_empty() {
+        return Ok(());
+    }
+    for conflict in &merged.conflicts {
+        eprintln!("Conflict: {}", conflict);
     }
+    let count = match merged.conflicts.len() {
+        1 => "a conflict".to_string(),
+        count => format!("{} conflicts", count),
+    };
+    Err(format!("merged with {}, keeping our side", count).into())
+}
+
+fn migrate(config: &Config, from: &dyn Storage, backend: Backend) -> Result<(), Box<dyn Error>> {
+    if backend == config.storage.backend {
+        return Err(format!("events are already stored in {}", backend).into());
+    }
+
+    let path = config.backend_path(backend)?;
+    let mut to = config.open_backend(backend)?;
+    if !to.load()?.is_empty() {
+        return Err(format!("{} already has events, not overwriting it", path.display()).into());
+    }
+
+    let count = storage::migrate(from, to.as_mut())?;
+    println!("Moved {} events to {}", count, path.display());
+    println!(
+        "Set `backend = \"{}\"` in the [storage] section of tu.toml to use it",
+        backend
+    );
+    Ok(())
 }
@@ -0,0 +1,432 @@
+/*
+   TU - Time's Up!
+   Copyright (C) 2020  Julio Biason
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Three-way merge of event lists.
+//!
+//! Two copies of the events that changed apart from a common base are
+//! merged event by event, matching them by ID: events added in either side
+//! are kept, events removed in one side and left alone in the other are
+//! removed, and an event changed in one side only takes that change. Events
+//! changed in both sides are merged field by field: tags added or removed
+//! in either side are added or removed, and the description, the due date,
+//! the priority and whether the event is done take the change of the side
+//! that changed them. Only a field changed differently in both sides, an
+//! event changed in one side and removed in the other or two different
+//! events added with the same ID are conflicts.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::event::Event;
+use crate::eventlist::EventList;
+use crate::eventtype::EventType;
+
+/// A field of an event that can't be merged.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Field {
+    /// What the event is about.
+    Description,
+    /// When the event is up.
+    Due,
+    /// How important the event is.
+    Priority,
+    /// Whether and when the event was done.
+    Completion,
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Field::Description => write!(f, "description"),
+            Field::Due => write!(f, "due date"),
+            Field::Priority => write!(f, "priority"),
+            Field::Completion => write!(f, "completion"),
+        }
+    }
+}
+
+/// How both sides disagree about an event.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ConflictKind {
+    /// Both sides added an event with the same ID, but different.
+    BothAdded,
+    /// Both sides changed the field of the event, differently.
+    BothChanged(Field),
+    /// One side changed the event and the other removed it.
+    ChangedAndRemoved,
+}
+
+/// An event both sides disagree about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    /// ID of the event.
+    pub id: String,
+    /// Description of the event, as in the merged list.
+    pub description: String,
+    /// How the sides disagree.
+    pub kind: ConflictKind,
+    /// The value "theirs" has for the field, which didn't stay.
+    pub theirs: Option<String>,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({}): ", self.id, self.description)?;
+        match self.kind {
+            ConflictKind::BothAdded => write!(f, "added in both sides, differently")?,
+            ConflictKind::BothChanged(field) => {
+                write!(f, "{} changed in both sides, differently", field)?
+            }
+            ConflictKind::ChangedAndRemoved => {
+                write!(f, "changed in one side and removed in the other")?
+            }
+        }
+        match &self.theirs {
+            Some(theirs) => write!(f, "; the other side has '{}'", theirs),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The result of a merge.
+#[derive(Debug)]
+pub struct Merge {
+    /// The merged events; conflicting fields and events are as in "ours",
+    /// or as in the side that changed them if the other removed them.
+    pub list: EventList,
+    /// What both sides disagree about.
+    pub conflicts: Vec<Conflict>,
+}
+
+/// Whether the event is the same in both, including not being in either.
+fn unchanged(one: Option<&Event>, other: Option<&Event>) -> bool {
+    match (one, other) {
+        (Some(one), Some(other)) => one.same_as(other),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// Three-way merge of a value; None if both sides changed it differently.
+fn merge_value<T: PartialEq + Clone>(base: &T, ours: &T, theirs: &T) -> Option<T> {
+    if ours == theirs || theirs == base {
+        Some(ours.clone())
+    } else if ours == base {
+        Some(theirs.clone())
+    } else {
+        None
+    }
+}
+
+/// The tags of ours, plus the ones theirs added, minus the ones theirs
+/// removed.
+fn merge_tags(base: &[String], ours: &[String], theirs: &[String]) -> Vec<String> {
+    let added = theirs
+        .iter()
+        .filter(|tag| !base.contains(tag) && !ours.contains(tag));
+    ours.iter()
+        .filter(|tag| !base.contains(tag) || theirs.contains(tag))
+        .chain(added)
+        .cloned()
+        .collect()
+}
+
+/// How the due date shows in conflicts.
+fn show_due(due: &EventType) -> String {
+    match due {
+        EventType::AllDay(date) => date.to_string(),
+        EventType::AtTime(datetime) => datetime.0.format("%Y-%m-%d %H:%M").to_string(),
+    }
+}
+
+/// How the completion shows in conflicts.
+fn show_completion(event: &Event) -> String {
+    match &event.completed_at {
+        Some(completed) => format!("done on {}", completed.0.format("%Y-%m-%d %H:%M")),
+        None => "not done".into(),
+    }
+}
+
+/// Merge an event changed in both sides, field by field.
+fn merge_event(base: &Event, ours: &Event, theirs: &Event) -> (Event, Vec<Conflict>) {
+    let mut merged = ours.clone();
+    let mut conflicts = Vec::new();
+    match merge_value(&base.description, &ours.description, &theirs.description) {
+        Some(description) => merged.description = description,
+        None => conflicts.push((Field::Description, theirs.description.clone())),
+    }
+    match merge_value(&base.due, &ours.due, &theirs.due) {
+        Some(due) => merged.due = due,
+        None => conflicts.push((Field::Due, show_due(&theirs.due))),
+    }
+    match merge_value(&base.priority, &ours.priority, &theirs.priority) {
+        Some(priority) => merged.priority = priority,
+        None => conflicts.push((Field::Priority, theirs.priority.to_string())),
+    }
+    match merge_value(&base.completed_at, &ours.completed_at, &theirs.completed_at) {
+        Some(completed_at) => merged.completed_at = completed_at,
+        None => conflicts.push((Field::Completion, show_completion(theirs))),
+    }
+    merged.tags = merge_tags(&base.tags, &ours.tags, &theirs.tags);
+    // Both sides changed it; the merge is as recent as the latest of them.
+    merged.updated_at = ours
+        .updated_at
+        .into_iter()
+        .chain(theirs.updated_at)
+        .max_by_key(|updated| updated.timestamp());
+
+    let conflicts = conflicts
+        .into_iter()
+        .map(|(field, theirs)| Conflict {
+            id: merged.id.clone(),
+            description: merged.description.clone(),
+            kind: ConflictKind::BothChanged(field),
+            theirs: Some(theirs),
+        })
+        .collect();
+    (merged, conflicts)
+}
+
+/// Merge the changes in "ours" and "theirs" since "base".
+pub fn merge(base: &EventList, ours: &EventList, theirs: &EventList) -> Merge {
+    let ids: BTreeSet<&str> = base
+        .iter()
+        .chain(ours.iter())
+        .chain(theirs.iter())
+        .map(|event| event.id.as_str())
+        .collect();
+
+    let mut list = EventList::empty();
+    let mut conflicts = Vec::new();
+    for id in ids {
+        let (base, ours, theirs) = (base.get(id), ours.get(id), theirs.get(id));
+        // Both did the same, or only ours changed it.
+        let (merged, conflict) = if unchanged(ours, theirs) || unchanged(base, theirs) {
+            (ours.cloned(), None)
+        } else if unchanged(base, ours) {
+            (theirs.cloned(), None)
+        } else {
+            match (base, ours, theirs) {
+                (Some(base), Some(ours), Some(theirs)) => {
+                    let (merged, field_conflicts) = merge_event(base, ours, theirs);
+                    conflicts.extend(field_conflicts);
+                    (Some(merged), None)
+                }
+                (None, _, _) => (ours.cloned(), Some(ConflictKind::BothAdded)),
+                (Some(_), _, _) => (
+                    ours.or(theirs).cloned(),
+                    Some(ConflictKind::ChangedAndRemoved),
+                ),
+            }
+        };
+        if let Some(mut event) = merged {
+            if let Some(kind) = conflict {
+                conflicts.push(Conflict {
+                    id: id.into(),
+                    description: event.description.clone(),
+                    kind,
+                    theirs: None,
+                });
+            }
+            // Keep the creation time and creator whichever side knows them.
+            event.created_at = event
+                .created_at
+                .or_else(|| ours.and_then(|ours| ours.created_at))
+                .or_else(|| theirs.and_then(|theirs| theirs.created_at));
+            event.created_by = event
+                .created_by
+                .or_else(|| ours.and_then(|ours| ours.created_by.clone()))
+                .or_else(|| theirs.and_then(|theirs| theirs.created_by.clone()));
+            list.push(event);
+        }
+    }
+    Merge { list, conflicts }
+}
+
+#[cfg(test)]
+mod merge_test {
+    use super::merge;
+    use super::ConflictKind;
+    use super::Field;
+    use crate::date::Date;
+    use crate::datetime::DateTime;
+    use crate::event::Event;
+    use crate::eventlist::EventList;
+    use crate::eventtype::EventType;
+    use crate::priority::Priority;
+
+    fn list(events: &[Event]) -> EventList {
+        let mut list = EventList::empty();
+        for event in events {
+            list.push(event.clone());
+        }
+        list
+    }
+
+    fn descriptions(list: &EventList) -> Vec<String> {
+        let mut descriptions: Vec<String> = list
+            .iter()
+            .map(|event| format!("{}={}", event.id, event.description))
+            .collect();
+        descriptions.sort();
+        descriptions
+    }
+
+    #[test]
+    pub fn adds_and_removes() {
+        let base = list(&[
+            Event::on_day("a", "A", 2030, 1, 1),
+            Event::on_day("b", "B", 2030, 1, 1),
+            Event::on_day("c", "C", 2030, 1, 1),
+        ]);
+        let ours = list(&[
+            Event::on_day("a", "A", 2030, 1, 1),
+            Event::on_day("c", "C", 2030, 1, 1),
+            Event::on_day("d", "D", 2030, 1, 1),
+        ]);
+        let theirs = list(&[
+            Event::on_day("a", "A", 2030, 1, 1),
+            Event::on_day("b", "B", 2030, 1, 1),
+            Event::on_day("e", "E", 2030, 1, 1),
+        ]);
+
+        let merged = merge(&base, &ours, &theirs);
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(descriptions(&merged.list), vec!["a=A", "d=D", "e=E"]);
+    }
+
+    #[test]
+    pub fn changes_in_one_side() {
+        let base = list(&[
+            Event::on_day("a", "A", 2030, 1, 1),
+            Event::on_day("b", "B", 2030, 1, 1),
+        ]);
+        let ours = list(&[
+            Event::on_day("a", "A2", 2030, 1, 1),
+            Event::on_day("b", "B", 2030, 1, 1),
+        ]);
+        let theirs = list(&[
+            Event::on_day("a", "A", 2030, 1, 1),
+            Event::on_day("b", "B2", 2030, 1, 1),
+        ]);
+
+        let merged = merge(&base, &ours, &theirs);
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(descriptions(&merged.list), vec!["a=A2", "b=B2"]);
+    }
+
+    #[test]
+    pub fn same_changes_are_no_conflict() {
+        let base = list(&[Event::on_day("a", "A", 2030, 1, 1)]);
+        let both = list(&[
+            Event::on_day("a", "A2", 2030, 1, 1),
+            Event::on_day("n", "N", 2030, 1, 1),
+        ]);
+
+        let merged = merge(&base, &both, &both);
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(descriptions(&merged.list), vec!["a=A2", "n=N"]);
+    }
+
+    #[test]
+    pub fn conflicts() {
+        let base = list(&[
+            Event::on_day("a", "A", 2030, 1, 1),
+            Event::on_day("b", "B", 2030, 1, 1),
+        ]);
+        let ours = list(&[
+            Event::on_day("a", "Ours", 2030, 1, 1),
+            Event::on_day("n", "New ours", 2030, 1, 1),
+        ]);
+        let theirs = list(&[
+            Event::on_day("a", "Theirs", 2030, 1, 1),
+            Event::on_day("b", "B2", 2030, 1, 1),
+            Event::on_day("n", "New theirs", 2030, 1, 1),
+        ]);
+
+        let merged = merge(&base, &ours, &theirs);
+        assert_eq!(
+            descriptions(&merged.list),
+            vec!["a=Ours", "b=B2", "n=New ours"]
+        );
+        let kinds: Vec<(&str, ConflictKind)> = merged
+            .conflicts
+            .iter()
+            .map(|conflict| (conflict.id.as_str(), conflict.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("a", ConflictKind::BothChanged(Field::Description)),
+                ("b", ConflictKind::ChangedAndRemoved),
+                ("n", ConflictKind::BothAdded),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn fields() {
+        let mut original = Event::on_day("a", "Launch", 2030, 1, 1);
+        original.tags = vec!["work".into(), "q3".into()];
+        let base = list(&[original.clone()]);
+
+        let mut ours = original.clone();
+        ours.description = "Launch v2".into();
+        ours.tags = vec!["work".into(), "q3".into(), "urgent".into()];
+        ours.updated_at = Some(DateTime::new(2020, 6, 1, 10, 0).unwrap());
+        ours.priority = Priority::High;
+        let mut theirs = original.clone();
+        theirs.due = EventType::AtTime(DateTime::new(2030, 1, 2, 10, 0).unwrap());
+        theirs.tags = vec!["work".into(), "public".into()];
+        theirs.completed_at = Some(DateTime::new(2020, 6, 2, 10, 0).unwrap());
+        theirs.updated_at = Some(DateTime::new(2020, 6, 2, 10, 0).unwrap());
+
+        let merged = merge(&base, &list(&[ours]), &list(&[theirs.clone()]));
+        assert!(merged.conflicts.is_empty());
+        let event = merged.list.get("a").unwrap();
+        assert_eq!(event.description, "Launch v2");
+        assert_eq!(event.due, theirs.due);
+        assert_eq!(event.tags, vec!["work", "urgent", "public"]);
+        assert_eq!(event.updated_at, theirs.updated_at);
+        assert_eq!(event.priority, Priority::High);
+        assert_eq!(event.completed_at, theirs.completed_at);
+    }
+
+    #[test]
+    pub fn field_conflicts() {
+        let original = Event::on_day("a", "Launch", 2030, 1, 1);
+        let mut ours = original.clone();
+        ours.description = "Launch on Monday".into();
+        ours.tags = vec!["ours".into()];
+        let mut theirs = original.clone();
+        theirs.description = "Launch on Friday".into();
+        theirs.due = EventType::AllDay(Date::new(2030, 1, 5).unwrap());
+
+        let merged = merge(&list(&[original]), &list(&[ours]), &list(&[theirs]));
+        let event = merged.list.get("a").unwrap();
+        assert_eq!(event.description, "Launch on Monday");
+        assert_eq!(event.due, EventType::AllDay(Date::new(2030, 1, 5).unwrap()));
+        assert_eq!(event.tags, vec!["ours"]);
+        assert_eq!(merged.conflicts.len(), 1);
+        assert_eq!(
+            merged.conflicts[0].to_string(),
+            "a (Launch on Monday): description changed in both sides, differently; \
+             the other side has 'Launch on Friday'"
+        );
+    }
+}
@@ -0,0 +1,338 @@
+/*
+   TU - Time's Up!
+   Copyright (C) 2020  Julio Biason
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! The nearest upcoming event, for shell prompts and status bars.
+//!
+//! Those run `tu next` very often, so the result can be cached: the event
+//! is kept in the user cache directory, with the path of the storage it came
+//! from, and reused while it is young enough and that same storage didn't
+//! change since.
+
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+use serde_json::json;
+use serde_json::Value;
+
+use tu::eta::Urgency;
+use tu::template::TemplateError;
+use tu::Clock;
+use tu::Event;
+use tu::EventList;
+use tu::EventListError;
+use tu::Storage;
+
+use crate::args::NextOptions;
+use crate::args::NextOutput;
+
+static CACHE_FILE: &str = "next.toml";
+
+/// The cached event and where the events were read from.
+#[derive(Serialize, Deserialize)]
+struct Cache {
+    storage: PathBuf,
+    events: EventList,
+}
+
+/// Print the nearest upcoming event of the storage, kept at `source`.
+pub fn next(
+    storage: &dyn Storage,
+    source: &Path,
+    clock: &dyn Clock,
+    options: &NextOptions,
+) -> Result<(), Box<dyn Error>> {
+    let event = match (options.max_age, cache_path()) {
+        (Some(max_age), Some(path)) => cached_upcoming(storage, source, clock, &path, max_age)?,
+        _ => upcoming(&storage.load()?, clock),
+    };
+    if let Some(line) = show(event.as_ref(), clock, options)? {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+fn cache_path() -> Option<PathBuf> {
+    let mut path = dirs::cache_dir()?;
+    path.push("tu");
+    path.push(CACHE_FILE);
+    Some(path)
+}
+
+/// The first event that is not over nor done.
+fn upcoming(list: &EventList, clock: &dyn Clock) -> Option<Event> {
+    list.iter()
+        .find(|event| !event.eta(clock).is_over() && !event.is_done())
+        .cloned()
+}
+
+fn cached_upcoming(
+    storage: &dyn Storage,
+    source: &Path,
+    clock: &dyn Clock,
+    path: &Path,
+    max_age: Duration,
+) -> Result<Option<Event>, EventListError> {
+    if let Some(event) = read_cache(storage, source, clock, path, max_age) {
+        return Ok(event);
+    }
+    let event = upcoming(&storage.load()?, clock);
+    write_cache(path, source, event.as_ref());
+    Ok(event)
+}
+
+/// The event in the cache, if the cache can still be used; a cache can also
+/// say there are no upcoming events.
+fn read_cache(
+    storage: &dyn Storage,
+    source: &Path,
+    clock: &dyn Clock,
+    path: &Path,
+    max_age: Duration,
+) -> Option<Option<Event>> {
+    let cached_at = fs::metadata(path).ok()?.modified().ok()?;
+    let age = SystemTime::now().duration_since(cached_at).ok()?;
+    if age >= max_age {
+        return None;
+    }
+    if storage
+        .modified()
+        .is_some_and(|changed| changed > cached_at)
+    {
+        return None;
+    }
+
+    let cache: Cache = toml::from_str(&fs::read_to_string(path).ok()?).ok()?;
+    // Events of another storage, from before changing the configuration.
+    if cache.storage != source {
+        return None;
+    }
+    match cache.events.iter().next() {
+        // Once the cached event is over, only the storage knows what is next.
+        Some(event) if event.eta(clock).is_over() => None,
+        event => Some(event.cloned()),
+    }
+}
+
+/// Failing to write the cache only means the next run loads the events again.
+fn write_cache(path: &Path, source: &Path, event: Option<&Event>) {
+    let cache = Cache {
+        storage: source.into(),
+        events: event.cloned().into_iter().collect(),
+    };
+    if let Err(error) = try_write_cache(path, &cache) {
+        log::debug!("Can't write the cache at {}: {}", path.display(), error);
+    }
+}
+
+fn try_write_cache(path: &Path, cache: &Cache) -> Result<(), Box<dyn Error>> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, toml::to_string(cache)?)?;
+    Ok(())
+}
+
+fn urgency_color(urgency: Urgency) -> Option<&'static str> {
+    match urgency {
+        Urgency::Overdue => Some("#ff0000"),
+        Urgency::Today => Some("#ffff00"),
+        Urgency::Soon => Some("#00ffff"),
+        Urgency::Later => None,
+    }
+}
+
+/// The line to print; plain output prints nothing when there are no
+/// upcoming events, but the bars always get a block.
+fn show(
+    event: Option<&Event>,
+    clock: &dyn Clock,
+    options: &NextOptions,
+) -> Result<Option<String>, TemplateError> {
+    let text = match event {
+        Some(event) => options.template.render(event, clock)?,
+        None => String::new(),
+    };
+    let urgency = event.map(|event| event.eta(clock).urgency());
+
+    Ok(match options.output {
+        NextOutput::Plain => event.map(|_| text),
+        NextOutput::Waybar => {
+            let mut block = json!({ "text": text });
+            if let (Some(event), Some(urgency)) = (event, urgency) {
+                block["tooltip"] = json!(format!("{}: {}", event.due.date(), event.description));
+                block["class"] = json!(urgency.to_string());
+            }
+            Some(block.to_string())
+        }
+        NextOutput::I3bar => {
+            let mut block = json!({ "name": "tu", "full_text": text });
+            if let Some(color) = urgency.and_then(urgency_color) {
+                block["color"] = Value::from(color);
+            }
+            Some(block.to_string())
+        }
+    })
+}
+
+#[cfg(test)]
+mod next_test {
+    use std::path::Path;
+    use std::time::Duration;
+
+    use tu::clock::FixedClock;
+    use tu::idgen::Sequential;
+    use tu::storage::Memory;
+    use tu::template::Template;
+    use tu::Date;
+    use tu::DateTime;
+    use tu::EventList;
+    use tu::NewEvent;
+
+    use super::cached_upcoming;
+    use super::show;
+    use crate::args::NextOptions;
+    use crate::args::NextOutput;
+    use crate::test_clock;
+
+    fn add(storage: &mut Memory, description: &str, day: u8) {
+        EventList::add(
+            storage,
+            &mut Sequential,
+            &test_clock(),
+            NewEvent::on_date(description, &Date::new(2020, 6, day).unwrap()),
+        )
+        .unwrap();
+    }
+
+    fn source() -> &'static Path {
+        Path::new("/tmp/events.toml")
+    }
+
+    fn options(output: NextOutput) -> NextOptions {
+        NextOptions {
+            template: Template::parse("{description} in {eta}").unwrap(),
+            max_age: None,
+            output,
+        }
+    }
+
+    #[test]
+    pub fn skips_over_events() {
+        let mut storage = Memory::new();
+        add(&mut storage, "past", 5);
+        add(&mut storage, "future", 12);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("next.toml");
+
+        let event =
+            cached_upcoming(&storage, source(), &test_clock(), &path, Duration::ZERO).unwrap();
+        assert_eq!(event.unwrap().description, "future");
+    }
+
+    #[test]
+    pub fn fresh_cache_is_reused() {
+        let mut storage = Memory::new();
+        add(&mut storage, "later", 15);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache").join("next.toml");
+        let max_age = Duration::from_secs(60);
+        cached_upcoming(&storage, source(), &test_clock(), &path, max_age).unwrap();
+
+        add(&mut storage, "sooner", 12);
+        let event = cached_upcoming(&storage, source(), &test_clock(), &path, max_age).unwrap();
+        assert_eq!(event.unwrap().description, "later");
+
+        let event =
+            cached_upcoming(&storage, source(), &test_clock(), &path, Duration::ZERO).unwrap();
+        assert_eq!(event.unwrap().description, "sooner");
+    }
+
+    #[test]
+    pub fn cache_of_another_storage_is_dropped() {
+        let mut storage = Memory::new();
+        add(&mut storage, "old", 15);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("next.toml");
+        let max_age = Duration::from_secs(60);
+        cached_upcoming(&storage, source(), &test_clock(), &path, max_age).unwrap();
+
+        let mut other = Memory::new();
+        add(&mut other, "new", 12);
+        let other_source = Path::new("/tmp/events.sqlite");
+        let event = cached_upcoming(&other, other_source, &test_clock(), &path, max_age).unwrap();
+        assert_eq!(event.unwrap().description, "new");
+    }
+
+    #[test]
+    pub fn over_cached_event_is_dropped() {
+        let mut storage = Memory::new();
+        add(&mut storage, "first", 11);
+        add(&mut storage, "second", 12);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("next.toml");
+        let max_age = Duration::from_secs(60);
+        cached_upcoming(&storage, source(), &test_clock(), &path, max_age).unwrap();
+
+        let later = FixedClock::new(&DateTime::new(2020, 6, 12, 8, 0).unwrap());
+        let event = cached_upcoming(&storage, source(), &later, &path, max_age).unwrap();
+        assert_eq!(event.unwrap().description, "second");
+    }
+
+    #[test]
+    pub fn outputs() {
+        let mut storage = Memory::new();
+        add(&mut storage, "taxes", 10);
+        let list = tu::Storage::load(&storage).unwrap();
+        let event = list.iter().next();
+
+        assert_eq!(
+            show(event, &test_clock(), &options(NextOutput::Plain))
+                .unwrap()
+                .unwrap(),
+            "taxes in Today"
+        );
+        assert_eq!(
+            show(event, &test_clock(), &options(NextOutput::Waybar))
+                .unwrap()
+                .unwrap(),
+            r#"{"class":"today","text":"taxes in Today","tooltip":"2020-06-10: taxes"}"#
+        );
+        assert_eq!(
+            show(event, &test_clock(), &options(NextOutput::I3bar))
+                .unwrap()
+                .unwrap(),
+            r##"{"color":"#ffff00","full_text":"taxes in Today","name":"tu"}"##
+        );
+
+        assert!(show(None, &test_clock(), &options(NextOutput::Plain))
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            show(None, &test_clock(), &options(NextOutput::Waybar))
+                .unwrap()
+                .unwrap(),
+            r#"{"text":""}"#
+        );
+    }
+}
@@ -0,0 +1,97 @@
+/*
+   TU - Time's Up!
+   Copyright (C) 2020  Julio Biason
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! How important an event is.
+
+use std::fmt;
+
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+
+/// How important an event is; more important events compare greater.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    /// Nice to know about.
+    Low,
+    /// Most events.
+    #[default]
+    Normal,
+    /// Worth standing out in the listings.
+    High,
+    /// Must not be missed.
+    Critical,
+}
+
+impl Priority {
+    /// All priorities, from the least to the most important.
+    pub const ALL: [Priority; 4] = [
+        Priority::Low,
+        Priority::Normal,
+        Priority::High,
+        Priority::Critical,
+    ];
+
+    /// The priority with the name, ignoring case.
+    pub fn from_name(name: &str) -> Option<Priority> {
+        Priority::ALL
+            .iter()
+            .copied()
+            .find(|priority| priority.to_string().eq_ignore_ascii_case(name.trim()))
+    }
+
+    /// True for the priorities that should stand out.
+    pub fn is_high(&self) -> bool {
+        *self >= Priority::High
+    }
+}
+
+/// Lowercase names, as used in the command line and the files.
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Priority::Low => f.pad("low"),
+            Priority::Normal => f.pad("normal"),
+            Priority::High => f.pad("high"),
+            Priority::Critical => f.pad("critical"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod priority_test {
+    use super::Priority;
+
+    #[test]
+    pub fn order() {
+        assert!(Priority::Low < Priority::Normal);
+        assert!(Priority::High < Priority::Critical);
+        assert_eq!(Priority::default(), Priority::Normal);
+        assert!(!Priority::Normal.is_high());
+        assert!(Priority::High.is_high());
+    }
+
+    #[test]
+    pub fn names() {
+        for priority in Priority::ALL.iter() {
+            assert_eq!(Priority::from_name(&priority.to_string()), Some(*priority));
+        }
+        assert_eq!(Priority::from_name("HIGH"), Some(Priority::High));
+        assert_eq!(Priority::from_name("urgent"), None);
+    }
+}
@@ -0,0 +1,256 @@
+/*
+   TU - Time's Up!
+   Copyright (C) 2020  Julio Biason
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Terminal output: colors and fitting tables in the terminal width.
+
+use std::env;
+use std::io::IsTerminal;
+
+use terminal_size::terminal_size;
+use terminal_size::Width;
+use unicode_width::UnicodeWidthChar;
+use unicode_width::UnicodeWidthStr;
+
+use tu::eta::Urgency;
+use tu::Clock;
+use tu::Event;
+use tu::Priority;
+
+/// Descriptions are never wrapped narrower than this.
+const MIN_DESCRIPTION_WIDTH: usize = 10;
+
+/// When to use colors.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ColorChoice {
+    Always,
+    Never,
+    /// Only when the output is a terminal and NO_COLOR is not set.
+    Auto,
+}
+
+/// Text styles, as ANSI escape codes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Style {
+    Red,
+    Yellow,
+    Cyan,
+    Bold,
+    Reverse,
+    Underline,
+}
+
+impl Style {
+    fn code(&self) -> &'static str {
+        match self {
+            Style::Red => "\x1b[31m",
+            Style::Yellow => "\x1b[33m",
+            Style::Cyan => "\x1b[36m",
+            Style::Bold => "\x1b[1m",
+            Style::Reverse => "\x1b[7m",
+            Style::Underline => "\x1b[4m",
+        }
+    }
+
+    /// The color for events of that urgency, if any.
+    pub fn for_urgency(urgency: Urgency) -> Option<Style> {
+        match urgency {
+            Urgency::Overdue => Some(Style::Red),
+            Urgency::Today => Some(Style::Yellow),
+            Urgency::Soon => Some(Style::Cyan),
+            Urgency::Later => None,
+        }
+    }
+}
+
+/// Marks the important events, so they stand out even without colors.
+fn priority_marker(priority: Priority) -> &'static str {
+    match priority {
+        Priority::Critical => "!! ",
+        Priority::High => "! ",
+        Priority::Normal | Priority::Low => "",
+    }
+}
+
+static RESET: &str = "\x1b[0m";
+
+/// Decides how things look in the output.
+#[derive(Debug)]
+pub struct Renderer {
+    color: bool,
+    /// Width of the terminal; None if not writing to one.
+    width: Option<usize>,
+}
+
+impl Renderer {
+    /// A renderer for the standard output.
+    pub fn new(choice: ColorChoice) -> Self {
+        let terminal = std::io::stdout().is_terminal();
+        let color = match choice {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                terminal && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+            }
+        };
+        let width = if terminal {
+            terminal_size().map(|(Width(width), _)| width as usize)
+        } else {
+            None
+        };
+        Self { color, width }
+    }
+
+    /// Apply the styles to the text, if colors are enabled.
+    pub fn paint(&self, text: &str, styles: &[Style]) -> String {
+        if !self.color || styles.is_empty() {
+            return text.into();
+        }
+        let codes: String = styles.iter().map(Style::code).collect();
+        format!("{}{}{}", codes, text, RESET)
+    }
+
+    /// Print the events in a table, with their IDs and ETAs, colored by
+    /// urgency, the important ones marked and in bold, and with the
+    /// descriptions wrapped to fit the terminal. Done events have no ETA
+    /// nor color.
+    pub fn print_events<'a>(&self, events: impl Iterator<Item = &'a Event>, clock: &dyn Clock) {
+        let rows: Vec<(&Event, String)> = events
+            .map(|event| match event.is_done() {
+                true => (event, "Done".to_string()),
+                false => (event, event.eta(clock).to_string()),
+            })
+            .collect();
+        let id_width = rows
+            .iter()
+            .map(|(event, _)| event.id.width())
+            .fold(8, usize::max);
+        let eta_width = rows.iter().map(|(_, eta)| eta.width()).fold(7, usize::max);
+        let description_width = self
+            .width
+            .map(|width| width.saturating_sub(id_width + eta_width + 6))
+            .map(|width| width.max(MIN_DESCRIPTION_WIDTH));
+
+        println!(
+            "{:^id_width$} | {:^eta_width$} | Description",
+            "ID",
+            "ETA",
+            id_width = id_width,
+            eta_width = eta_width
+        );
+        for (event, eta) in rows {
+            let description = format!("{}{}", priority_marker(event.priority), event.description);
+            let lines = match description_width {
+                Some(width) => wrap(&description, width),
+                None => vec![description],
+            };
+            let mut styles: Vec<Style> = Style::for_urgency(event.eta(clock).urgency())
+                .into_iter()
+                .filter(|_| !event.is_done())
+                .collect();
+            if event.priority.is_high() {
+                styles.push(Style::Bold);
+            }
+            for (number, line) in lines.iter().enumerate() {
+                let (id, eta) = if number == 0 {
+                    (event.id.as_str(), eta.as_str())
+                } else {
+                    ("", "")
+                };
+                let row = format!(
+                    "{} | {} | {}",
+                    pad_left(id, id_width),
+                    pad_left(eta, eta_width),
+                    line
+                );
+                println!("{}", self.paint(&row, &styles));
+            }
+        }
+    }
+}
+
+/// Right-align the text in the width, counting display columns.
+pub fn pad_left(text: &str, width: usize) -> String {
+    format!("{}{}", " ".repeat(width.saturating_sub(text.width())), text)
+}
+
+/// Break the text in lines of up to `width` columns, breaking at spaces when
+/// possible and splitting words that don't fit in a line by themselves.
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+
+    for word in text.split_whitespace() {
+        let separator = if line.is_empty() { 0 } else { 1 };
+        if line.width() + separator + word.width() <= width {
+            if separator == 1 {
+                line.push(' ');
+            }
+            line.push_str(word);
+            continue;
+        }
+
+        if !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
+        }
+        for c in word.chars() {
+            if line.width() + c.width().unwrap_or(0) > width {
+                lines.push(std::mem::take(&mut line));
+            }
+            line.push(c);
+        }
+    }
+
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod render_test {
+    use super::pad_left;
+    use super::wrap;
+
+    #[test]
+    pub fn short_text() {
+        assert_eq!(wrap("Dentist", 10), vec!["Dentist"]);
+        assert_eq!(wrap("", 10), vec![""]);
+    }
+
+    #[test]
+    pub fn wrap_at_spaces() {
+        assert_eq!(
+            wrap("Renew the visa at the consulate", 10),
+            vec!["Renew the", "visa at", "the", "consulate"]
+        );
+    }
+
+    #[test]
+    pub fn split_long_words() {
+        assert_eq!(
+            wrap("see supercalifragilistic", 10),
+            vec!["see", "supercalif", "ragilistic"]
+        );
+    }
+
+    #[test]
+    pub fn wide_characters() {
+        assert_eq!(wrap("日本語の予定", 6), vec!["日本語", "の予定"]);
+        assert_eq!(pad_left("日本", 6), "  日本");
+    }
+}
@@ -0,0 +1,307 @@
+/*
+   TU - Time's Up!
+   Copyright (C) 2020  Julio Biason
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Reports of the events, for status documents.
+//!
+//! A report lists the events that are over in their own section, then the
+//! upcoming ones grouped by week, each with a badge showing how long is left.
+//! It can be written as Markdown or as a standalone HTML page.
+
+use crate::agenda;
+use crate::agenda::GroupBy;
+use crate::clock::Clock;
+use crate::event::Event;
+use crate::eventlist::EventList;
+use crate::eventtype::EventType;
+
+/// Default title of the reports.
+pub static DEFAULT_TITLE: &str = "Countdowns";
+
+static STYLE: &str = "
+body { font-family: system-ui, sans-serif; max-width: 50em; margin: 2em auto; padding: 0 1em; color: #222; }
+h1 { margin-bottom: 0; }
+.generated { color: #666; margin-top: .2em; }
+table { border-collapse: collapse; width: 100%; }
+td { padding: .3em .6em; border-bottom: 1px solid #ddd; vertical-align: top; }
+td.due { white-space: nowrap; width: 10em; }
+td.eta { width: 6em; }
+.badge { display: inline-block; min-width: 4em; padding: .1em .5em; border-radius: 1em; text-align: center; font-size: .85em; font-weight: bold; background: #e0e0e0; }
+.badge.overdue { background: #d32f2f; color: #fff; }
+.badge.today { background: #f9a825; }
+.badge.soon { background: #81d4fa; }
+.tag { color: #555; font-size: .85em; margin-left: .5em; }
+section.overdue h2 { color: #d32f2f; }
+";
+
+/// A section of the report.
+#[derive(Debug)]
+pub struct Section<'a> {
+    /// Title of the section, like "Overdue" or "Next week".
+    pub title: String,
+    /// The events in the section, in due order.
+    pub events: Vec<&'a Event>,
+}
+
+/// The events split in the report sections.
+pub struct Report<'a> {
+    /// Title of the report.
+    pub title: String,
+    /// Events that are already over.
+    pub overdue: Section<'a>,
+    /// Upcoming events, one section per week.
+    pub weeks: Vec<Section<'a>>,
+    clock: &'a dyn Clock,
+}
+
+/// When the event is due, with the day of the week.
+fn due(event: &Event) -> String {
+    match &event.due {
+        EventType::AllDay(date) => date.0.format("%a %Y-%m-%d").to_string(),
+        EventType::AtTime(datetime) => datetime.0.format("%a %Y-%m-%d %H:%M").to_string(),
+    }
+}
+
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\`*_[]<>#|".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl<'a> Report<'a> {
+    /// Split the events of the list in sections, as of the clock time.
+    pub fn new(list: &'a EventList, title: &str, clock: &'a dyn Clock) -> Self {
+        let (overdue, upcoming): (Vec<&Event>, Vec<&Event>) =
+            list.iter().partition(|event| event.eta(clock).is_over());
+        let weeks = agenda::group(&upcoming, GroupBy::Week, clock, false)
+            .into_iter()
+            .map(|group| Section {
+                title: group.title,
+                events: group.events,
+            })
+            .collect();
+        Self {
+            title: title.into(),
+            overdue: Section {
+                title: "Overdue".into(),
+                events: overdue,
+            },
+            weeks,
+            clock,
+        }
+    }
+
+    /// The sections with events, the overdue one first.
+    fn sections(&self) -> impl Iterator<Item = &Section<'a>> {
+        std::iter::once(&self.overdue)
+            .chain(self.weeks.iter())
+            .filter(|section| !section.events.is_empty())
+    }
+
+    fn generated(&self) -> String {
+        self.clock.now().format("%Y-%m-%d %H:%M").to_string()
+    }
+
+    /// The report as Markdown, with the ETA badges as code spans.
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!(
+            "# {}\n\nGenerated on {}.\n",
+            escape_markdown(&self.title),
+            self.generated()
+        );
+        let mut empty = true;
+        for section in self.sections() {
+            empty = false;
+            out.push_str(&format!("\n## {}\n\n", escape_markdown(&section.title)));
+            for event in &section.events {
+                out.push_str(&format!(
+                    "- `{}` **{}**, {}",
+                    event.eta(self.clock),
+                    escape_markdown(&event.description),
+                    due(event)
+                ));
+                for tag in &event.tags {
+                    out.push_str(&format!(" \\#{}", escape_markdown(tag)));
+                }
+                out.push('\n');
+            }
+        }
+        if empty {
+            out.push_str("\nNo events.\n");
+        }
+        out
+    }
+
+    /// The report as a standalone HTML page, styles included.
+    pub fn to_html(&self) -> String {
+        let title = escape_html(&self.title);
+        let mut out = format!(
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n\
+             <meta charset=\"utf-8\">\n\
+             <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+             <title>{}</title>\n<style>{}</style>\n</head>\n<body>\n\
+             <h1>{}</h1>\n<p class=\"generated\">Generated on {}.</p>\n",
+            title,
+            STYLE,
+            title,
+            self.generated()
+        );
+        let mut empty = true;
+        for (position, section) in self.sections().enumerate() {
+            empty = false;
+            let class = if position == 0 && !self.overdue.events.is_empty() {
+                " class=\"overdue\""
+            } else {
+                ""
+            };
+            out.push_str(&format!(
+                "<section{}>\n<h2>{}</h2>\n<table>\n",
+                class,
+                escape_html(&section.title)
+            ));
+            for event in &section.events {
+                let eta = event.eta(self.clock);
+                let tags: String = event
+                    .tags
+                    .iter()
+                    .map(|tag| format!("<span class=\"tag\">#{}</span>", escape_html(tag)))
+                    .collect();
+                out.push_str(&format!(
+                    "<tr><td class=\"due\">{}</td>\
+                     <td class=\"eta\"><span class=\"badge {}\">{}</span></td>\
+                     <td>{}{}</td></tr>\n",
+                    due(event),
+                    eta.urgency(),
+                    eta,
+                    escape_html(&event.description),
+                    tags
+                ));
+            }
+            out.push_str("</table>\n</section>\n");
+        }
+        if empty {
+            out.push_str("<p>No events.</p>\n");
+        }
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+}
+
+#[cfg(test)]
+mod report_test {
+    use super::Report;
+    use crate::clock::FixedClock;
+    use crate::date::Date;
+    use crate::datetime::DateTime;
+    use crate::event::Event;
+    use crate::eventlist::EventList;
+
+    fn list() -> EventList {
+        let mut taxes =
+            Event::new_on_date("1", "Pay *taxes*", &Date::new(2020, 6, 12).unwrap()).unwrap();
+        taxes.tags = vec!["home".into()];
+        vec![
+            Event::new_on_date("2", "Late <thing>", &Date::new(2020, 6, 1).unwrap()).unwrap(),
+            taxes,
+            Event::new_on_date_time("3", "Launch", &DateTime::new(2020, 6, 17, 9, 30).unwrap())
+                .unwrap(),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    #[test]
+    pub fn sections() {
+        let list = list();
+        let clock = FixedClock::at(2020, 6, 10, 12, 0);
+        let report = Report::new(&list, "Status", &clock);
+        assert_eq!(report.overdue.events[0].id, "2");
+        let titles: Vec<&str> = report
+            .weeks
+            .iter()
+            .map(|week| week.title.as_str())
+            .collect();
+        assert_eq!(titles, vec!["This week", "Next week"]);
+    }
+
+    #[test]
+    pub fn markdown() {
+        let list = list();
+        let clock = FixedClock::at(2020, 6, 10, 12, 0);
+        assert_eq!(
+            Report::new(&list, "Status", &clock).to_markdown(),
+            "# Status\n\
+             \n\
+             Generated on 2020-06-10 12:00.\n\
+             \n\
+             ## Overdue\n\
+             \n\
+             - `Over` **Late \\<thing\\>**, Mon 2020-06-01\n\
+             \n\
+             ## This week\n\
+             \n\
+             - `2d` **Pay \\*taxes\\***, Fri 2020-06-12 \\#home\n\
+             \n\
+             ## Next week\n\
+             \n\
+             - `6d 21h` **Launch**, Wed 2020-06-17 09:30\n"
+        );
+    }
+
+    #[test]
+    pub fn html() {
+        let list = list();
+        let clock = FixedClock::at(2020, 6, 10, 12, 0);
+        let html = Report::new(&list, "Status & plans", &clock).to_html();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<title>Status &amp; plans</title>"));
+        assert!(html.contains("<section class=\"overdue\">\n<h2>Overdue</h2>"));
+        assert!(html
+            .contains("<span class=\"badge overdue\">Over</span></td><td>Late &lt;thing&gt;</td>"));
+        assert!(html.contains("<span class=\"badge soon\">2d</span></td><td>Pay *taxes*<span class=\"tag\">#home</span>"));
+        assert!(html.trim_end().ends_with("</html>"));
+    }
+
+    #[test]
+    pub fn empty() {
+        let list = EventList::empty();
+        let clock = FixedClock::at(2020, 6, 10, 12, 0);
+        let report = Report::new(&list, "Status", &clock);
+        assert!(report.to_markdown().ends_with("\nNo events.\n"));
+        assert!(report.to_html().contains("<p>No events.</p>"));
+    }
+}
@@ -0,0 +1,207 @@
+/*
+   TU - Time's Up!
+   Copyright (C) 2020  Julio Biason
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Finding events by their text.
+
+use std::fmt;
+
+use regex::RegexBuilder;
+
+use crate::event::Event;
+use crate::eventlist::EventList;
+
+/// How the query is matched against the events.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SearchMode {
+    /// The query appears somewhere in the text, ignoring case.
+    Substring,
+    /// The query is a regular expression, matched ignoring case.
+    Regex,
+    /// The letters of the query appear in the text, in order, but not
+    /// necessarily together; closer letters rank better.
+    Fuzzy,
+}
+
+/// Scores a text against the query; None if it doesn't match.
+type Scorer = Box<dyn Fn(&str) -> Option<i64>>;
+
+/// An event that matched a search.
+#[derive(Debug)]
+pub struct SearchMatch<'a> {
+    /// The matching event.
+    pub event: &'a Event,
+    /// How good the match is; bigger is better.
+    pub score: i64,
+}
+
+/// Errors when searching.
+#[derive(Debug)]
+pub enum SearchError {
+    /// The query is not a valid regular expression.
+    InvalidRegex(String),
+}
+
+impl fmt::Display for SearchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SearchError::InvalidRegex(reason) => {
+                write!(f, "invalid regular expression: {}", reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SearchError {}
+
+/// Search the events, returning the ones that match with the best matches
+/// first; events with the same score keep their due order.
+pub fn search<'a>(
+    list: &'a EventList,
+    query: &str,
+    mode: SearchMode,
+) -> Result<Vec<SearchMatch<'a>>, SearchError> {
+    let scorer: Scorer = match mode {
+        SearchMode::Substring => {
+            let query = query.to_lowercase();
+            Box::new(move |text| substring_score(&query, text))
+        }
+        SearchMode::Regex => {
+            let regex = RegexBuilder::new(query)
+                .case_insensitive(true)
+                .build()
+                .map_err(|error| SearchError::InvalidRegex(error.to_string()))?;
+            Box::new(move |text| regex.find(text).map(|found| -(found.start() as i64)))
+        }
+        SearchMode::Fuzzy => {
+            let query = query.to_lowercase();
+            Box::new(move |text| fuzzy_score(&query, text))
+        }
+    };
+
+    let mut matches: Vec<SearchMatch> = list
+        .iter()
+        .filter_map(|event| {
+            searchable(event)
+                .into_iter()
+                .filter_map(&scorer)
+                .max()
+                .map(|score| SearchMatch { event, score })
+        })
+        .collect();
+    matches.sort_by_key(|found| std::cmp::Reverse(found.score));
+    Ok(matches)
+}
+
+/// The texts of an event that are searched.
+fn searchable(event: &Event) -> Vec<&str> {
+    let mut texts = vec![event.description.as_str()];
+    texts.extend(event.tags.iter().map(String::as_str));
+    texts
+}
+
+/// Score for a substring match: whole matches first, then matches at the
+/// start of the text, then earlier matches.
+fn substring_score(query: &str, text: &str) -> Option<i64> {
+    let text = text.to_lowercase();
+    let position = text.find(query)? as i64;
+    if text == query {
+        Some(1000)
+    } else if position == 0 {
+        Some(500)
+    } else {
+        Some(-position)
+    }
+}
+
+/// Score for a fuzzy match: every matching letter counts, letters right
+/// after the previous match or at the start of a word count more, and gaps
+/// count against.
+fn fuzzy_score(query: &str, text: &str) -> Option<i64> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut last_match: Option<usize> = None;
+
+    for wanted in query.chars().filter(|c| !c.is_whitespace()) {
+        let found = position + text[position..].iter().position(|c| *c == wanted)?;
+        score += 10;
+        match last_match {
+            Some(last) if last + 1 == found => score += 15,
+            Some(last) => score -= (found - last - 1) as i64,
+            None => score -= found as i64,
+        }
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 10;
+        }
+        last_match = Some(found);
+        position = found + 1;
+    }
+    Some(score)
+}
+
+#[cfg(test)]
+mod search_test {
+    use super::search;
+    use super::SearchMode;
+    use crate::date::Date;
+    use crate::event::Event;
+    use crate::eventlist::EventList;
+
+    fn list() -> EventList {
+        let date = Date::new(2030, 1, 1).unwrap();
+        vec![
+            ("1", "Renew the visa"),
+            ("2", "Visa appointment"),
+            ("3", "Dentist"),
+            ("4", "Pay the VAT invoice"),
+        ]
+        .into_iter()
+        .map(|(id, description)| Event::new_on_date(id, description, &date).unwrap())
+        .collect()
+    }
+
+    fn ids(list: &EventList, query: &str, mode: SearchMode) -> Vec<String> {
+        search(list, query, mode)
+            .unwrap()
+            .into_iter()
+            .map(|found| found.event.id.clone())
+            .collect()
+    }
+
+    #[test]
+    pub fn substring() {
+        let list = list();
+        assert_eq!(ids(&list, "VISA", SearchMode::Substring), vec!["2", "1"]);
+        assert!(ids(&list, "passport", SearchMode::Substring).is_empty());
+    }
+
+    #[test]
+    pub fn regex() {
+        let list = list();
+        assert_eq!(ids(&list, "^d.*t$", SearchMode::Regex), vec!["3"]);
+        assert!(search(&list, "(", SearchMode::Regex).is_err());
+    }
+
+    #[test]
+    pub fn fuzzy() {
+        let list = list();
+        assert_eq!(ids(&list, "vsa", SearchMode::Fuzzy), vec!["2", "1"]);
+        assert_eq!(ids(&list, "dnt", SearchMode::Fuzzy), vec!["3"]);
+        assert!(ids(&list, "xyz", SearchMode::Fuzzy).is_empty());
+    }
+}
@@ -0,0 +1,750 @@
+/*
+   TU - Time's Up!
+   Copyright (C) 2020  Julio Biason
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! HTTP API over the events.
+//!
+//! `GET /events` lists the events (only the ones with any of the tags, if
+//! there are `tag` parameters in the query), `POST /events` adds one,
+//! `GET`, `PATCH` and `DELETE` on `/events/ID` read, change and remove a
+//! single event. Events use the JSON layout of `tu export --format json`.
+//!
+//! `GET /calendar.ics` is the same events as an iCalendar feed, with the
+//! same `tag` parameters, for calendar applications to subscribe to; it
+//! carries validators from the storage modification time, so clients can
+//! ask for it again cheaply.
+//!
+//! Web pages can only use the API from the origins it is told to allow;
+//! requests from any other origin are refused, and events are only added or
+//! changed with JSON bodies, which browsers don't send across origins
+//! without asking first.
+
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use std::error::Error;
+
+use serde::Serialize;
+use serde_json::json;
+use tiny_http::Header;
+use tiny_http::Response;
+use tiny_http::Server;
+
+use tu::ics;
+use tu::json::EventChanges;
+use tu::json::EventJson;
+use tu::json::NewEventJson;
+use tu::Clock;
+use tu::Event;
+use tu::EventList;
+use tu::EventListError;
+use tu::IdGenerator;
+use tu::Storage;
+
+/// A request, already read from the connection.
+#[derive(Debug, Default)]
+pub struct Request {
+    pub method: String,
+    /// Path and query, like `/events?tag=work`.
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Request {
+    /// Value of a header; names are case insensitive.
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// What to answer to a request.
+#[derive(Debug)]
+pub struct Reply {
+    pub status: u16,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: String,
+}
+
+impl Reply {
+    fn json<T: Serialize>(status: u16, value: &T) -> Self {
+        Self {
+            status,
+            headers: vec![("Content-Type", "application/json".into())],
+            body: serde_json::to_string(value).unwrap_or_default(),
+        }
+    }
+
+    fn empty(status: u16) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: String::new(),
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Self::json(status, &json!({ "error": message }))
+    }
+
+    fn with_header(mut self, name: &'static str, value: &str) -> Self {
+        self.headers.push((name, value.into()));
+        self
+    }
+
+    /// The same reply, as for a new resource, if it succeeded.
+    fn created(mut self) -> Self {
+        if self.status == 200 {
+            self.status = 201;
+        }
+        self
+    }
+}
+
+impl From<EventListError> for Reply {
+    fn from(error: EventListError) -> Reply {
+        match error {
+            EventListError::NoSuchEvent => Reply::error(404, &error.to_string()),
+            EventListError::AlreadyDone | EventListError::NotDone => {
+                Reply::error(409, &error.to_string())
+            }
+            _ => Reply::error(500, &error.to_string()),
+        }
+    }
+}
+
+/// Decode `%XX` escapes and `+` for spaces in a part of an URL.
+fn decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut position = 0;
+    while position < bytes.len() {
+        let hex = text
+            .get(position + 1..position + 3)
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[position], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                position += 3;
+            }
+            (b'+', _) => {
+                decoded.push(b' ');
+                position += 1;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                position += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// The path and the decoded query parameters of an URL.
+fn split_url(url: &str) -> (&str, Vec<(String, String)>) {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let parameters = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode(name), decode(value))
+        })
+        .collect();
+    (path, parameters)
+}
+
+/// The values of the `tag` parameters in the query.
+fn tags(query: &[(String, String)]) -> Vec<&str> {
+    query
+        .iter()
+        .filter(|(name, _)| name == "tag")
+        .map(|(_, value)| value.as_str())
+        .collect()
+}
+
+/// Whether the event has any of the tags; any event does if there are none.
+fn has_any(event: &Event, tags: &[&str]) -> bool {
+    tags.is_empty() || event.tags.iter().any(|tag| tags.contains(&tag.as_str()))
+}
+
+/// Whether the body of the request is JSON, by its `Content-Type`.
+fn is_json(request: &Request) -> bool {
+    request
+        .header("Content-Type")
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|kind| kind.trim().eq_ignore_ascii_case("application/json"))
+}
+
+/// Weak entity tag for the content as of the modification time; weak because
+/// the content also changes with the current time.
+fn entity_tag(modified: SystemTime) -> String {
+    let since = modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+    format!("W/\"{:x}-{:x}\"", since.as_secs(), since.subsec_nanos())
+}
+
+/// Whether the client already has the content as of the modification time,
+/// by `If-None-Match` or, without it, `If-Modified-Since`.
+fn not_modified(request: &Request, modified: SystemTime, tag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    match request.header("If-None-Match") {
+        Some(tags) => tags
+            .split(',')
+            .any(|candidate| candidate.trim() == "*" || opaque(candidate) == opaque(tag)),
+        None => request
+            .header("If-Modified-Since")
+            .and_then(|since| httpdate::parse_http_date(since).ok())
+            .is_some_and(|since| {
+                // HTTP dates have no fractions of a second.
+                let modified = modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+                let since = since.duration_since(UNIX_EPOCH).unwrap_or_default();
+                modified.as_secs() <= since.as_secs()
+            }),
+    }
+}
+
+/// The API, over a storage.
+pub struct Api<'a> {
+    storage: &'a mut dyn Storage,
+    ids: &'a mut dyn IdGenerator,
+    clock: &'a dyn Clock,
+    /// Origins of the web pages allowed to use the API.
+    origins: Vec<String>,
+}
+
+impl<'a> Api<'a> {
+    pub fn new(
+        storage: &'a mut dyn Storage,
+        ids: &'a mut dyn IdGenerator,
+        clock: &'a dyn Clock,
+    ) -> Self {
+        Self {
+            storage,
+            ids,
+            clock,
+            origins: Vec::new(),
+        }
+    }
+
+    /// Let the web pages of these origins, like `http://localhost:3000`, use
+    /// the API.
+    pub fn allow_origins(mut self, origins: Vec<String>) -> Self {
+        self.origins = origins;
+        self
+    }
+
+    pub fn handle(&mut self, request: &Request) -> Reply {
+        // Browsers say where a page is from; other clients don't.
+        let origin = request.header("Origin");
+        if let Some(origin) = origin {
+            if !self.origins.iter().any(|allowed| allowed == origin) {
+                return Reply::error(403, "origin not allowed");
+            }
+        }
+
+        let (path, query) = split_url(&request.url);
+        let segments: Vec<String> = path.trim_matches('/').split('/').map(decode).collect();
+        let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+
+        let reply = match (request.method.as_str(), segments.as_slice()) {
+            ("OPTIONS", _) => Reply::empty(204)
+                .with_header("Access-Control-Allow-Methods", "GET, POST, PATCH, DELETE")
+                .with_header("Access-Control-Allow-Headers", "Content-Type"),
+            ("GET", ["events"]) => self.list(&query),
+            ("POST", ["events"]) | ("PATCH", ["events", _]) if !is_json(request) => {
+                Reply::error(415, "expected a JSON body")
+            }
+            ("POST", ["events"]) => self.create(&request.body),
+            ("GET", ["events", id]) => self.get(id),
+            ("PATCH", ["events", id]) => self.change(id, &request.body),
+            ("DELETE", ["events", id]) => self.delete(id),
+            ("POST", ["events", id, "done"]) => self.complete(id, true),
+            ("DELETE", ["events", id, "done"]) => self.complete(id, false),
+            ("GET", ["calendar.ics"]) => self.calendar(request, &query),
+            (_, ["calendar.ics"]) => Reply::error(405, "method not allowed"),
+            (_, ["events"]) | (_, ["events", _]) | (_, ["events", _, "done"]) => {
+                Reply::error(405, "method not allowed")
+            }
+            _ => Reply::error(404, "not found"),
+        };
+        match origin {
+            Some(origin) => reply
+                .with_header("Access-Control-Allow-Origin", origin)
+                .with_header("Vary", "Origin"),
+            None => reply,
+        }
+    }
+
+    fn list(&self, query: &[(String, String)]) -> Reply {
+        let tags = tags(query);
+        match self.storage.load() {
+            Ok(list) => {
+                let events: Vec<EventJson> = list
+                    .iter()
+                    .filter(|event| has_any(event, &tags))
+                    .map(|event| EventJson::new(event, self.clock))
+                    .collect();
+                Reply::json(200, &events)
+            }
+            Err(error) => error.into(),
+        }
+    }
+
+    fn calendar(&self, request: &Request, query: &[(String, String)]) -> Reply {
+        let validators = self
+            .storage
+            .modified()
+            .map(|modified| (modified, entity_tag(modified)));
+        let reply = match &validators {
+            Some((modified, tag)) if not_modified(request, *modified, tag) => Reply::empty(304),
+            _ => match self.storage.load() {
+                Ok(list) => {
+                    let tags = tags(query);
+                    let events = list.iter().filter(|event| has_any(event, &tags));
+                    Reply {
+                        status: 200,
+                        headers: vec![("Content-Type", "text/calendar; charset=utf-8".into())],
+                        body: ics::calendar(events, self.clock),
+                    }
+                }
+                Err(error) => return error.into(),
+            },
+        };
+        match validators {
+            Some((modified, tag)) => reply
+                .with_header("ETag", &tag)
+                .with_header("Last-Modified", &httpdate::fmt_http_date(modified)),
+            None => reply,
+        }
+    }
+
+    fn get(&self, id: &str) -> Reply {
+        match self.storage.load() {
+            Ok(list) => match list.get(id) {
+                Some(event) => Reply::json(200, &EventJson::new(event, self.clock)),
+                None => EventListError::NoSuchEvent.into(),
+            },
+            Err(error) => error.into(),
+        }
+    }
+
+    fn create(&mut self, body: &str) -> Reply {
+        let new = match serde_json::from_str::<NewEventJson>(body) {
+            Ok(new) => new,
+            Err(error) => return Reply::error(400, &error.to_string()),
+        };
+        let new = match new.into_new_event() {
+            Ok(new) => new,
+            Err(error) => return Reply::error(400, &error.to_string()),
+        };
+        match EventList::add(self.storage, self.ids, self.clock, new) {
+            Ok(id) => self
+                .get(&id)
+                .with_header("Location", &format!("/events/{}", id))
+                .created(),
+            Err(error) => error.into(),
+        }
+    }
+
+    fn change(&mut self, id: &str, body: &str) -> Reply {
+        let changes = match serde_json::from_str::<EventChanges>(body) {
+            Ok(changes) => changes,
+            Err(error) => return Reply::error(400, &error.to_string()),
+        };
+        let mut event = match self.storage.load() {
+            Ok(list) => match list.get(id) {
+                Some(event) => event.clone(),
+                None => return EventListError::NoSuchEvent.into(),
+            },
+            Err(error) => return error.into(),
+        };
+        if let Err(error) = changes.apply(&mut event) {
+            return Reply::error(400, &error.to_string());
+        }
+        match EventList::edit(self.storage, self.clock, event) {
+            Ok(()) => self.get(id),
+            Err(error) => error.into(),
+        }
+    }
+
+    /// Mark the event as done, or not done.
+    fn complete(&mut self, id: &str, done: bool) -> Reply {
+        let result = if done {
+            EventList::complete(self.storage, self.clock, id)
+        } else {
+            EventList::reopen(self.storage, self.clock, id)
+        };
+        match result {
+            Ok(event) => Reply::json(200, &EventJson::new(&event, self.clock)),
+            Err(error) => error.into(),
+        }
+    }
+
+    fn delete(&mut self, id: &str) -> Reply {
+        match EventList::remove_by_id(self.storage, id) {
+            Ok(_) => Reply::empty(204),
+            Err(error) => error.into(),
+        }
+    }
+}
+
+/// Read the request from the connection, answer it and log it.
+fn respond(api: &mut Api, mut request: tiny_http::Request) {
+    let mut body = String::new();
+    let reply = match request.as_reader().read_to_string(&mut body) {
+        Ok(_) => api.handle(&Request {
+            method: request.method().as_str().to_uppercase(),
+            url: request.url().into(),
+            headers: request
+                .headers()
+                .iter()
+                .map(|header| (header.field.to_string(), header.value.to_string()))
+                .collect(),
+            body,
+        }),
+        Err(error) => Reply::error(400, &error.to_string()),
+    };
+    log::info!("{} {} {}", request.method(), request.url(), reply.status);
+
+    let mut response = Response::from_string(reply.body).with_status_code(reply.status);
+    for (name, value) in reply.headers {
+        if let Ok(header) = Header::from_bytes(name.as_bytes(), value.as_bytes()) {
+            response.add_header(header);
+        }
+    }
+    if let Err(error) = request.respond(response) {
+        log::warn!("Can't send the response: {}", error);
+    }
+}
+
+/// Answer requests till the program is stopped.
+pub fn serve(
+    bind: &str,
+    origins: Vec<String>,
+    storage: &mut dyn Storage,
+    ids: &mut dyn IdGenerator,
+    clock: &dyn Clock,
+) -> Result<(), Box<dyn Error>> {
+    let server =
+        Server::http(bind).map_err(|error| format!("can't listen on {}: {}", bind, error))?;
+    println!("Serving the events on http://{}/events", bind);
+    let mut api = Api::new(storage, ids, clock).allow_origins(origins);
+    for request in server.incoming_requests() {
+        respond(&mut api, request);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod serve_test {
+    use std::io::Read;
+    use std::io::Write;
+    use std::net::TcpStream;
+    use std::thread;
+
+    use tiny_http::Server;
+
+    use tu::idgen::Sequential;
+    use tu::storage::Memory;
+    use tu::storage::TomlFile;
+    use tu::EventList;
+    use tu::Storage;
+
+    use super::decode;
+    use super::respond;
+    use super::Api;
+    use super::Reply;
+    use super::Request;
+    use crate::test_clock;
+
+    fn handle(storage: &mut dyn Storage, request: &Request) -> Reply {
+        let clock = test_clock();
+        let mut ids = Sequential;
+        Api::new(storage, &mut ids, &clock)
+            .allow_origins(vec![ORIGIN.into()])
+            .handle(request)
+    }
+
+    const ORIGIN: &str = "http://localhost:3000";
+
+    fn request(method: &str, url: &str, headers: &[(&str, &str)], body: &str) -> Request {
+        Request {
+            method: method.into(),
+            url: url.into(),
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            body: body.into(),
+        }
+    }
+
+    /// A request as sent by scripts, with JSON bodies.
+    fn call(storage: &mut Memory, method: &str, url: &str, body: &str) -> Reply {
+        let headers = [("Content-Type", "application/json")];
+        handle(storage, &request(method, url, &headers, body))
+    }
+
+    fn get_calendar(storage: &mut dyn Storage, url: &str, headers: &[(&str, &str)]) -> Reply {
+        handle(storage, &request("GET", url, headers, ""))
+    }
+
+    fn header<'a>(reply: &'a Reply, name: &str) -> Option<&'a str> {
+        reply
+            .headers
+            .iter()
+            .find(|(header, _)| *header == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    #[test]
+    pub fn create_and_list() {
+        let mut storage = Memory::new();
+        let reply = call(
+            &mut storage,
+            "POST",
+            "/events",
+            r#"{"description": "Pay taxes", "date": "2020-06-13", "tags": ["home"]}"#,
+        );
+        assert_eq!(reply.status, 201);
+        assert_eq!(header(&reply, "Location"), Some("/events/1"));
+        assert!(reply.body.contains(r#""eta":"3d""#));
+
+        call(
+            &mut storage,
+            "POST",
+            "/events",
+            r#"{"description": "Call", "date": "2020-06-11", "time": "14:05"}"#,
+        );
+        let reply = call(&mut storage, "GET", "/events", "");
+        assert_eq!(reply.status, 200);
+        let events: serde_json::Value = serde_json::from_str(&reply.body).unwrap();
+        assert_eq!(events[0]["description"], "Call");
+        assert_eq!(events[0]["time"], "14:05");
+        assert_eq!(events[1]["tags"][0], "home");
+
+        let reply = call(&mut storage, "GET", "/events?tag=home", "");
+        let events: serde_json::Value = serde_json::from_str(&reply.body).unwrap();
+        assert_eq!(events.as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    pub fn change_and_delete() {
+        let mut storage = Memory::new();
+        call(
+            &mut storage,
+            "POST",
+            "/events",
+            r#"{"description": "Call", "date": "2020-06-11"}"#,
+        );
+
+        let reply = call(&mut storage, "PATCH", "/events/1", r#"{"time": "09:30"}"#);
+        assert_eq!(reply.status, 200);
+        assert!(reply.body.contains(r#""time":"09:30""#));
+
+        let reply = call(&mut storage, "POST", "/events/1/done", "");
+        assert_eq!(reply.status, 200);
+        assert!(reply
+            .body
+            .contains(r#""completed_at":"2020-06-10T12:00:59"#));
+        assert_eq!(call(&mut storage, "POST", "/events/1/done", "").status, 409);
+        assert_eq!(
+            call(&mut storage, "DELETE", "/events/1/done", "").status,
+            200
+        );
+        assert!(!storage.load().unwrap().get("1").unwrap().is_done());
+
+        assert_eq!(call(&mut storage, "DELETE", "/events/1", "").status, 204);
+        assert!(storage.load().unwrap().is_empty());
+        assert_eq!(call(&mut storage, "GET", "/events/1", "").status, 404);
+        assert_eq!(call(&mut storage, "DELETE", "/events/1", "").status, 404);
+    }
+
+    #[test]
+    pub fn bad_requests() {
+        let mut storage = Memory::new();
+        let reply = call(&mut storage, "POST", "/events", "{");
+        assert_eq!(reply.status, 400);
+        let reply = call(
+            &mut storage,
+            "POST",
+            "/events",
+            r#"{"description": "x", "date": "2020-02-30"}"#,
+        );
+        assert_eq!(reply.status, 400);
+        assert!(reply.body.contains("there is no day 2020-02-30"));
+        assert_eq!(call(&mut storage, "PUT", "/events", "").status, 405);
+        assert_eq!(call(&mut storage, "GET", "/nope", "").status, 404);
+        assert_eq!(call(&mut storage, "OPTIONS", "/events", "").status, 204);
+    }
+
+    #[test]
+    pub fn only_json_bodies() {
+        let mut storage = Memory::new();
+        let body = r#"{"description": "x", "date": "2020-07-01"}"#;
+        let plain = [("Content-Type", "text/plain")];
+        let reply = handle(&mut storage, &request("POST", "/events", &plain, body));
+        assert_eq!(reply.status, 415);
+        let reply = handle(&mut storage, &request("POST", "/events", &[], body));
+        assert_eq!(reply.status, 415);
+        assert!(storage.load().unwrap().is_empty());
+
+        let json = [("Content-Type", "application/json; charset=utf-8")];
+        let reply = handle(&mut storage, &request("POST", "/events", &json, body));
+        assert_eq!(reply.status, 201);
+        let reply = handle(&mut storage, &request("PATCH", "/events/1", &plain, "{}"));
+        assert_eq!(reply.status, 415);
+    }
+
+    #[test]
+    pub fn origins() {
+        let mut storage = Memory::new();
+        call(
+            &mut storage,
+            "POST",
+            "/events",
+            r#"{"description": "x", "date": "2020-07-01"}"#,
+        );
+
+        let reply = call(&mut storage, "GET", "/events", "");
+        assert!(header(&reply, "Access-Control-Allow-Origin").is_none());
+
+        let page = [("Origin", ORIGIN)];
+        let reply = handle(&mut storage, &request("GET", "/events", &page, ""));
+        assert_eq!(reply.status, 200);
+        assert_eq!(header(&reply, "Access-Control-Allow-Origin"), Some(ORIGIN));
+
+        let other = [("Origin", "http://evil.example.com")];
+        for (method, url) in [
+            ("OPTIONS", "/events/1"),
+            ("POST", "/events/1/done"),
+            ("DELETE", "/events/1"),
+        ] {
+            let reply = handle(&mut storage, &request(method, url, &other, ""));
+            assert_eq!(reply.status, 403);
+            assert!(header(&reply, "Access-Control-Allow-Origin").is_none());
+        }
+        assert!(!storage.load().unwrap().get("1").unwrap().is_done());
+    }
+
+    #[test]
+    pub fn calendar_with_tags() {
+        let mut storage = Memory::new();
+        call(
+            &mut storage,
+            "POST",
+            "/events",
+            r#"{"description": "Launch", "date": "2020-07-01", "tags": ["work"]}"#,
+        );
+        call(
+            &mut storage,
+            "POST",
+            "/events",
+            r#"{"description": "Party", "date": "2020-07-02"}"#,
+        );
+
+        let reply = get_calendar(&mut storage, "/calendar.ics?tag=work", &[]);
+        assert_eq!(reply.status, 200);
+        assert_eq!(
+            header(&reply, "Content-Type"),
+            Some("text/calendar; charset=utf-8")
+        );
+        assert!(reply.body.contains("SUMMARY:Launch"));
+        assert!(!reply.body.contains("SUMMARY:Party"));
+        // Memory storage can't tell when it changed.
+        assert!(header(&reply, "ETag").is_none());
+    }
+
+    #[test]
+    pub fn calendar_validators() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut storage = TomlFile::new(dir.path().join("events.toml"));
+        storage.save(&EventList::empty()).unwrap();
+
+        let reply = get_calendar(&mut storage, "/calendar.ics", &[]);
+        assert_eq!(reply.status, 200);
+        let tag = header(&reply, "ETag").unwrap().to_string();
+        let modified = header(&reply, "Last-Modified").unwrap().to_string();
+
+        let reply = get_calendar(&mut storage, "/calendar.ics", &[("if-none-match", &tag)]);
+        assert_eq!(reply.status, 304);
+        assert!(reply.body.is_empty());
+        assert_eq!(header(&reply, "ETag"), Some(tag.as_str()));
+
+        let reply = get_calendar(
+            &mut storage,
+            "/calendar.ics",
+            &[("If-Modified-Since", &modified)],
+        );
+        assert_eq!(reply.status, 304);
+
+        let reply = get_calendar(
+            &mut storage,
+            "/calendar.ics",
+            &[
+                ("If-None-Match", "W/\"0-0\""),
+                ("If-Modified-Since", &modified),
+            ],
+        );
+        assert_eq!(reply.status, 200);
+        let reply = get_calendar(
+            &mut storage,
+            "/calendar.ics",
+            &[("If-Modified-Since", "Thu, 01 Jan 1970 00:00:00 GMT")],
+        );
+        assert_eq!(reply.status, 200);
+    }
+
+    #[test]
+    pub fn decoding() {
+        assert_eq!(decode("team%20a+b%2"), "team a b%2");
+    }
+
+    #[test]
+    pub fn over_http() {
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let address = server.server_addr().to_ip().unwrap();
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(address).unwrap();
+            let body = r#"{"description": "Launch", "date": "2020-07-01"}"#;
+            write!(
+                stream,
+                "POST /events HTTP/1.0\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        });
+
+        let mut storage = Memory::new();
+        let clock = test_clock();
+        let mut ids = Sequential;
+        let mut api = Api::new(&mut storage, &mut ids, &clock);
+        respond(&mut api, server.recv().unwrap());
+
+        let response = client.join().unwrap();
+        assert!(response.starts_with("HTTP/1.0 201"));
+        assert!(response.contains(r#""description":"Launch""#));
+        assert_eq!(storage.load().unwrap().len(), 1);
+    }
+}
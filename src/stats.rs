@@ -0,0 +1,271 @@
+/*
+   TU - Time's Up!
+   Copyright (C) 2020  Julio Biason
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Statistics over the events, for looking back at how they went.
+//!
+//! Everything counts all the events in the list, done or not: how many are
+//! upcoming, overdue or done, which weeks had the most events due, how many
+//! events were due each month and, for the events that know when they were
+//! created or done, how much warning there was and how early they were done.
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+use chrono::prelude::*;
+use chrono::Duration;
+
+use crate::clock::Clock;
+use crate::date::Date;
+use crate::event::Event;
+use crate::eventlist::EventList;
+
+/// How many of the busiest weeks are kept.
+const BUSIEST_WEEKS: usize = 3;
+
+/// The events due in a week.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WeekCount {
+    /// The Monday the week starts.
+    pub start: Date,
+    /// The Sunday the week ends.
+    pub end: Date,
+    /// Number of events due in the week.
+    pub events: usize,
+}
+
+/// The events due in a month.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MonthCount {
+    /// The year of the month.
+    pub year: i32,
+    /// The month, from 1 to 12.
+    pub month: u32,
+    /// Number of events due in the month.
+    pub events: usize,
+}
+
+/// A summary of the events.
+#[derive(Debug)]
+pub struct Stats {
+    /// Events not done and not over yet.
+    pub upcoming: usize,
+    /// Events not done that are already over.
+    pub overdue: usize,
+    /// Events done.
+    pub completed: usize,
+    /// The weeks with the most events due, the busiest first; weeks with as
+    /// many events come in date order.
+    pub busiest_weeks: Vec<WeekCount>,
+    /// Average time between creating an event and it being due; None if no
+    /// event knows when it was created.
+    pub average_lead: Option<Duration>,
+    /// Average time the events were done before being due, negative if they
+    /// were done late on average; None if no event is done.
+    pub average_ahead: Option<Duration>,
+    /// Events due each month, from the first to the last month with events,
+    /// including the months without any.
+    pub months: Vec<MonthCount>,
+}
+
+/// The average of the durations; None if there are none.
+fn average<I: Iterator<Item = Duration>>(durations: I) -> Option<Duration> {
+    let (total, count) = durations.fold((0, 0), |(total, count), duration| {
+        (total + duration.num_seconds(), count + 1)
+    });
+    if count == 0 {
+        None
+    } else {
+        Some(Duration::seconds(total / count))
+    }
+}
+
+/// How long the event was known before being due.
+fn lead(event: &Event) -> Option<Duration> {
+    let created = event.created_at?;
+    Some(Duration::seconds(
+        event.due.timestamp() - created.timestamp(),
+    ))
+}
+
+fn busiest_weeks(list: &EventList) -> Vec<WeekCount> {
+    let mut weeks: BTreeMap<NaiveDate, usize> = BTreeMap::new();
+    for event in list {
+        let day = event.due.date().0;
+        let monday = day - Duration::days(day.weekday().num_days_from_monday() as i64);
+        *weeks.entry(monday).or_default() += 1;
+    }
+    let mut weeks: Vec<WeekCount> = weeks
+        .into_iter()
+        .map(|(start, events)| WeekCount {
+            start: Date(start),
+            end: Date(start + Duration::days(6)),
+            events,
+        })
+        .collect();
+    // Stable, so weeks with as many events stay in date order.
+    weeks.sort_by_key(|week| Reverse(week.events));
+    weeks.truncate(BUSIEST_WEEKS);
+    weeks
+}
+
+fn months(list: &EventList) -> Vec<MonthCount> {
+    let mut counts: BTreeMap<(i32, u32), usize> = BTreeMap::new();
+    for event in list {
+        let day = event.due.date().0;
+        *counts.entry((day.year(), day.month())).or_default() += 1;
+    }
+    let (first, last) = match (counts.keys().next(), counts.keys().next_back()) {
+        (Some(first), Some(last)) => (*first, *last),
+        _ => return Vec::new(),
+    };
+
+    let mut months = Vec::new();
+    let (mut year, mut month) = first;
+    while (year, month) <= last {
+        months.push(MonthCount {
+            year,
+            month,
+            events: counts.get(&(year, month)).copied().unwrap_or(0),
+        });
+        if month == 12 {
+            year += 1;
+            month = 1;
+        } else {
+            month += 1;
+        }
+    }
+    months
+}
+
+impl Stats {
+    /// The statistics of the events in the list, as of the clock time.
+    pub fn new(list: &EventList, clock: &dyn Clock) -> Self {
+        let completed = list.iter().filter(|event| event.is_done()).count();
+        let overdue = list
+            .iter()
+            .filter(|event| !event.is_done() && event.eta(clock).is_over())
+            .count();
+        Self {
+            upcoming: list.len() - completed - overdue,
+            overdue,
+            completed,
+            busiest_weeks: busiest_weeks(list),
+            average_lead: average(list.iter().filter_map(lead)),
+            average_ahead: average(list.iter().filter_map(Event::ahead_of_due)),
+            months: months(list),
+        }
+    }
+
+    /// Number of events counted.
+    pub fn total(&self) -> usize {
+        self.upcoming + self.overdue + self.completed
+    }
+}
+
+#[cfg(test)]
+mod stats_test {
+    use chrono::Duration;
+
+    use super::MonthCount;
+    use super::Stats;
+    use super::WeekCount;
+    use crate::clock::FixedClock;
+    use crate::date::Date;
+    use crate::datetime::DateTime;
+    use crate::event::Event;
+    use crate::eventlist::EventList;
+
+    fn list() -> EventList {
+        let mut done_early = Event::on_day("1", "event", 2020, 6, 12);
+        done_early.completed_at = Some(DateTime::new(2020, 6, 8, 9, 0).unwrap());
+        let mut done_late = Event::on_day("2", "event", 2020, 6, 1);
+        done_late.completed_at = Some(DateTime::new(2020, 6, 3, 9, 0).unwrap());
+        let mut known = Event::on_day("3", "event", 2020, 6, 11);
+        known.created_at = Some(DateTime::new(2020, 6, 1, 23, 59).unwrap());
+        vec![
+            done_early,
+            done_late,
+            known,
+            Event::on_day("4", "event", 2020, 6, 2),
+            Event::on_day("5", "event", 2020, 6, 13),
+            Event::on_day("6", "event", 2020, 8, 20),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    #[test]
+    pub fn counts() {
+        let stats = Stats::new(&list(), &FixedClock::at(2020, 6, 10, 12, 0));
+        assert_eq!(stats.upcoming, 3);
+        assert_eq!(stats.overdue, 1);
+        assert_eq!(stats.completed, 2);
+        assert_eq!(stats.total(), 6);
+    }
+
+    #[test]
+    pub fn busiest_weeks() {
+        let stats = Stats::new(&list(), &FixedClock::at(2020, 6, 10, 12, 0));
+        assert_eq!(
+            stats.busiest_weeks,
+            vec![
+                WeekCount {
+                    start: Date::new(2020, 6, 8).unwrap(),
+                    end: Date::new(2020, 6, 14).unwrap(),
+                    events: 3,
+                },
+                WeekCount {
+                    start: Date::new(2020, 6, 1).unwrap(),
+                    end: Date::new(2020, 6, 7).unwrap(),
+                    events: 2,
+                },
+                WeekCount {
+                    start: Date::new(2020, 8, 17).unwrap(),
+                    end: Date::new(2020, 8, 23).unwrap(),
+                    events: 1,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    pub fn averages() {
+        let stats = Stats::new(&list(), &FixedClock::at(2020, 6, 10, 12, 0));
+        // All day events are due at the end of the day.
+        assert_eq!(stats.average_lead, Some(Duration::days(10)));
+        // 4 days early and 2 days late.
+        assert_eq!(stats.average_ahead, Some(Duration::days(1)));
+
+        let empty = Stats::new(&EventList::empty(), &FixedClock::at(2020, 6, 10, 12, 0));
+        assert!(empty.average_lead.is_none());
+        assert!(empty.average_ahead.is_none());
+        assert!(empty.months.is_empty());
+        assert!(empty.busiest_weeks.is_empty());
+    }
+
+    #[test]
+    pub fn months() {
+        let stats = Stats::new(&list(), &FixedClock::at(2020, 6, 10, 12, 0));
+        let months: Vec<(u32, usize)> = stats
+            .months
+            .iter()
+            .map(|MonthCount { month, events, .. }| (*month, *events))
+            .collect();
+        assert_eq!(months, vec![(6, 5), (7, 0), (8, 1)]);
+    }
+}
@@ -0,0 +1,52 @@
+/*
+   TU - Time's Up!
+   Copyright (C) 2020  Julio Biason
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Storage that lives only in memory.
+
+use crate::eventlist::EventList;
+use crate::eventlist::EventListError;
+use crate::storage::Storage;
+
+/// Keeps the events in memory; nothing survives the end of the program.
+#[derive(Debug, Default)]
+pub struct Memory {
+    list: EventList,
+}
+
+impl Memory {
+    /// Create an empty storage.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a storage already holding the events in the list.
+    pub fn with_events(list: EventList) -> Self {
+        Self { list }
+    }
+}
+
+impl Storage for Memory {
+    fn load(&self) -> Result<EventList, EventListError> {
+        Ok(self.list.clone())
+    }
+
+    fn save(&mut self, list: &EventList) -> Result<(), EventListError> {
+        self.list = list.clone();
+        Ok(())
+    }
+}
@@ -0,0 +1,90 @@
+/*
+   TU - Time's Up!
+   Copyright (C) 2020  Julio Biason
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Where the events are kept.
+//!
+//! The [`Storage`] trait is the interface between the [`EventList`] and the
+//! place the events live. [`TomlFile`] is the default, keeping everything in
+//! a single TOML file; [`Memory`] keeps the events only while the program
+//! runs, which is mostly useful for tests; [`Sqlite`] keeps the events in a
+//! SQLite database, changing only the affected rows.
+
+mod memory;
+mod sqlite;
+mod tomlfile;
+
+pub use memory::Memory;
+pub use sqlite::Sqlite;
+pub use tomlfile::TomlFile;
+
+use std::time::SystemTime;
+
+use crate::event::Event;
+use crate::eventlist::EventList;
+use crate::eventlist::EventListError;
+
+/// A place to load and save events.
+///
+/// Only [`load`](Storage::load) and [`save`](Storage::save) are required;
+/// the single event operations default to loading the whole list, changing
+/// it and saving it back, and backends that can do better should override
+/// them.
+pub trait Storage {
+    /// Load all the events.
+    fn load(&self) -> Result<EventList, EventListError>;
+
+    /// Replace all the stored events with the ones in the list.
+    fn save(&mut self, list: &EventList) -> Result<(), EventListError>;
+
+    /// Add a single event.
+    fn insert(&mut self, event: Event) -> Result<(), EventListError> {
+        let mut list = self.load()?;
+        list.push(event);
+        self.save(&list)
+    }
+
+    /// Remove a single event by its ID, returning it.
+    fn remove(&mut self, id: &str) -> Result<Event, EventListError> {
+        let mut list = self.load()?;
+        let event = list.remove(id).ok_or(EventListError::NoSuchEvent)?;
+        self.save(&list)?;
+        Ok(event)
+    }
+
+    /// Replace an existing event with a new version of it, matched by ID.
+    fn update(&mut self, event: Event) -> Result<(), EventListError> {
+        let mut list = self.load()?;
+        list.remove(&event.id).ok_or(EventListError::NoSuchEvent)?;
+        list.push(event);
+        self.save(&list)
+    }
+
+    /// When the stored events last changed, if the backend can tell without
+    /// loading them.
+    fn modified(&self) -> Option<SystemTime> {
+        None
+    }
+}
+
+/// Copy all events from one storage to another.
+/// Returns the number of copied events.
+pub fn migrate(from: &dyn Storage, to: &mut dyn Storage) -> Result<usize, EventListError> {
+    let list = from.load()?;
+    to.save(&list)?;
+    Ok(list.len())
+}
@@ -0,0 +1,445 @@
+/*
+   TU - Time's Up!
+   Copyright (C) 2020  Julio Biason
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Storage in a SQLite database.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use dirs::config_dir;
+use rusqlite::params;
+use rusqlite::Connection;
+use rusqlite::Row;
+
+use crate::date::Date;
+use crate::date_errors::DateError;
+use crate::datetime::DateTime;
+use crate::event::Event;
+use crate::eventlist::EventList;
+use crate::eventlist::EventListError;
+use crate::eventtype::EventType;
+use crate::priority::Priority;
+use crate::storage::Storage;
+
+static FILENAME: &str = "events.sqlite";
+
+/// Schema changes, in order. The database keeps the number of applied
+/// migrations in its `user_version`; new changes must be appended, never
+/// edited.
+static MIGRATIONS: &[&str] = &[
+    "CREATE TABLE events (
+        id TEXT PRIMARY KEY,
+        description TEXT NOT NULL,
+        all_day INTEGER NOT NULL,
+        due TEXT NOT NULL,
+        due_timestamp INTEGER NOT NULL
+    );
+    CREATE INDEX events_due_timestamp ON events (due_timestamp);",
+    "CREATE TABLE tags (
+        event_id TEXT NOT NULL,
+        tag TEXT NOT NULL,
+        PRIMARY KEY (event_id, tag)
+    );
+    ALTER TABLE events ADD COLUMN created_at TEXT;",
+    "ALTER TABLE events ADD COLUMN updated_at TEXT;
+    ALTER TABLE events ADD COLUMN created_by TEXT;
+    UPDATE events SET updated_at = created_at;",
+    "ALTER TABLE events ADD COLUMN priority TEXT NOT NULL DEFAULT 'normal';",
+    "ALTER TABLE events ADD COLUMN completed_at TEXT;",
+];
+
+static COLUMNS: &str =
+    "id, description, all_day, due, created_at, updated_at, created_by, priority, completed_at";
+
+impl From<rusqlite::Error> for EventListError {
+    fn from(error: rusqlite::Error) -> EventListError {
+        EventListError::StorageFailure(error.to_string())
+    }
+}
+
+/// Keeps the events in a SQLite database, one row per event.
+#[derive(Debug)]
+pub struct Sqlite {
+    connection: Connection,
+}
+
+impl Sqlite {
+    /// Open (or create) the database in the path, bringing its schema up to
+    /// date.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, EventListError> {
+        Sqlite::with_connection(Connection::open(path)?)
+    }
+
+    /// Open a database that lives only in memory.
+    pub fn open_in_memory() -> Result<Self, EventListError> {
+        Sqlite::with_connection(Connection::open_in_memory()?)
+    }
+
+    /// Default location of the database, inside the user configuration
+    /// directory.
+    pub fn default_path() -> Result<PathBuf, EventListError> {
+        let mut path = config_dir().ok_or(EventListError::NoStorage)?;
+        path.push(FILENAME);
+        Ok(path)
+    }
+
+    /// The schema version of the database.
+    pub fn schema_version(&self) -> Result<usize, EventListError> {
+        let version: i64 = self
+            .connection
+            .query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        Ok(version as usize)
+    }
+
+    fn with_connection(mut connection: Connection) -> Result<Self, EventListError> {
+        migrate(&mut connection)?;
+        Ok(Self { connection })
+    }
+}
+
+/// Apply the migrations the database is still missing, each in its own
+/// transaction.
+fn migrate(connection: &mut Connection) -> Result<(), EventListError> {
+    let version: i64 = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    let version = version as usize;
+    if version > MIGRATIONS.len() {
+        return Err(EventListError::NewerFormat(version as u32));
+    }
+
+    for (number, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        log::debug!("Applying database migration {}", number + 1);
+        let transaction = connection.transaction()?;
+        transaction.execute_batch(migration)?;
+        transaction.pragma_update(None, "user_version", (number + 1) as i64)?;
+        transaction.commit()?;
+    }
+    Ok(())
+}
+
+/// The values stored in the `all_day` and `due` columns for an event.
+fn due_columns(due: &EventType) -> (bool, String) {
+    match due {
+        EventType::AllDay(date) => (true, date.to_string()),
+        EventType::AtTime(datetime) => (false, datetime.to_rfc3339()),
+    }
+}
+
+fn event_from_row(row: &Row) -> rusqlite::Result<Result<Event, EventListError>> {
+    let id: String = row.get(0)?;
+    let description: String = row.get(1)?;
+    let all_day: bool = row.get(2)?;
+    let due: String = row.get(3)?;
+    let created_at: Option<String> = row.get(4)?;
+    let updated_at: Option<String> = row.get(5)?;
+    let created_by: Option<String> = row.get(6)?;
+    let priority: String = row.get(7)?;
+    let completed_at: Option<String> = row.get(8)?;
+    let priority = match Priority::from_name(&priority) {
+        Some(priority) => priority,
+        None => return Ok(Err(EventListError::BrokenFormat)),
+    };
+
+    let event = || -> Result<Event, DateError> {
+        let due = if all_day {
+            EventType::AllDay(Date::try_from(&due)?)
+        } else {
+            EventType::AtTime(DateTime::from_rfc3339(&due)?)
+        };
+        let moment = |value: Option<String>| match value {
+            Some(value) => DateTime::from_rfc3339(&value).map(Some),
+            None => Ok(None),
+        };
+        Ok(Event {
+            id,
+            description,
+            tags: Vec::new(),
+            priority,
+            created_at: moment(created_at)?,
+            updated_at: moment(updated_at)?,
+            created_by,
+            completed_at: moment(completed_at)?,
+            due,
+        })
+    };
+    Ok(event().map_err(|_| EventListError::BrokenFormat))
+}
+
+/// Tags of the events, by event ID, in the order they were added.
+fn load_tags(connection: &Connection) -> Result<HashMap<String, Vec<String>>, EventListError> {
+    let mut statement = connection.prepare("SELECT event_id, tag FROM tags ORDER BY rowid")?;
+    let rows = statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    let mut tags: HashMap<String, Vec<String>> = HashMap::new();
+    for row in rows {
+        let (id, tag) = row?;
+        tags.entry(id).or_default().push(tag);
+    }
+    Ok(tags)
+}
+
+fn insert_event(connection: &Connection, event: &Event) -> Result<(), EventListError> {
+    let (all_day, due) = due_columns(&event.due);
+    connection.execute(
+        "INSERT INTO events (id, description, all_day, due, due_timestamp, created_at,
+                             updated_at, created_by, priority, completed_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![
+            event.id,
+            event.description,
+            all_day,
+            due,
+            event.due.timestamp(),
+            event.created_at.map(|created_at| created_at.to_rfc3339()),
+            event.updated_at.map(|updated_at| updated_at.to_rfc3339()),
+            event.created_by.as_deref(),
+            event.priority.to_string(),
+            event.completed_at.map(|completed| completed.to_rfc3339()),
+        ],
+    )?;
+    for tag in &event.tags {
+        connection.execute(
+            "INSERT OR IGNORE INTO tags (event_id, tag) VALUES (?1, ?2)",
+            params![event.id, tag],
+        )?;
+    }
+    Ok(())
+}
+
+/// Get a single event, with its tags.
+fn find_event(connection: &Connection, id: &str) -> Result<Event, EventListError> {
+    let mut event = connection
+        .query_row(
+            &format!("SELECT {} FROM events WHERE id = ?1", COLUMNS),
+            params![id],
+            event_from_row,
+        )
+        .map_err(|error| match error {
+            rusqlite::Error::QueryReturnedNoRows => EventListError::NoSuchEvent,
+            error => error.into(),
+        })??;
+    let mut statement =
+        connection.prepare("SELECT tag FROM tags WHERE event_id = ?1 ORDER BY rowid")?;
+    let tags = statement.query_map(params![id], |row| row.get(0))?;
+    for tag in tags {
+        event.tags.push(tag?);
+    }
+    Ok(event)
+}
+
+fn delete_event(connection: &Connection, id: &str) -> Result<(), EventListError> {
+    connection.execute("DELETE FROM tags WHERE event_id = ?1", params![id])?;
+    connection.execute("DELETE FROM events WHERE id = ?1", params![id])?;
+    Ok(())
+}
+
+impl Storage for Sqlite {
+    fn load(&self) -> Result<EventList, EventListError> {
+        let mut tags = load_tags(&self.connection)?;
+        let mut statement = self.connection.prepare(&format!(
+            "SELECT {} FROM events ORDER BY due_timestamp",
+            COLUMNS
+        ))?;
+        let rows = statement.query_map([], event_from_row)?;
+        let mut events = Vec::new();
+        for row in rows {
+            let mut event = row??;
+            event.tags = tags.remove(&event.id).unwrap_or_default();
+            events.push(event);
+        }
+        Ok(events.into_iter().collect())
+    }
+
+    fn save(&mut self, list: &EventList) -> Result<(), EventListError> {
+        let transaction = self.connection.transaction()?;
+        transaction.execute("DELETE FROM tags", [])?;
+        transaction.execute("DELETE FROM events", [])?;
+        for event in list {
+            insert_event(&transaction, event)?;
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn insert(&mut self, event: Event) -> Result<(), EventListError> {
+        let transaction = self.connection.transaction()?;
+        insert_event(&transaction, &event)?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn remove(&mut self, id: &str) -> Result<Event, EventListError> {
+        let transaction = self.connection.transaction()?;
+        let event = find_event(&transaction, id)?;
+        delete_event(&transaction, id)?;
+        transaction.commit()?;
+        Ok(event)
+    }
+
+    fn update(&mut self, event: Event) -> Result<(), EventListError> {
+        let transaction = self.connection.transaction()?;
+        find_event(&transaction, &event.id)?;
+        delete_event(&transaction, &event.id)?;
+        insert_event(&transaction, &event)?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// The modification time of the database file; databases in memory
+    /// can't tell.
+    fn modified(&self) -> Option<SystemTime> {
+        let path = self.connection.path().filter(|path| !path.is_empty())?;
+        fs::metadata(path).ok()?.modified().ok()
+    }
+}
+
+#[cfg(test)]
+mod sqlite_test {
+    use super::Sqlite;
+    use super::MIGRATIONS;
+    use crate::clock::FixedClock;
+    use crate::date::Date;
+    use crate::datetime::DateTime;
+    use crate::event::Event;
+    use crate::event::NewEvent;
+    use crate::eventlist::EventList;
+    use crate::eventlist::EventListError;
+    use crate::idgen::Sequential;
+    use crate::storage::migrate;
+    use crate::storage::Storage;
+    use crate::storage::TomlFile;
+
+    #[test]
+    pub fn schema_is_current() {
+        let storage = Sqlite::open_in_memory().unwrap();
+        assert_eq!(storage.schema_version().unwrap(), MIGRATIONS.len());
+    }
+
+    #[test]
+    pub fn newer_schema() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.sqlite");
+        let connection = rusqlite::Connection::open(&path).unwrap();
+        connection
+            .pragma_update(None, "user_version", (MIGRATIONS.len() + 1) as i64)
+            .unwrap();
+        drop(connection);
+
+        assert!(matches!(
+            Sqlite::open(&path),
+            Err(EventListError::NewerFormat(_))
+        ));
+    }
+
+    #[test]
+    pub fn add_edit_remove() {
+        let mut storage = Sqlite::open_in_memory().unwrap();
+        let date = Date::new(2030, 5, 26).unwrap();
+        let id = EventList::add(
+            &mut storage,
+            &mut Sequential,
+            &FixedClock::at(2020, 6, 10, 12, 0),
+            NewEvent::on_date("event", &date),
+        )
+        .unwrap();
+
+        let mut event = storage.load().unwrap().get(&id).unwrap().clone();
+        event.description = "changed".into();
+        storage.update(event).unwrap();
+        assert_eq!(
+            storage.load().unwrap().get(&id).unwrap().description,
+            "changed"
+        );
+
+        assert_eq!(
+            EventList::remove_by_id(&mut storage, &id).unwrap(),
+            "changed"
+        );
+        assert!(storage.load().unwrap().is_empty());
+        assert!(matches!(
+            storage.remove(&id),
+            Err(EventListError::NoSuchEvent)
+        ));
+    }
+
+    #[test]
+    pub fn tags() {
+        let mut storage = Sqlite::open_in_memory().unwrap();
+        let date = Date::new(2030, 5, 26).unwrap();
+        let new = NewEvent::on_date("event", &date).with_tags(vec!["work".into(), "home".into()]);
+        let id = EventList::add(
+            &mut storage,
+            &mut Sequential,
+            &FixedClock::at(2020, 6, 10, 12, 0),
+            new,
+        )
+        .unwrap();
+
+        let mut event = storage.load().unwrap().get(&id).unwrap().clone();
+        assert_eq!(event.tags, vec!["work", "home"]);
+        assert!(event.created_at.is_some());
+        assert_eq!(event.updated_at, event.created_at);
+
+        event.tags = vec!["home".into()];
+        storage.update(event).unwrap();
+        assert_eq!(storage.remove(&id).unwrap().tags, vec!["home"]);
+    }
+
+    #[test]
+    pub fn duplicated_id() {
+        let mut storage = Sqlite::open_in_memory().unwrap();
+        let date = Date::new(2030, 5, 26).unwrap();
+        let event = Event::new_on_date("1", "event", &date).unwrap();
+        storage.insert(event.clone()).unwrap();
+        assert!(storage.insert(event).is_err());
+        assert_eq!(storage.load().unwrap().len(), 1);
+    }
+
+    #[test]
+    pub fn migrate_from_toml_and_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut toml = TomlFile::new(dir.path().join("events.toml"));
+        let date = Date::new(2030, 5, 26).unwrap();
+        let datetime = DateTime::new(2030, 5, 25, 9, 30).unwrap();
+        EventList::add(
+            &mut toml,
+            &mut Sequential,
+            &FixedClock::at(2020, 6, 10, 12, 0),
+            NewEvent::on_date("all day", &date),
+        )
+        .unwrap();
+        EventList::add(
+            &mut toml,
+            &mut Sequential,
+            &FixedClock::at(2020, 6, 10, 12, 0),
+            NewEvent::at_time("at time", &datetime).with_tags(vec!["b".into(), "a".into()]),
+        )
+        .unwrap();
+
+        let mut sqlite = Sqlite::open(dir.path().join("events.sqlite")).unwrap();
+        assert_eq!(migrate(&toml, &mut sqlite).unwrap(), 2);
+
+        let mut back = TomlFile::new(dir.path().join("back.toml"));
+        assert_eq!(migrate(&sqlite, &mut back).unwrap(), 2);
+
+        let original = std::fs::read_to_string(dir.path().join("events.toml")).unwrap();
+        let converted = std::fs::read_to_string(dir.path().join("back.toml")).unwrap();
+        assert_eq!(original, converted);
+    }
+}
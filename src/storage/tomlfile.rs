@@ -0,0 +1,326 @@
+/*
+   TU - Time's Up!
+   Copyright (C) 2020  Julio Biason
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Storage in a single TOML file.
+//!
+//! The file carries a `version` key with the layout it was written with;
+//! files from before the key existed are version 1. When loading, older
+//! layouts are brought up to date by a chain of upgrade functions, one
+//! version at a time, and files from newer versions of tu are refused.
+
+use std::convert::TryFrom;
+use std::fs;
+use std::fs::File;
+use std::io::{ErrorKind, Read, Write};
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use dirs::config_dir;
+use toml::value::Table;
+use toml::Value;
+
+use crate::eventlist::EventList;
+use crate::eventlist::EventListError;
+use crate::storage::Storage;
+
+static FILENAME: &str = "events.toml";
+
+/// Version of the layout written by this version of tu.
+pub const FORMAT_VERSION: u32 = 6;
+
+/// Functions to bring a layout to the next version: the first one turns a
+/// version 1 file into version 2, and so on. New layouts must append a
+/// function here and bump [`FORMAT_VERSION`].
+static UPGRADES: &[fn(&mut Table)] = &[
+    upgrade_v1_to_v2,
+    upgrade_v2_to_v3,
+    upgrade_v3_to_v4,
+    upgrade_v4_to_v5,
+    upgrade_v5_to_v6,
+];
+
+/// Version 2 only introduced the version key; the events are the same.
+fn upgrade_v1_to_v2(_content: &mut Table) {}
+
+/// Version 3 added tags and the creation time to events; old events have
+/// no tags, and when they were created is unknown.
+fn upgrade_v2_to_v3(content: &mut Table) {
+    for event in events(content) {
+        event
+            .entry("tags")
+            .or_insert_with(|| Value::Array(Vec::new()));
+    }
+}
+
+/// Version 4 added when events were last changed and who created them;
+/// old events were last changed when they were created, by someone unknown.
+fn upgrade_v3_to_v4(content: &mut Table) {
+    for event in events(content) {
+        if let Some(created_at) = event.get("created_at").cloned() {
+            event.entry("updated_at").or_insert(created_at);
+        }
+    }
+}
+
+/// Version 5 added priorities; old events are all normal.
+fn upgrade_v4_to_v5(content: &mut Table) {
+    for event in events(content) {
+        event
+            .entry("priority")
+            .or_insert_with(|| Value::String("normal".into()));
+    }
+}
+
+/// Version 6 added the completion of events; no old event is done, and
+/// older versions of tu would take the done ones for pending.
+fn upgrade_v5_to_v6(_content: &mut Table) {}
+
+/// The events in the file content, as TOML tables.
+fn events(content: &mut Table) -> impl Iterator<Item = &mut Table> {
+    content
+        .get_mut("events")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_table_mut)
+}
+
+/// Bring the content of a file to the current layout, returning the version
+/// it had.
+fn upgrade(content: &mut Table) -> Result<u32, EventListError> {
+    let version = match content.remove("version") {
+        None => 1,
+        Some(Value::Integer(version)) if version >= 1 => {
+            u32::try_from(version).map_err(|_| EventListError::BrokenFormat)?
+        }
+        Some(_) => return Err(EventListError::BrokenFormat),
+    };
+    if version > FORMAT_VERSION {
+        return Err(EventListError::NewerFormat(version));
+    }
+
+    for (from, step) in UPGRADES.iter().enumerate().skip(version as usize - 1) {
+        log::debug!("Upgrading event file from version {}", from + 1);
+        step(content);
+    }
+    Ok(version)
+}
+
+/// Keeps all the events in a TOML file, which is rewritten on every change.
+#[derive(Debug)]
+pub struct TomlFile {
+    path: PathBuf,
+}
+
+impl TomlFile {
+    /// Use the file in the path as storage.
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Default location of the event file, inside the user configuration
+    /// directory.
+    pub fn default_path() -> Result<PathBuf, EventListError> {
+        let mut path = config_dir().ok_or(EventListError::NoStorage)?;
+        path.push(FILENAME);
+        Ok(path)
+    }
+
+    /// Path of the event file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Read the events in the content of an event file, of any version.
+    pub fn parse(content: &str) -> Result<EventList, EventListError> {
+        let mut data: Table = toml::from_str(content)?;
+        upgrade(&mut data)?;
+        Ok(Value::Table(data).try_into()?)
+    }
+
+    /// Content of an event file with the events, in the current version.
+    pub fn render(list: &EventList) -> Result<String, EventListError> {
+        Ok(format!(
+            "version = {}\n\n{}",
+            FORMAT_VERSION,
+            toml::to_string(list)?
+        ))
+    }
+}
+
+impl Storage for TomlFile {
+    /// Load the event list from the file; if there is no file, the list is
+    /// empty.
+    fn load(&self) -> Result<EventList, EventListError> {
+        let mut fp = match File::open(&self.path) {
+            Ok(fp) => fp,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(EventList::empty()),
+            Err(error) => return Err(error.into()),
+        };
+        let mut content = String::new();
+        fp.read_to_string(&mut content)?;
+        TomlFile::parse(&content)
+    }
+
+    fn save(&mut self, list: &EventList) -> Result<(), EventListError> {
+        let content = TomlFile::render(list)?;
+        let mut fp = File::create(&self.path)?;
+        fp.write_all(content.as_bytes())?;
+        Ok(())
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        fs::metadata(&self.path).ok()?.modified().ok()
+    }
+}
+
+#[cfg(test)]
+mod tomlfile_test {
+    use super::TomlFile;
+    use super::FORMAT_VERSION;
+    use crate::clock::FixedClock;
+    use crate::date::Date;
+    use crate::event::NewEvent;
+    use crate::eventlist::EventList;
+    use crate::eventlist::EventListError;
+    use crate::idgen::Sequential;
+    use crate::priority::Priority;
+    use crate::storage::Storage;
+
+    static VERSION_1: &str = r#"
+[[events]]
+id = "372ab709"
+description = "test"
+
+[events.due]
+due = "AllDay"
+datetime = "2030-01-01"
+"#;
+
+    #[test]
+    pub fn missing_file_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = TomlFile::new(dir.path().join("events.toml"));
+        assert!(storage.load().unwrap().is_empty());
+    }
+
+    #[test]
+    pub fn round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut storage = TomlFile::new(dir.path().join("events.toml"));
+        let date = Date::new(2030, 5, 26).unwrap();
+        let id = EventList::add(
+            &mut storage,
+            &mut Sequential,
+            &FixedClock::at(2020, 6, 10, 12, 0),
+            NewEvent::on_date("event", &date),
+        )
+        .unwrap();
+
+        let list = TomlFile::new(dir.path().join("events.toml"))
+            .load()
+            .unwrap();
+        let event = list.get(&id).unwrap();
+        assert_eq!(event.description, "event");
+        assert_eq!(event.due.timestamp(), date.timestamp());
+    }
+
+    #[test]
+    pub fn modified() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut storage = TomlFile::new(dir.path().join("events.toml"));
+        assert!(storage.modified().is_none());
+        storage.save(&EventList::empty()).unwrap();
+        assert!(storage.modified().is_some());
+    }
+
+    #[test]
+    pub fn saves_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.toml");
+        TomlFile::new(&path).save(&EventList::empty()).unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.starts_with(&format!("version = {}", FORMAT_VERSION)));
+    }
+
+    #[test]
+    pub fn upgrades_version_1() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.toml");
+        std::fs::write(&path, VERSION_1).unwrap();
+
+        let list = TomlFile::new(&path).load().unwrap();
+        assert_eq!(list.get("372ab709").unwrap().description, "test");
+    }
+
+    #[test]
+    pub fn upgrades_version_2() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.toml");
+        std::fs::write(&path, format!("version = 2\n{}", VERSION_1)).unwrap();
+
+        let list = TomlFile::new(&path).load().unwrap();
+        let event = list.get("372ab709").unwrap();
+        assert!(event.tags.is_empty());
+        assert!(event.created_at.is_none());
+        assert!(event.updated_at.is_none());
+    }
+
+    #[test]
+    pub fn upgrades_version_3() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.toml");
+        let content = VERSION_1.replace(
+            "description = \"test\"\n",
+            "description = \"test\"\ntags = []\ncreated_at = \"2020-06-01T08:00:59+00:00\"\n",
+        );
+        std::fs::write(&path, format!("version = 3\n{}", content)).unwrap();
+
+        let list = TomlFile::new(&path).load().unwrap();
+        let event = list.get("372ab709").unwrap();
+        assert!(event.created_at.is_some());
+        assert_eq!(event.updated_at, event.created_at);
+        assert!(event.created_by.is_none());
+        assert_eq!(event.priority, Priority::Normal);
+    }
+
+    #[test]
+    pub fn refuses_newer_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.toml");
+        let content = format!("version = {}\n{}", FORMAT_VERSION + 1, VERSION_1);
+        std::fs::write(&path, content).unwrap();
+
+        match TomlFile::new(&path).load() {
+            Err(EventListError::NewerFormat(version)) => assert_eq!(version, FORMAT_VERSION + 1),
+            other => panic!("Newer file accepted: {:?}", other),
+        }
+
+        // Doesn't even fit a version number, so it can't be taken as a
+        // smaller one.
+        let content = format!("version = {}\n{}", u64::from(u32::MAX) + 2, VERSION_1);
+        assert!(matches!(
+            TomlFile::parse(&content),
+            Err(EventListError::BrokenFormat)
+        ));
+    }
+}
@@ -0,0 +1,99 @@
+/*
+   TU - Time's Up!
+   Copyright (C) 2020  Julio Biason
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use chrono::Duration;
+use chrono::NaiveDate;
+
+use tu::stats::Stats;
+
+use crate::render::Renderer;
+use crate::render::Style;
+use crate::timeliness;
+
+/// Width of the longest bar in the histogram.
+const BAR_WIDTH: usize = 40;
+
+/// Print the statistics: the counts, the averages, the busiest weeks and a
+/// histogram of the events due each month.
+pub fn print_stats(stats: &Stats, renderer: &Renderer) {
+    println!("{}", renderer.paint("Events", &[Style::Bold]));
+    println!("  Upcoming:  {}", stats.upcoming);
+    println!(
+        "  Overdue:   {}",
+        renderer.paint(&stats.overdue.to_string(), overdue_style(stats))
+    );
+    println!("  Completed: {}", stats.completed);
+    println!("  Total:     {}", stats.total());
+
+    if let Some(lead) = stats.average_lead {
+        println!("  Average lead time: {}", length(lead));
+    }
+    if let Some(ahead) = stats.average_ahead {
+        println!("  Average completion: {}", timeliness(ahead));
+    }
+
+    if !stats.busiest_weeks.is_empty() {
+        println!();
+        println!("{}", renderer.paint("Busiest weeks", &[Style::Bold]));
+        for week in &stats.busiest_weeks {
+            println!("  {} to {}: {}", week.start, week.end, events(week.events));
+        }
+    }
+
+    if !stats.months.is_empty() {
+        println!();
+        println!("{}", renderer.paint("Events per month", &[Style::Bold]));
+        let most = stats.months.iter().map(|month| month.events).max();
+        for month in &stats.months {
+            let name = NaiveDate::from_ymd_opt(month.year, month.month, 1)
+                .map(|first| first.format("%b %Y").to_string())
+                .unwrap_or_default();
+            let bar = match most {
+                Some(most) if most > 0 => "#".repeat((month.events * BAR_WIDTH).div_ceil(most)),
+                _ => String::new(),
+            };
+            let line = format!("  {} {:>3} {}", name, month.events, bar);
+            println!("{}", renderer.paint(line.trim_end(), &[Style::Cyan]));
+        }
+    }
+}
+
+fn overdue_style(stats: &Stats) -> &'static [Style] {
+    if stats.overdue > 0 {
+        &[Style::Red]
+    } else {
+        &[]
+    }
+}
+
+fn events(count: usize) -> String {
+    match count {
+        1 => "1 event".into(),
+        _ => format!("{} events", count),
+    }
+}
+
+/// A duration in days, or hours if shorter than a day.
+fn length(duration: Duration) -> String {
+    let (amount, unit) = match duration {
+        duration if duration.num_days().abs() > 0 => (duration.num_days(), "day"),
+        duration => (duration.num_hours(), "hour"),
+    };
+    let plural = if amount.abs() == 1 { "" } else { "s" };
+    format!("{} {}{}", amount, unit, plural)
+}
@@ -0,0 +1,496 @@
+/*
+   TU - Time's Up!
+   Copyright (C) 2020  Julio Biason
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Showing events through user supplied templates.
+//!
+//! A template is text with fields between braces, like
+//! `{description} in {eta}`; each field is replaced by that value of the
+//! event. Fields can go through filters, separated by `|`: `upper` and
+//! `lower` change the case, and a `strftime` format like `%d/%m` formats the
+//! date fields (`{due|%a %d %b}`). Literal braces are written twice, `{{`
+//! and `}}`, and `\t`, `\n` and `\\` are a tab, a new line and a backslash.
+
+use std::fmt;
+use std::fmt::Write;
+
+use chrono::format::Item;
+use chrono::format::StrftimeItems;
+use chrono::Local;
+use chrono::NaiveDate;
+
+use crate::clock::Clock;
+use crate::eta::Eta;
+use crate::event::Event;
+use crate::eventtype::EventType;
+
+/// Default formats for the date fields without a date filter.
+static DATE_FORMAT: &str = "%Y-%m-%d";
+static DATE_TIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// A value of the event that can be used in a template.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Field {
+    /// The event ID.
+    Id,
+    /// What the event is about.
+    Description,
+    /// The tags of the event, separated by commas.
+    Tags,
+    /// How important the event is: low, normal, high or critical.
+    Priority,
+    /// When the event is due: the day for all day events, the day and time
+    /// for the others.
+    Due,
+    /// The day the event is due.
+    Date,
+    /// The time the event is due, as `HH:MM`; empty for all day events.
+    Time,
+    /// When the event was created; empty if unknown.
+    Created,
+    /// When the event was last changed; empty if unknown.
+    Updated,
+    /// Who created the event; empty if unknown.
+    CreatedBy,
+    /// When the event was done; empty while it is not.
+    Completed,
+    /// Time left till the event, like in the listing.
+    Eta,
+    /// Whole days left till the event; empty if it is over.
+    EtaDays,
+    /// Hours left after the whole days; 0 for all day events and empty if
+    /// the event is over.
+    EtaHours,
+    /// How close the event is: overdue, today, soon or later.
+    Urgency,
+}
+
+/// The value of a field, before turning it into text.
+enum Value {
+    Text(String),
+    Date(NaiveDate),
+    DateTime(chrono::DateTime<Local>),
+}
+
+impl Value {
+    /// Format the date; fails if the format asks for a time and the value is
+    /// only a date.
+    fn format(&self, format: &str) -> Result<Value, TemplateError> {
+        let mut text = String::new();
+        let written = match self {
+            Value::Text(text) => return Ok(Value::Text(text.clone())),
+            Value::Date(date) => write!(text, "{}", date.format(format)),
+            Value::DateTime(datetime) => write!(text, "{}", datetime.format(format)),
+        };
+        written.map_err(|_| TemplateError::NoTime(format.into()))?;
+        Ok(Value::Text(text))
+    }
+
+    fn into_text(self) -> String {
+        match self {
+            Value::Text(text) => text,
+            Value::Date(date) => date.format(DATE_FORMAT).to_string(),
+            Value::DateTime(datetime) => datetime.format(DATE_TIME_FORMAT).to_string(),
+        }
+    }
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Field> {
+        match name {
+            "id" => Some(Field::Id),
+            "description" => Some(Field::Description),
+            "tags" => Some(Field::Tags),
+            "priority" => Some(Field::Priority),
+            "due" => Some(Field::Due),
+            "date" => Some(Field::Date),
+            "time" => Some(Field::Time),
+            "created" => Some(Field::Created),
+            "updated" => Some(Field::Updated),
+            "created_by" => Some(Field::CreatedBy),
+            "completed" => Some(Field::Completed),
+            "eta" => Some(Field::Eta),
+            "eta_days" => Some(Field::EtaDays),
+            "eta_hours" => Some(Field::EtaHours),
+            "urgency" => Some(Field::Urgency),
+            _ => None,
+        }
+    }
+
+    /// True for the fields that can go through date formats.
+    fn is_date(&self) -> bool {
+        matches!(
+            self,
+            Field::Due | Field::Date | Field::Created | Field::Updated | Field::Completed
+        )
+    }
+
+    fn value(&self, event: &Event, clock: &dyn Clock) -> Value {
+        match self {
+            Field::Id => Value::Text(event.id.clone()),
+            Field::Description => Value::Text(event.description.clone()),
+            Field::Tags => Value::Text(event.tags.join(",")),
+            Field::Priority => Value::Text(event.priority.to_string()),
+            Field::Due => match &event.due {
+                EventType::AllDay(date) => Value::Date(date.0),
+                EventType::AtTime(datetime) => Value::DateTime(datetime.0),
+            },
+            Field::Date => Value::Date(event.due.date().0),
+            Field::Time => match &event.due {
+                EventType::AllDay(_) => Value::Text(String::new()),
+                EventType::AtTime(datetime) => Value::Text(datetime.0.format("%H:%M").to_string()),
+            },
+            Field::Created => match &event.created_at {
+                None => Value::Text(String::new()),
+                Some(created) => Value::DateTime(created.0),
+            },
+            Field::Updated => match &event.updated_at {
+                None => Value::Text(String::new()),
+                Some(updated) => Value::DateTime(updated.0),
+            },
+            Field::CreatedBy => Value::Text(event.created_by.clone().unwrap_or_default()),
+            Field::Completed => match &event.completed_at {
+                None => Value::Text(String::new()),
+                Some(completed) => Value::DateTime(completed.0),
+            },
+            Field::Eta => Value::Text(event.eta(clock).to_string()),
+            Field::EtaDays => Value::Text(match event.eta(clock) {
+                Eta::Over => String::new(),
+                Eta::Days(days) | Eta::DaysAndHours(days, _) => days.to_string(),
+            }),
+            Field::EtaHours => Value::Text(match event.eta(clock) {
+                Eta::Over => String::new(),
+                Eta::Days(_) => "0".into(),
+                Eta::DaysAndHours(_, hours) => hours.to_string(),
+            }),
+            Field::Urgency => Value::Text(event.eta(clock).urgency().to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Filter {
+    Upper,
+    Lower,
+    DateFormat(String),
+}
+
+impl Filter {
+    fn apply(&self, value: Value) -> Result<Value, TemplateError> {
+        match self {
+            Filter::Upper => Ok(Value::Text(value.into_text().to_uppercase())),
+            Filter::Lower => Ok(Value::Text(value.into_text().to_lowercase())),
+            Filter::DateFormat(format) => value.format(format),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Piece {
+    Text(String),
+    Field(Field, Vec<Filter>),
+}
+
+/// A parsed template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    pieces: Vec<Piece>,
+}
+
+/// Errors when parsing templates.
+#[derive(Debug, PartialEq, Eq)]
+pub enum TemplateError {
+    /// There is no field with this name.
+    UnknownField(String),
+    /// There is no filter with this name.
+    UnknownFilter(String),
+    /// A date format was used on a field that is not a date.
+    NotADate(String),
+    /// The date format is not valid.
+    InvalidDateFormat(String),
+    /// The date format asks for a time, but the event has only a date.
+    NoTime(String),
+    /// A `{` without the matching `}`.
+    Unclosed,
+    /// A `}` without a `{` before it.
+    Unopened,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TemplateError::UnknownField(name) => write!(f, "unknown template field '{}'", name),
+            TemplateError::UnknownFilter(name) => write!(f, "unknown template filter '{}'", name),
+            TemplateError::NotADate(name) => {
+                write!(
+                    f,
+                    "template field '{}' is not a date and can't be formatted as one",
+                    name
+                )
+            }
+            TemplateError::InvalidDateFormat(format) => {
+                write!(f, "invalid date format '{}'", format)
+            }
+            TemplateError::NoTime(format) => write!(
+                f,
+                "date format '{}' needs a time, but the event is all day",
+                format
+            ),
+            TemplateError::Unclosed => write!(f, "template has a '{{' without a '}}'"),
+            TemplateError::Unopened => {
+                write!(
+                    f,
+                    "template has a '}}' without a '{{'; use '}}}}' for a brace"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+/// Parse the inside of the braces: a field name and its filters.
+fn placeholder(content: &str) -> Result<Piece, TemplateError> {
+    let mut parts = content.split('|');
+    let name = parts.next().unwrap_or_default().trim();
+    let field = Field::from_name(name).ok_or_else(|| TemplateError::UnknownField(name.into()))?;
+
+    // Date formats turn dates into text, so they can only come first.
+    let mut is_date = field.is_date();
+    let mut filters = Vec::new();
+    for filter in parts {
+        let filter = match filter.trim() {
+            "upper" => Filter::Upper,
+            "lower" => Filter::Lower,
+            format if format.contains('%') => {
+                if !is_date {
+                    return Err(TemplateError::NotADate(name.into()));
+                }
+                if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+                    return Err(TemplateError::InvalidDateFormat(format.into()));
+                }
+                Filter::DateFormat(format.into())
+            }
+            other => return Err(TemplateError::UnknownFilter(other.into())),
+        };
+        is_date = false;
+        filters.push(filter);
+    }
+    Ok(Piece::Field(field, filters))
+}
+
+impl Template {
+    /// Parse the template text.
+    pub fn parse(text: &str) -> Result<Self, TemplateError> {
+        let mut pieces = Vec::new();
+        let mut literal = String::new();
+        let mut chars = text.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '}' => return Err(TemplateError::Unopened),
+                '{' => {
+                    let mut content = String::new();
+                    loop {
+                        match chars.next() {
+                            None => return Err(TemplateError::Unclosed),
+                            Some('}') => break,
+                            Some(c) => content.push(c),
+                        }
+                    }
+                    if !literal.is_empty() {
+                        pieces.push(Piece::Text(std::mem::take(&mut literal)));
+                    }
+                    pieces.push(placeholder(&content)?);
+                }
+                '\\' => match chars.peek() {
+                    Some('t') => {
+                        chars.next();
+                        literal.push('\t');
+                    }
+                    Some('n') => {
+                        chars.next();
+                        literal.push('\n');
+                    }
+                    Some('\\') => {
+                        chars.next();
+                        literal.push('\\');
+                    }
+                    _ => literal.push('\\'),
+                },
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            pieces.push(Piece::Text(literal));
+        }
+        Ok(Self { pieces })
+    }
+
+    /// Fill the template with the values of the event; fails if a date
+    /// format asks for the time of a date.
+    pub fn render(&self, event: &Event, clock: &dyn Clock) -> Result<String, TemplateError> {
+        self.pieces
+            .iter()
+            .map(|piece| match piece {
+                Piece::Text(text) => Ok(text.clone()),
+                Piece::Field(field, filters) => Ok(filters
+                    .iter()
+                    .try_fold(field.value(event, clock), |value, filter| {
+                        filter.apply(value)
+                    })?
+                    .into_text()),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod template_test {
+    use super::Template;
+    use super::TemplateError;
+    use crate::clock::FixedClock;
+    use crate::date::Date;
+    use crate::datetime::DateTime;
+    use crate::event::Event;
+    use crate::priority::Priority;
+
+    fn all_day() -> Event {
+        let mut event =
+            Event::new_on_date("1a", "Pay taxes", &Date::new(2020, 6, 13).unwrap()).unwrap();
+        event.tags = vec!["home".into(), "money".into()];
+        event
+    }
+
+    fn at_time() -> Event {
+        Event::new_on_date_time("2", "Call", &DateTime::new(2020, 6, 11, 14, 5).unwrap()).unwrap()
+    }
+
+    fn render(template: &str, event: &Event) -> String {
+        Template::parse(template)
+            .unwrap()
+            .render(event, &FixedClock::at(2020, 6, 10, 12, 0))
+            .unwrap()
+    }
+
+    #[test]
+    pub fn render_fields() {
+        assert_eq!(
+            render("{id}: {description} in {eta} ({date}, {tags})", &all_day()),
+            "1a: Pay taxes in 3d (2020-06-13, home,money)"
+        );
+    }
+
+    #[test]
+    pub fn render_due() {
+        assert_eq!(render("{due} [{time}]", &all_day()), "2020-06-13 []");
+        assert_eq!(
+            render("{due} [{time}]", &at_time()),
+            "2020-06-11 14:05 [14:05]"
+        );
+    }
+
+    #[test]
+    pub fn render_eta_values() {
+        let template = "{eta_days}/{eta_hours}/{urgency}";
+        assert_eq!(render(template, &all_day()), "3/0/soon");
+        assert_eq!(render(template, &at_time()), "1/2/soon");
+
+        let past = Event::new_on_date("3", "Gone", &Date::new(2020, 6, 1).unwrap()).unwrap();
+        assert_eq!(render(template, &past), "//overdue");
+    }
+
+    #[test]
+    pub fn render_created() {
+        let mut event = all_day();
+        assert_eq!(render("[{created}]", &event), "[]");
+        event.created_at = Some(DateTime::new(2020, 5, 1, 9, 30).unwrap());
+        assert_eq!(render("[{created|%d/%m}]", &event), "[01/05]");
+    }
+
+    #[test]
+    pub fn render_updated_and_creator() {
+        let mut event = all_day();
+        assert_eq!(render("[{updated}][{created_by}]", &event), "[][]");
+        event.updated_at = Some(DateTime::new(2020, 5, 2, 9, 30).unwrap());
+        event.created_by = Some("julio".into());
+        event.priority = Priority::High;
+        assert_eq!(
+            render("[{updated|%d/%m}][{created_by|upper}] {priority}", &event),
+            "[02/05][JULIO] high"
+        );
+        assert_eq!(render("[{completed}]", &event), "[]");
+        event.completed_at = Some(DateTime::new(2020, 5, 3, 9, 30).unwrap());
+        assert_eq!(render("[{completed|%d/%m}]", &event), "[03/05]");
+    }
+
+    #[test]
+    pub fn filters() {
+        assert_eq!(render("{due|%a %d %b}", &all_day()), "Sat 13 Jun");
+        assert_eq!(render("{due|%H:%M}", &at_time()), "14:05");
+        assert_eq!(render("{due|%b|upper}", &all_day()), "JUN");
+        assert_eq!(render("{description|lower}", &all_day()), "pay taxes");
+        for (template, format) in [("{due|%H:%M}", "%H:%M"), ("{date|%H}", "%H")] {
+            assert_eq!(
+                Template::parse(template)
+                    .unwrap()
+                    .render(&all_day(), &FixedClock::at(2020, 6, 10, 12, 0)),
+                Err(TemplateError::NoTime(format.into()))
+            );
+        }
+    }
+
+    #[test]
+    pub fn escapes() {
+        assert_eq!(render("{{{id}}}", &all_day()), "{1a}");
+        assert_eq!(render(r"{id}\t{eta}\n\\\x", &all_day()), "1a\t3d\n\\\\x");
+    }
+
+    #[test]
+    pub fn broken_templates() {
+        assert_eq!(
+            Template::parse("{nope}"),
+            Err(TemplateError::UnknownField("nope".into()))
+        );
+        assert_eq!(
+            Template::parse("{id|shout}"),
+            Err(TemplateError::UnknownFilter("shout".into()))
+        );
+        assert_eq!(
+            Template::parse("{id|%d}"),
+            Err(TemplateError::NotADate("id".into()))
+        );
+        assert_eq!(
+            Template::parse("{due|upper|%d}"),
+            Err(TemplateError::NotADate("due".into()))
+        );
+        assert_eq!(
+            Template::parse("{due|%Q}"),
+            Err(TemplateError::InvalidDateFormat("%Q".into()))
+        );
+        assert_eq!(Template::parse("{id"), Err(TemplateError::Unclosed));
+        assert_eq!(Template::parse("id}"), Err(TemplateError::Unopened));
+    }
+}
@@ -0,0 +1,776 @@
+/*
+   TU - Time's Up!
+   Copyright (C) 2020  Julio Biason
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as published by
+   the Free Software Foundation, either version 3 of the License, or
+   (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Full screen, interactive view of the events.
+
+use std::error::Error;
+use std::io;
+use std::time::Duration;
+use std::time::Instant;
+
+use ratatui::backend::CrosstermBackend;
+use ratatui::crossterm::cursor::Show;
+use ratatui::crossterm::event as term;
+use ratatui::crossterm::event::KeyCode;
+use ratatui::crossterm::event::KeyEventKind;
+use ratatui::crossterm::execute;
+use ratatui::crossterm::terminal::disable_raw_mode;
+use ratatui::crossterm::terminal::enable_raw_mode;
+use ratatui::crossterm::terminal::EnterAlternateScreen;
+use ratatui::crossterm::terminal::LeaveAlternateScreen;
+use ratatui::layout::Constraint;
+use ratatui::layout::Layout;
+use ratatui::layout::Rect;
+use ratatui::style::Color;
+use ratatui::style::Modifier;
+use ratatui::style::Style;
+use ratatui::text::Line;
+use ratatui::text::Span;
+use ratatui::widgets::Block;
+use ratatui::widgets::Borders;
+use ratatui::widgets::Cell;
+use ratatui::widgets::Clear;
+use ratatui::widgets::Paragraph;
+use ratatui::widgets::Row;
+use ratatui::widgets::Table;
+use ratatui::widgets::TableState;
+use ratatui::Frame;
+use ratatui::Terminal;
+
+use tu::eta::Urgency;
+use tu::Clock;
+use tu::Date;
+use tu::DateError;
+use tu::DateTime;
+use tu::Event;
+use tu::EventList;
+use tu::EventType;
+use tu::IdGenerator;
+use tu::NewEvent;
+use tu::Priority;
+use tu::Storage;
+
+/// How often the screen is redrawn, so the countdowns keep moving.
+const TICK: Duration = Duration::from_secs(1);
+
+/// How often the events are read again, to pick changes done elsewhere.
+const RELOAD: Duration = Duration::from_secs(30);
+
+/// Names of the fields in the add/edit form, in order.
+const FIELDS: [&str; 5] = ["Date", "Time", "Description", "Tags", "Priority"];
+const DATE: usize = 0;
+const TIME: usize = 1;
+const DESCRIPTION: usize = 2;
+const TAGS: usize = 3;
+const PRIORITY: usize = 4;
+
+const HELP: &str = "j/k move  a add  e edit  x done  d delete  t tag  o over  r reload  q quit";
+
+/// The add/edit form.
+#[derive(Debug, Default)]
+struct Form {
+    /// ID of the event being edited; None when adding a new one.
+    editing: Option<String>,
+    fields: [String; 5],
+    focus: usize,
+}
+
+impl Form {
+    fn edit(event: &Event) -> Self {
+        let time = match &event.due {
+            EventType::AllDay(_) => String::new(),
+            EventType::AtTime(datetime) => {
+                format!("{:02}:{:02}", datetime.hour(), datetime.minute())
+            }
+        };
+        Self {
+            editing: Some(event.id.clone()),
+            fields: [
+                event.due.date().to_string(),
+                time,
+                event.description.clone(),
+                event.tags.join(", "),
+                event.priority.to_string(),
+            ],
+            focus: DESCRIPTION,
+        }
+    }
+
+    fn due(&self) -> Result<EventType, String> {
+        let date = self.fields[DATE].trim();
+        let time = self.fields[TIME].trim();
+        let invalid = |error: DateError| error.to_string();
+        if time.is_empty() {
+            Ok(EventType::AllDay(Date::try_from(date).map_err(invalid)?))
+        } else {
+            Ok(EventType::AtTime(
+                DateTime::try_from(date, time).map_err(invalid)?,
+            ))
+        }
+    }
+
+    fn tags(&self) -> Vec<String> {
+        self.fields[TAGS]
+            .split(',')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(String::from)
+            .collect()
+    }
+
+    /// The priority in the form; empty is normal.
+    fn priority(&self) -> Result<Priority, String> {
+        let priority = self.fields[PRIORITY].trim();
+        if priority.is_empty() {
+            return Ok(Priority::Normal);
+        }
+        Priority::from_name(priority).ok_or_else(|| {
+            format!(
+                "invalid priority: {}; use low, normal, high or critical",
+                priority
+            )
+        })
+    }
+}
+
+#[derive(Debug)]
+enum Mode {
+    Browse,
+    Form(Form),
+    /// Waiting for the user to confirm removing the event with this ID.
+    ConfirmDelete(String),
+}
+
+/// State of the interactive view.
+pub struct App {
+    list: EventList,
+    selected: usize,
+    /// Only show events with this tag.
+    tag: Option<String>,
+    /// Show events that are already over or done.
+    show_over: bool,
+    mode: Mode,
+    /// Result of the last action, shown in place of the help line.
+    message: Option<String>,
+    quit: bool,
+}
+
+impl App {
+    pub fn new(list: EventList) -> Self {
+        Self {
+            list,
+            selected: 0,
+            tag: None,
+            show_over: false,
+            mode: Mode::Browse,
+            message: None,
+            quit: false,
+        }
+    }
+
+    /// The events on screen, after the tag and "over" filters.
+    fn visible(&self, clock: &dyn Clock) -> Vec<&Event> {
+        self.list
+            .iter()
+            .filter(|event| self.show_over || !(event.eta(clock).is_over() || event.is_done()))
+            .filter(|event| self.tag.as_ref().is_none_or(|tag| event.tags.contains(tag)))
+            .collect()
+    }
+
+    fn current(&self, clock: &dyn Clock) -> Option<&Event> {
+        self.visible(clock).get(self.selected).copied()
+    }
+
+    /// All tags in use, sorted.
+    fn tags(&self) -> Vec<&str> {
+        let mut tags: Vec<&str> = self
+            .list
+            .iter()
+            .flat_map(|event| event.tags.iter().map(String::as_str))
+            .collect();
+        tags.sort_unstable();
+        tags.dedup();
+        tags
+    }
+
+    /// Keep the selection inside the visible events.
+    fn clamp(&mut self, clock: &dyn Clock) {
+        let count = self.visible(clock).len();
+        self.selected = self.selected.min(count.saturating_sub(1));
+    }
+
+    fn reload(&mut self, storage: &dyn Storage, clock: &dyn Clock) {
+        match storage.load() {
+            Ok(list) => self.list = list,
+            Err(error) => self.message = Some(format!("Error: {}", error)),
+        }
+        self.clamp(clock);
+    }
+
+    /// Move to the next tag filter; after the last tag, show all events again.
+    fn next_tag(&mut self) {
+        let tags = self.tags();
+        let next = match &self.tag {
+            None => tags.first(),
+            Some(current) => tags
+                .iter()
+                .position(|tag| tag == current)
+                .and_then(|position| tags.get(position + 1)),
+        };
+        self.tag = next.map(|tag| tag.to_string());
+    }
+
+    pub fn handle_key(
+        &mut self,
+        key: KeyCode,
+        storage: &mut dyn Storage,
+        ids: &mut dyn IdGenerator,
+        clock: &dyn Clock,
+    ) {
+        match std::mem::replace(&mut self.mode, Mode::Browse) {
+            Mode::Browse => self.browse_key(key, storage, clock),
+            Mode::Form(form) => self.form_key(form, key, storage, ids, clock),
+            Mode::ConfirmDelete(id) => {
+                if key == KeyCode::Char('y') {
+                    self.message = Some(match EventList::remove_by_id(storage, &id) {
+                        Ok(description) => format!("Removed event '{}'", description),
+                        Err(error) => format!("Error: {}", error),
+                    });
+                    self.reload(storage, clock);
+                }
+            }
+        }
+    }
+
+    fn browse_key(&mut self, key: KeyCode, storage: &mut dyn Storage, clock: &dyn Clock) {
+        self.message = None;
+        match key {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Char('j') | KeyCode::Down => self.selected += 1,
+            KeyCode::Char('k') | KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Char('g') | KeyCode::Home => self.selected = 0,
+            KeyCode::Char('G') | KeyCode::End => self.selected = usize::MAX,
+            KeyCode::Char('a') => self.mode = Mode::Form(Form::default()),
+            KeyCode::Char('e') | KeyCode::Enter => {
+                if let Some(event) = self.current(clock) {
+                    self.mode = Mode::Form(Form::edit(event));
+                }
+            }
+            KeyCode::Char('d') | KeyCode::Delete => {
+                if let Some(event) = self.current(clock) {
+                    self.mode = Mode::ConfirmDelete(event.id.clone());
+                }
+            }
+            KeyCode::Char('x') => {
+                if let Some(event) = self.current(clock) {
+                    let id = event.id.clone();
+                    self.message = Some(
+                        match event.is_done() {
+                            true => EventList::reopen(storage, clock, &id).map(|event| {
+                                format!("'{}' is not done anymore", event.description)
+                            }),
+                            false => EventList::complete(storage, clock, &id)
+                                .map(|event| format!("Done '{}'", event.description)),
+                        }
+                        .unwrap_or_else(|error| format!("Error: {}", error)),
+                    );
+                    self.reload(storage, clock);
+                }
+            }
+            KeyCode::Char('t') => {
+                self.next_tag();
+                self.selected = 0;
+            }
+            KeyCode::Char('o') => self.show_over = !self.show_over,
+            KeyCode::Char('r') => self.reload(storage, clock),
+            _ => {}
+        }
+        self.clamp(clock);
+    }
+
+    fn form_key(
+        &mut self,
+        mut form: Form,
+        key: KeyCode,
+        storage: &mut dyn Storage,
+        ids: &mut dyn IdGenerator,
+        clock: &dyn Clock,
+    ) {
+        match key {
+            KeyCode::Esc => return,
+            KeyCode::Tab | KeyCode::Down => form.focus = (form.focus + 1) % FIELDS.len(),
+            KeyCode::BackTab | KeyCode::Up => {
+                form.focus = (form.focus + FIELDS.len() - 1) % FIELDS.len()
+            }
+            KeyCode::Backspace => {
+                form.fields[form.focus].pop();
+            }
+            KeyCode::Char(c) => form.fields[form.focus].push(c),
+            KeyCode::Enter => match self.save(&form, storage, ids, clock) {
+                Ok(message) => {
+                    self.message = Some(message);
+                    self.reload(storage, clock);
+                    return;
+                }
+                Err(error) => self.message = Some(format!("Error: {}", error)),
+            },
+            _ => {}
+        }
+        self.mode = Mode::Form(form);
+    }
+
+    fn save(
+        &self,
+        form: &Form,
+        storage: &mut dyn Storage,
+        ids: &mut dyn IdGenerator,
+        clock: &dyn Clock,
+    ) -> Result<String, String> {
+        let description = form.fields[DESCRIPTION].trim();
+        if description.is_empty() {
+            return Err("the event needs a description".into());
+        }
+        let due = form.due()?;
+        let priority = form.priority()?;
+
+        match &form.editing {
+            None => {
+                let new = NewEvent {
+                    description: description.into(),
+                    due,
+                    tags: form.tags(),
+                    priority,
+                };
+                let id = EventList::add(storage, ids, clock, new).map_err(|e| e.to_string())?;
+                Ok(format!("Created new event {}", id))
+            }
+            Some(id) => {
+                let mut event = self
+                    .list
+                    .get(id)
+                    .ok_or_else(|| "no such event".to_string())?
+                    .clone();
+                event.description = description.into();
+                event.due = due;
+                event.tags = form.tags();
+                event.priority = priority;
+                EventList::edit(storage, clock, event).map_err(|e| e.to_string())?;
+                Ok(format!("Changed event {}", id))
+            }
+        }
+    }
+}
+
+/// Run the interactive view till the user quits.
+pub fn run(
+    storage: &mut dyn Storage,
+    ids: &mut dyn IdGenerator,
+    clock: &dyn Clock,
+) -> Result<(), Box<dyn Error>> {
+    let mut app = App::new(storage.load()?);
+
+    let _screen = Screen::enter()?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+    event_loop(&mut terminal, &mut app, storage, ids, clock)
+}
+
+/// Raw mode and the alternate screen, restored when dropped, so the terminal
+/// is usable again after an error. Panics restore it before the message is
+/// printed, or the message would vanish with the alternate screen.
+struct Screen;
+
+impl Screen {
+    fn enter() -> io::Result<Screen> {
+        enable_raw_mode()?;
+        let screen = Screen;
+        execute!(io::stdout(), EnterAlternateScreen)?;
+
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            Screen::restore();
+            previous(info);
+        }));
+        Ok(screen)
+    }
+
+    fn restore() {
+        // Nothing else to do if the terminal refuses; restoring twice is
+        // harmless.
+        let _ = execute!(io::stdout(), LeaveAlternateScreen, Show);
+        let _ = disable_raw_mode();
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        // The hook can't be changed while unwinding, and it already ran.
+        if !std::thread::panicking() {
+            let _ = std::panic::take_hook();
+        }
+        Screen::restore();
+    }
+}
+
+fn event_loop(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    app: &mut App,
+    storage: &mut dyn Storage,
+    ids: &mut dyn IdGenerator,
+    clock: &dyn Clock,
+) -> Result<(), Box<dyn Error>> {
+    let mut last_reload = Instant::now();
+    while !app.quit {
+        terminal.draw(|frame| draw(frame, app, clock))?;
+
+        if term::poll(TICK)? {
+            if let term::Event::Key(key) = term::read()? {
+                if key.kind == KeyEventKind::Press {
+                    app.handle_key(key.code, storage, ids, clock);
+                }
+            }
+        }
+
+        if matches!(app.mode, Mode::Browse) && last_reload.elapsed() >= RELOAD {
+            app.reload(storage, clock);
+            last_reload = Instant::now();
+        }
+    }
+    Ok(())
+}
+
+fn urgency_style(urgency: Urgency) -> Style {
+    match urgency {
+        Urgency::Overdue => Style::default().fg(Color::Red),
+        Urgency::Today => Style::default().fg(Color::Yellow),
+        Urgency::Soon => Style::default().fg(Color::Cyan),
+        Urgency::Later => Style::default(),
+    }
+}
+
+/// The urgency color, in bold for the important events.
+fn event_style(event: &Event, urgency: Urgency) -> Style {
+    let style = urgency_style(urgency);
+    if event.priority.is_high() {
+        style.add_modifier(Modifier::BOLD)
+    } else {
+        style
+    }
+}
+
+fn due(event: &Event) -> String {
+    match &event.due {
+        EventType::AllDay(date) => date.to_string(),
+        EventType::AtTime(datetime) => format!(
+            "{} {:02}:{:02}",
+            event.due.date(),
+            datetime.hour(),
+            datetime.minute()
+        ),
+    }
+}
+
+fn draw(frame: &mut Frame, app: &App, clock: &dyn Clock) {
+    let [main, footer] =
+        Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+
+    let events = app.visible(clock);
+    let id_width = events
+        .iter()
+        .map(|event| event.id.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(2) as u16;
+    let rows = events.iter().map(|event| {
+        let eta = event.eta(clock);
+        let (eta_text, style) = match event.is_done() {
+            true => ("Done".to_string(), Style::default()),
+            false => (eta.to_string(), event_style(event, eta.urgency())),
+        };
+        Row::new(vec![
+            Cell::from(event.id.as_str()),
+            Cell::from(eta_text),
+            Cell::from(due(event)),
+            Cell::from(event.description.as_str()),
+            Cell::from(event.tags.join(", ")),
+        ])
+        .style(style)
+    });
+
+    let mut title = format!(" tu: {} events ", events.len());
+    if let Some(tag) = &app.tag {
+        title.push_str(&format!("[tag: {}] ", tag));
+    }
+    if app.show_over {
+        title.push_str("[with over and done] ");
+    }
+
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(id_width),
+            Constraint::Length(8),
+            Constraint::Length(16),
+            Constraint::Fill(3),
+            Constraint::Fill(1),
+        ],
+    )
+    .header(
+        Row::new(vec!["ID", "ETA", "Due", "Description", "Tags"])
+            .style(Style::default().add_modifier(Modifier::BOLD)),
+    )
+    .block(Block::default().borders(Borders::ALL).title(title))
+    .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+    let mut state = TableState::default();
+    if !events.is_empty() {
+        state.select(Some(app.selected));
+    }
+    frame.render_stateful_widget(table, main, &mut state);
+
+    let status = match (&app.mode, &app.message) {
+        (Mode::ConfirmDelete(id), _) => format!("Remove event {}? (y/n)", id),
+        (_, Some(message)) => message.clone(),
+        (Mode::Form(_), None) => "Tab next field  Enter save  Esc cancel".into(),
+        (Mode::Browse, None) => HELP.into(),
+    };
+    frame.render_widget(Paragraph::new(status), footer);
+
+    if let Mode::Form(form) = &app.mode {
+        draw_form(frame, form, main);
+    }
+}
+
+fn draw_form(frame: &mut Frame, form: &Form, area: Rect) {
+    let width = area.width.min(60);
+    let height = (FIELDS.len() as u16 + 2).min(area.height);
+    let popup = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+
+    let label_width = FIELDS.iter().map(|label| label.len()).max().unwrap_or(0);
+    let lines: Vec<Line> = FIELDS
+        .iter()
+        .zip(form.fields.iter())
+        .enumerate()
+        .map(|(index, (label, value))| {
+            let style = if index == form.focus {
+                Style::default().add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            Line::from(vec![
+                Span::styled(format!("{:>width$}: ", label, width = label_width), style),
+                Span::raw(value.as_str()),
+            ])
+        })
+        .collect();
+
+    let title = match &form.editing {
+        None => " New event ".to_string(),
+        Some(id) => format!(" Edit event {} ", id),
+    };
+    frame.render_widget(Clear, popup);
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(title)),
+        popup,
+    );
+
+    let cursor = form.fields[form.focus].chars().count() + label_width + 2;
+    frame.set_cursor_position((
+        popup.x + 1 + (cursor as u16).min(width.saturating_sub(3)),
+        popup.y + 1 + form.focus as u16,
+    ));
+}
+
+#[cfg(test)]
+mod tui_test {
+    use ratatui::crossterm::event::KeyCode;
+
+    use tu::idgen::Sequential;
+    use tu::storage::Memory;
+    use tu::Date;
+    use tu::EventList;
+    use tu::NewEvent;
+    use tu::Priority;
+    use tu::Storage;
+
+    use super::App;
+    use super::Mode;
+    use crate::test_clock;
+
+    fn storage() -> Memory {
+        let mut storage = Memory::new();
+        let events = [
+            ("past", 5, vec![]),
+            ("first", 11, vec!["work"]),
+            ("second", 12, vec!["home"]),
+            ("third", 13, vec!["work"]),
+        ];
+        for (description, day, tags) in events {
+            let new = NewEvent::on_date(description, &Date::new(2020, 6, day).unwrap())
+                .with_tags(tags.into_iter().map(String::from).collect());
+            EventList::add(&mut storage, &mut Sequential, &test_clock(), new).unwrap();
+        }
+        storage
+    }
+
+    fn press(app: &mut App, storage: &mut Memory, keys: &[KeyCode]) {
+        for key in keys {
+            app.handle_key(*key, storage, &mut Sequential, &test_clock());
+        }
+    }
+
+    fn type_text(app: &mut App, storage: &mut Memory, text: &str) {
+        let keys: Vec<KeyCode> = text.chars().map(KeyCode::Char).collect();
+        press(app, storage, &keys);
+    }
+
+    fn descriptions(app: &App) -> Vec<&str> {
+        app.visible(&test_clock())
+            .iter()
+            .map(|event| event.description.as_str())
+            .collect()
+    }
+
+    #[test]
+    pub fn hides_over_events() {
+        let mut storage = storage();
+        let mut app = App::new(storage.load().unwrap());
+        assert_eq!(descriptions(&app), vec!["first", "second", "third"]);
+
+        press(&mut app, &mut storage, &[KeyCode::Char('o')]);
+        assert_eq!(descriptions(&app), vec!["past", "first", "second", "third"]);
+    }
+
+    #[test]
+    pub fn done_events() {
+        let mut storage = storage();
+        let mut app = App::new(storage.load().unwrap());
+        press(&mut app, &mut storage, &[KeyCode::Char('x')]);
+        assert!(storage.load().unwrap().get("2").unwrap().is_done());
+        assert_eq!(descriptions(&app), vec!["second", "third"]);
+
+        press(&mut app, &mut storage, &[KeyCode::Char('o'), KeyCode::Down]);
+        assert_eq!(app.current(&test_clock()).unwrap().description, "first");
+        press(&mut app, &mut storage, &[KeyCode::Char('x')]);
+        assert!(!storage.load().unwrap().get("2").unwrap().is_done());
+    }
+
+    #[test]
+    pub fn navigation_stays_inside() {
+        let mut storage = storage();
+        let mut app = App::new(storage.load().unwrap());
+        press(&mut app, &mut storage, &[KeyCode::Up]);
+        assert_eq!(app.selected, 0);
+        press(&mut app, &mut storage, &[KeyCode::Down; 10]);
+        assert_eq!(app.selected, 2);
+        press(&mut app, &mut storage, &[KeyCode::Char('g')]);
+        assert_eq!(app.selected, 0);
+    }
+
+    #[test]
+    pub fn cycle_tags() {
+        let mut storage = storage();
+        let mut app = App::new(storage.load().unwrap());
+        press(&mut app, &mut storage, &[KeyCode::Char('t')]);
+        assert_eq!(descriptions(&app), vec!["second"]);
+        press(&mut app, &mut storage, &[KeyCode::Char('t')]);
+        assert_eq!(descriptions(&app), vec!["first", "third"]);
+        press(&mut app, &mut storage, &[KeyCode::Char('t')]);
+        assert_eq!(descriptions(&app).len(), 3);
+    }
+
+    #[test]
+    pub fn add_event() {
+        let mut storage = storage();
+        let mut app = App::new(storage.load().unwrap());
+        press(&mut app, &mut storage, &[KeyCode::Char('a')]);
+        type_text(&mut app, &mut storage, "2020-06-20");
+        press(&mut app, &mut storage, &[KeyCode::Tab, KeyCode::Tab]);
+        type_text(&mut app, &mut storage, "new one");
+        press(&mut app, &mut storage, &[KeyCode::Tab]);
+        type_text(&mut app, &mut storage, "work, later");
+        press(&mut app, &mut storage, &[KeyCode::Tab]);
+        type_text(&mut app, &mut storage, "high");
+        press(&mut app, &mut storage, &[KeyCode::Enter]);
+
+        assert!(matches!(app.mode, Mode::Browse));
+        let list = storage.load().unwrap();
+        let event = list.get("5").unwrap();
+        assert_eq!(event.description, "new one");
+        assert_eq!(event.tags, vec!["work", "later"]);
+        assert_eq!(event.priority, Priority::High);
+        assert_eq!(descriptions(&app).last(), Some(&"new one"));
+    }
+
+    #[test]
+    pub fn add_invalid_date_keeps_form() {
+        let mut storage = storage();
+        let mut app = App::new(storage.load().unwrap());
+        press(&mut app, &mut storage, &[KeyCode::Char('a')]);
+        type_text(&mut app, &mut storage, "2020-13-20");
+        press(&mut app, &mut storage, &[KeyCode::Tab, KeyCode::Tab]);
+        type_text(&mut app, &mut storage, "broken");
+        press(&mut app, &mut storage, &[KeyCode::Enter]);
+
+        assert!(matches!(app.mode, Mode::Form(_)));
+        assert_eq!(storage.load().unwrap().len(), 4);
+        assert_eq!(
+            app.message.as_deref(),
+            Some("Error: there is no day 2020-13-20")
+        );
+    }
+
+    #[test]
+    pub fn edit_event() {
+        let mut storage = storage();
+        let mut app = App::new(storage.load().unwrap());
+        press(&mut app, &mut storage, &[KeyCode::Down, KeyCode::Char('e')]);
+        press(&mut app, &mut storage, &[KeyCode::Backspace; 6]);
+        type_text(&mut app, &mut storage, "changed");
+        press(&mut app, &mut storage, &[KeyCode::Enter]);
+
+        assert_eq!(
+            storage.load().unwrap().get("3").unwrap().description,
+            "changed"
+        );
+    }
+
+    #[test]
+    pub fn delete_needs_confirmation() {
+        let mut storage = storage();
+        let mut app = App::new(storage.load().unwrap());
+        press(
+            &mut app,
+            &mut storage,
+            &[KeyCode::Char('d'), KeyCode::Char('n')],
+        );
+        assert_eq!(storage.load().unwrap().len(), 4);
+
+        press(
+            &mut app,
+            &mut storage,
+            &[KeyCode::Char('d'), KeyCode::Char('y')],
+        );
+        assert!(storage.load().unwrap().get("2").is_none());
+        assert_eq!(descriptions(&app), vec!["second", "third"]);
+    }
+}